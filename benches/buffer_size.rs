@@ -0,0 +1,64 @@
+//! Сравнение пропускной способности --stream при разных --buffer-size на
+//! большом файле. Запуск: `cargo bench --bench buffer_size`. Каждый размер
+//! прогоняется несколько раз, в таблицу попадает лучшее время.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Размер входного файла
+const INPUT_SIZE: usize = 32 * 1024 * 1024;
+
+const BUFFER_SIZES: [usize; 5] = [4 * 1024, 16 * 1024, 64 * 1024, 256 * 1024, 1024 * 1024];
+
+const RUNS: usize = 3;
+
+fn main() {
+    let dir = tempfile::tempdir().expect("не удалось создать временный каталог");
+    let key = dir.path().join("key.txt");
+    let input = dir.path().join("input.txt");
+    let output = dir.path().join("output.txt");
+
+    let alphabet: Vec<char> = "абвгдеёжзийклмнопрстуфхцчшщъыьэюя".chars().collect();
+    let pairs: String = alphabet
+        .iter()
+        .zip(alphabet.iter().cycle().skip(3))
+        .map(|(key, value)| format!("{} = {}\n", key, value))
+        .collect();
+    fs::write(&key, pairs).unwrap();
+
+    let line = "съешь же ещё этих мягких французских булок, да выпей чаю\n";
+    fs::write(&input, line.repeat(INPUT_SIZE / line.len())).unwrap();
+    let megabytes = fs::metadata(&input).unwrap().len() as f64 / (1024.0 * 1024.0);
+
+    println!("Входной файл: {:.1} МиБ, лучшее время из {} запусков", megabytes, RUNS);
+    println!("{:>13}  {:>10}  {:>10}", "--buffer-size", "Время", "МиБ/с");
+    for size in BUFFER_SIZES {
+        let best = (0..RUNS).map(|_| run(&key, &input, &output, size)).min().unwrap();
+        println!(
+            "{:>13}  {:>7.1} мс  {:>10.1}",
+            size,
+            best.as_secs_f64() * 1000.0,
+            megabytes / best.as_secs_f64()
+        );
+    }
+}
+
+fn run(key: &Path, input: &Path, output: &Path, buffer_size: usize) -> Duration {
+    let started = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_cipher"))
+        .arg("-a")
+        .arg(key)
+        .arg("-i")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .args(["-e", "--stream", "--buffer-size", &buffer_size.to_string()])
+        .stdout(std::process::Stdio::null())
+        .status()
+        .expect("не удалось запустить cipher");
+    let elapsed = started.elapsed();
+    assert!(status.success(), "cipher завершился с ошибкой при --buffer-size {}", buffer_size);
+    elapsed
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use cipher::Direction;
+
+use crate::modes::Mode;
+use crate::profile::Profiler;
+use crate::{check_output_size, copy_metadata, read_input_file, strip_header, text_header, transform, Args};
+
+/// Обрабатывает все файлы, подходящие под шаблон, и сохраняет результаты
+/// в каталог `--output`, повторяя структуру подкаталогов относительно
+/// неизменной части шаблона
+pub fn run_glob(args: &Args, pattern: &str, direction: Direction) -> Result<()> {
+    let output_dir = Path::new(args.output.as_deref().context("Для --input-glob укажите каталог --output")?);
+    let mut profiler = Profiler::new(args.profile);
+    let mode = Mode::from_args(args);
+    let alphabets = profiler.stage("read", || mode.read_alphabets(args))?;
+    let mut engine = profiler.stage("load", || mode.load(args, &alphabets))?;
+    let header = text_header(args, &alphabets)?;
+    let base = glob_base(pattern);
+
+    let paths = glob::glob(pattern)
+        .with_context(|| format!("Некорректный шаблон: {}", pattern))?;
+
+    let mut processed = 0;
+    let mut written: u64 = 0;
+    let mut skipped = 0;
+
+    for entry in paths {
+        let path = match entry {
+            Ok(path) if path.is_file() => path,
+            Ok(_) => continue,
+            Err(err) => {
+                skip(args, err.into(), &mut skipped)?;
+                continue;
+            }
+        };
+
+        let input_text = match profiler.stage("read", || read_input_file(args, &path.to_string_lossy())) {
+            Ok(text) => text,
+            Err(err) => {
+                skip(args, err, &mut skipped)?;
+                continue;
+            }
+        };
+
+        let result = match (&header, direction) {
+            (Some(expected), Direction::Decrypt) => strip_header(&input_text, expected)
+                .and_then(|text| transform(args, &mut engine, direction, text.to_string(), &mut profiler)),
+            (Some(header), Direction::Encrypt) => transform(args, &mut engine, direction, input_text, &mut profiler)
+                .map(|result| format!("{}\n{}", header, result)),
+            (None, _) => transform(args, &mut engine, direction, input_text, &mut profiler),
+        }
+        .with_context(|| format!("Ошибка при обработке файла: {}", path.display()))?;
+
+        // Ограничение --max-output-size действует на все файлы вместе
+        written += result.len() as u64;
+        check_output_size(args, written)
+            .with_context(|| format!("Файл {} не записан", path.display()))?;
+
+        let target = output_dir.join(relative_to_base(&path, &base));
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Не удалось создать каталог: {}", parent.display()))?;
+        }
+        profiler
+            .stage("write", || fs::write(&target, &result))
+            .with_context(|| format!("Не удалось записать результат в файл: {}", target.display()))?;
+        if args.preserve_metadata {
+            copy_metadata(args, &path, &target)?;
+        }
+        processed += 1;
+    }
+    engine.finish(args, direction)?;
+
+    println!(
+        "Обработано файлов: {}, пропущено: {}. Результаты сохранены в каталог: {}",
+        processed,
+        skipped,
+        output_dir.display()
+    );
+
+    profiler.report();
+    Ok(())
+}
+
+fn skip(args: &Args, err: anyhow::Error, skipped: &mut usize) -> Result<()> {
+    if args.fail_fast {
+        return Err(err);
+    }
+    eprintln!("Предупреждение: {:#}. Файл пропущен", err);
+    *skipped += 1;
+    Ok(())
+}
+
+/// Неизменная часть шаблона: компоненты пути до первого метасимвола
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+fn relative_to_base(path: &Path, base: &Path) -> PathBuf {
+    match path.strip_prefix(base) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+        // Шаблон без метасимволов совпадает с самим файлом
+        _ => path.file_name().map(PathBuf::from).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,146 @@
+//! Шифр Деластелля (bifid): координаты букв в квадрате Полибия 5×5
+//! разделяются на строки и столбцы, перемешиваются и снова собираются в буквы.
+//!
+//! Квадрат строится по ключевому слову над латинским алфавитом, в котором
+//! две буквы объединены в одну, чтобы осталось 25 (обычно J заменяется на I).
+//! Регистр не различается, результат записывается заглавными буквами.
+//! Символы, которых нет в квадрате, остаются на своих местах и в
+//! перемешивании не участвуют.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::generate::{keyword_alphabet, CharsetError};
+use crate::Direction;
+
+/// Ошибки построения квадрата
+#[derive(Debug, Error)]
+pub enum BifidError {
+    #[error(transparent)]
+    Charset(#[from] CharsetError),
+
+    #[error("Для квадрата 5×5 нужно объединить две разные заглавные латинские буквы, получено '{0}' и '{1}'")]
+    InvalidMerge(char, char),
+}
+
+/// Сторона квадрата Полибия
+const SIDE: usize = 5;
+
+#[derive(Debug)]
+pub struct BifidCipher {
+    square: Vec<char>,
+    positions: HashMap<char, usize>,
+    /// Буква, которая заменяется на `merge.0`
+    merge: (char, char),
+    period: Option<usize>,
+}
+
+impl BifidCipher {
+    /// Строит квадрат по ключевому слову. `merge = ('I', 'J')` означает, что
+    /// J шифруется как I. Перемешивание выполняется блоками по `period`
+    /// букв, а без периода — над всем текстом сразу.
+    pub fn new(keyword: &str, merge: (char, char), period: Option<usize>) -> Result<Self, BifidError> {
+        let (kept, merged) = merge;
+        if kept == merged || !kept.is_ascii_uppercase() || !merged.is_ascii_uppercase() {
+            return Err(BifidError::InvalidMerge(kept, merged));
+        }
+
+        let charset: Vec<char> = ('A'..='Z').filter(|&c| c != merged).collect();
+        let keyword: String = keyword.chars().map(|c| normalize(c, merge)).collect();
+        let square = keyword_alphabet(&keyword, &charset, false)?;
+        let positions = square.iter().enumerate().map(|(index, &c)| (c, index)).collect();
+
+        Ok(BifidCipher {
+            square,
+            positions,
+            merge,
+            period,
+        })
+    }
+
+    pub fn encrypt(&self, text: &str) -> String {
+        self.apply(text, Direction::Encrypt)
+    }
+
+    pub fn decrypt(&self, text: &str) -> String {
+        self.apply(text, Direction::Decrypt)
+    }
+
+    fn apply(&self, text: &str, direction: Direction) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+
+        // Позиции букв квадрата в тексте и их номера в квадрате
+        let letters: Vec<(usize, usize)> = chars
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &c)| {
+                self.positions
+                    .get(&normalize(c, self.merge))
+                    .map(|&position| (index, position))
+            })
+            .collect();
+
+        let period = self.period.unwrap_or(letters.len()).max(1);
+        for block in letters.chunks(period) {
+            let length = block.len();
+            let coordinates: Vec<usize> = match direction {
+                // Сначала все строки блока, затем все столбцы
+                Direction::Encrypt => block
+                    .iter()
+                    .map(|&(_, position)| position / SIDE)
+                    .chain(block.iter().map(|&(_, position)| position % SIDE))
+                    .collect(),
+                Direction::Decrypt => block
+                    .iter()
+                    .flat_map(|&(_, position)| [position / SIDE, position % SIDE])
+                    .collect(),
+            };
+
+            for (k, &(index, _)) in block.iter().enumerate() {
+                let (row, column) = match direction {
+                    Direction::Encrypt => (coordinates[2 * k], coordinates[2 * k + 1]),
+                    Direction::Decrypt => (coordinates[k], coordinates[length + k]),
+                };
+                chars[index] = self.square[row * SIDE + column];
+            }
+        }
+
+        chars.into_iter().collect()
+    }
+}
+
+fn normalize(c: char, (kept, merged): (char, char)) -> char {
+    match c.to_ascii_uppercase() {
+        upper if upper == merged => kept,
+        upper => upper,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn published_example_with_period() {
+        // Пример practicalcryptography.com: ключ phqgmeaylnofdxkrcvszwbuti, период 5
+        let cipher = BifidCipher::new("phqgmeaylnofdxkrcvszwbuti", ('I', 'J'), Some(5)).unwrap();
+        assert_eq!(cipher.encrypt("DEFENDTHEEASTWALLOFTHECASTLE"), "FFYHMKHYCPLIASHADTRLHCCHLBLR");
+        assert_eq!(cipher.decrypt("FFYHMKHYCPLIASHADTRLHCCHLBLR"), "DEFENDTHEEASTWALLOFTHECASTLE");
+    }
+
+    #[test]
+    fn published_example_without_period() {
+        // Пример из описания шифра Деластелля в Википедии
+        let cipher = BifidCipher::new("BGWKZQPNDSIOAXEFCLUMTHYVR", ('I', 'J'), None).unwrap();
+        assert_eq!(cipher.encrypt("FLEEATONCE"), "UAEOLWRINS");
+        assert_eq!(cipher.decrypt("UAEOLWRINS"), "FLEEATONCE");
+    }
+
+    #[test]
+    fn merged_letter_is_encrypted_as_kept_one() {
+        let cipher = BifidCipher::new("KEY", ('I', 'J'), None).unwrap();
+        assert_eq!(cipher.encrypt("JAM"), cipher.encrypt("IAM"));
+        assert!(matches!(BifidCipher::new("KEY", ('I', 'I'), None), Err(BifidError::InvalidMerge('I', 'I'))));
+    }
+}
@@ -0,0 +1,117 @@
+//! Перестановка блоков текста: дополнительный слой перестановки поверх
+//! подстановки. Текст делится на блоки по `size` символов, и блоки
+//! переставляются случайной перестановкой. Та же перестановка, полученная из
+//! того же генератора (например, с тем же начальным значением),
+//! восстанавливает исходный порядок.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Что делать с последним блоком, если он короче остальных
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PartialBlock {
+    /// Оставить его в конце
+    #[default]
+    Keep,
+    /// Переставлять вместе с остальными
+    Include,
+}
+
+/// Переставляет блоки текста по `size` символов
+pub fn shuffle<R: Rng + ?Sized>(text: &str, size: usize, partial: PartialBlock, rng: &mut R) -> String {
+    let blocks = split(text, size);
+    let shuffled = shuffled_count(blocks.len(), text.chars().count(), size, partial);
+    let permutation = permutation(shuffled, rng);
+
+    permutation
+        .iter()
+        .map(|&index| blocks[index])
+        .chain(blocks[shuffled..].iter().copied())
+        .collect()
+}
+
+/// Восстанавливает порядок блоков, переставленных [`shuffle`] с теми же
+/// `size`, `partial` и генератором в том же состоянии
+pub fn unshuffle<R: Rng + ?Sized>(text: &str, size: usize, partial: PartialBlock, rng: &mut R) -> String {
+    let length = text.chars().count();
+    let count = length.div_ceil(size);
+    let shuffled = shuffled_count(count, length, size, partial);
+    let permutation = permutation(shuffled, rng);
+
+    // На позиции `j` стоит исходный блок `permutation[j]`; короче
+    // остальных может быть только последний исходный блок
+    let block_len = |index: usize| if index + 1 == count { length - index * size } else { size };
+
+    let mut original = vec![""; count];
+    let mut rest = text;
+    for &index in &permutation {
+        let (block, tail) = split_at_char(rest, block_len(index));
+        original[index] = block;
+        rest = tail;
+    }
+    for block in &mut original[shuffled..] {
+        let (head, tail) = split_at_char(rest, size);
+        *block = head;
+        rest = tail;
+    }
+
+    original.concat()
+}
+
+/// Сколько блоков с начала участвуют в перестановке
+fn shuffled_count(count: usize, length: usize, size: usize, partial: PartialBlock) -> usize {
+    if partial == PartialBlock::Keep && !length.is_multiple_of(size) {
+        count - 1
+    } else {
+        count
+    }
+}
+
+fn permutation<R: Rng + ?Sized>(count: usize, rng: &mut R) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..count).collect();
+    permutation.shuffle(rng);
+    permutation
+}
+
+fn split(text: &str, size: usize) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (block, tail) = split_at_char(rest, size);
+        blocks.push(block);
+        rest = tail;
+    }
+    blocks
+}
+
+/// Делит строку после `chars` символов (или в конце, если символов меньше)
+fn split_at_char(text: &str, chars: usize) -> (&str, &str) {
+    let index = text.char_indices().nth(chars).map_or(text.len(), |(index, _)| index);
+    text.split_at(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn unshuffle_restores_order_with_fixed_seed() {
+        for partial in [PartialBlock::Keep, PartialBlock::Include] {
+            for text in ["абвгдежзий", "абвгдежзийк", "аб", ""] {
+                let shuffled = shuffle(text, 3, partial, &mut ChaCha20Rng::seed_from_u64(5));
+                assert_eq!(shuffled.chars().count(), text.chars().count());
+                assert_eq!(unshuffle(&shuffled, 3, partial, &mut ChaCha20Rng::seed_from_u64(5)), text, "{:?}", partial);
+            }
+        }
+    }
+
+    #[test]
+    fn kept_partial_block_stays_at_end() {
+        let text = "абвгдежзийклмнопрст";
+        let shuffled = shuffle(text, 4, PartialBlock::Keep, &mut ChaCha20Rng::seed_from_u64(1));
+        assert!(shuffled.ends_with("рст"), "{}", shuffled);
+        assert_ne!(shuffled, text);
+    }
+}
@@ -0,0 +1,120 @@
+//! Построение шифра в коде, без файла алфавита:
+//! `Cipher::builder().map('а', 'б').reciprocal(true).build()`.
+
+use std::collections::HashSet;
+
+use crate::format::Pair;
+use crate::{Cipher, CipherError, UnmappedPolicy};
+
+/// Построитель [`Cipher`]. Подстановки проверяются в [`build`](Self::build)
+/// той же проверкой, что и при разборе файла алфавита: ключи и значения не
+/// должны повторяться, иначе шифр необратим. Номер строки в ошибке — это
+/// порядковый номер подстановки, начиная с единицы.
+#[derive(Debug, Clone, Default)]
+pub struct CipherBuilder {
+    /// Подстановки в порядке добавления; `None` — символ удаляется
+    entries: Vec<(char, Option<char>)>,
+    reciprocal: bool,
+    unmapped: UnmappedPolicy,
+}
+
+impl CipherBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляет подстановку `key` → `value`
+    pub fn map(mut self, key: char, value: char) -> Self {
+        self.entries.push((key, Some(value)));
+        self
+    }
+
+    /// Добавляет подстановки из пар
+    pub fn maps(mut self, pairs: impl IntoIterator<Item = (char, char)>) -> Self {
+        self.entries.extend(pairs.into_iter().map(|(key, value)| (key, Some(value))));
+        self
+    }
+
+    /// Удалять символ при шифровании, как пустое значение в файле алфавита.
+    /// При расшифровке удаленный символ не восстанавливается.
+    pub fn delete(mut self, key: char) -> Self {
+        self.entries.push((key, None));
+        self
+    }
+
+    /// Дополнить подстановки обратными: для `а` → `б` добавляется `б` → `а`,
+    /// если для `б` подстановка не задана. Так шифрование совпадает с
+    /// расшифровкой.
+    pub fn reciprocal(mut self, reciprocal: bool) -> Self {
+        self.reciprocal = reciprocal;
+        self
+    }
+
+    /// Что делать с символами без подстановки, как `--swap-case-unmapped`
+    /// в утилите. По умолчанию они остаются без изменений.
+    pub fn unmapped(mut self, policy: UnmappedPolicy) -> Self {
+        self.unmapped = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<Cipher, CipherError> {
+        let mut entries = self.entries;
+        if self.reciprocal {
+            let keys: HashSet<char> = entries.iter().map(|&(key, _)| key).collect();
+            let inverse: Vec<(char, Option<char>)> = entries
+                .iter()
+                .filter_map(|&(key, value)| value.map(|value| (value, Some(key))))
+                .filter(|(value, _)| !keys.contains(value))
+                .collect();
+            entries.extend(inverse);
+        }
+
+        let pairs = entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, (key, value))| Pair {
+                key,
+                long_key: None,
+                key_line: index + 1,
+                value,
+                value_line: index + 1,
+            })
+            .collect();
+
+        let mut cipher = Cipher::from_pairs(pairs, Vec::new(), false)?;
+        cipher.unmapped = self.unmapped;
+        Ok(cipher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_reciprocal_cipher() {
+        let cipher = Cipher::builder().map('а', 'я').map('б', 'ю').delete('ъ').reciprocal(true).build().unwrap();
+        assert_eq!(cipher.encrypt("ябъ"), "аю");
+        assert_eq!(cipher.decrypt("яю"), "аб");
+    }
+
+    #[test]
+    fn duplicate_value_is_reported_like_in_alphabet_file() {
+        let err = Cipher::builder().map('а', 'я').map('б', 'я').build().unwrap_err();
+        assert!(matches!(err, CipherError::DuplicateValue { line: 2, ch: 'я' }), "{:?}", err);
+    }
+
+    #[test]
+    fn duplicate_key_is_reported_like_in_alphabet_file() {
+        let err = Cipher::builder().map('а', 'я').delete('а').build().unwrap_err();
+        assert!(matches!(err, CipherError::DuplicateKey { line: 2, ch: 'а' }), "{:?}", err);
+    }
+
+    #[test]
+    fn swap_case_policy_round_trips() {
+        let cipher = Cipher::builder().map('а', 'б').map('б', 'а').unmapped(UnmappedPolicy::SwapCase).build().unwrap();
+        let encrypted = cipher.encrypt("аВб!");
+        assert_eq!(encrypted, "бва!");
+        assert_eq!(cipher.decrypt(&encrypted), "аВб!");
+    }
+}
@@ -0,0 +1,94 @@
+//! Чтение текста фрагментами ограниченного размера для потоковой обработки
+
+use std::io::{self, ErrorKind, Read};
+use std::ops::ControlFlow;
+
+/// Самая длинная последовательность UTF-8 — буфер меньшего размера
+/// не сможет вместить один символ
+pub const MIN_BUFFER_SIZE: usize = 4;
+
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Читает `reader` порциями по `buffer_size` байт и передает `f` каждый
+/// фрагмент, декодированный как UTF-8. Символ, разрезанный границей порции,
+/// переносится в следующий фрагмент. Пустых фрагментов `f` не получает:
+/// если порция оборвалась внутри первого же символа, он дочитывается
+/// следующей. Обработку можно прервать, вернув `ControlFlow::Break`.
+pub fn for_each_chunk<R: Read>(
+    mut reader: R,
+    buffer_size: usize,
+    mut f: impl FnMut(&str) -> io::Result<ControlFlow<()>>,
+) -> io::Result<()> {
+    let mut buffer = vec![0; buffer_size.max(MIN_BUFFER_SIZE)];
+    let mut filled = 0;
+
+    loop {
+        let read = match reader.read(&mut buffer[filled..]) {
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        if read == 0 {
+            if filled > 0 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "поток оборвался посреди символа UTF-8",
+                ));
+            }
+            return Ok(());
+        }
+        filled += read;
+
+        let valid = match std::str::from_utf8(&buffer[..filled]) {
+            Ok(text) => text.len(),
+            // Неполная последовательность в конце порции дочитается позже
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(ErrorKind::InvalidData, err)),
+        };
+
+        if valid == 0 {
+            continue;
+        }
+        let chunk = std::str::from_utf8(&buffer[..valid])
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        if f(chunk)?.is_break() {
+            return Ok(());
+        }
+
+        buffer.copy_within(valid..filled, 0);
+        filled -= valid;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Отдает данные по одному байту, как канал, из которого пришла только
+    /// часть записанного
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl Read for ByteByByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((&first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn short_reads_never_produce_empty_chunks() {
+        let text = "\u{feff}аб";
+        let mut chunks = Vec::new();
+        for_each_chunk(ByteByByte(text.as_bytes()), 16, |chunk| {
+            chunks.push(chunk.to_string());
+            Ok(ControlFlow::Continue(()))
+        })
+        .unwrap();
+        assert_eq!(chunks, ["\u{feff}", "а", "б"]);
+    }
+}
@@ -0,0 +1,45 @@
+//! Единый поток «алфавит, пустая строка, текст»: для программ, у которых
+//! есть только один канал передачи данных
+
+/// Делит поток по первой пустой строке (строке из одних пробельных символов).
+/// Возвращает алфавит и текст без разделяющей строки, либо `None`, если
+/// разделителя нет.
+pub fn split(content: &str) -> Option<(&str, &str)> {
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            return Some((&content[..offset], &content[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Собирает поток из алфавита и текста, который [`split`] разделит обратно.
+/// Алфавит не должен содержать пустых строк.
+pub fn join(alphabet: &str, text: &str) -> String {
+    let mut content = String::with_capacity(alphabet.len() + text.len() + 2);
+    content.push_str(alphabet);
+    if !alphabet.is_empty() && !alphabet.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push('\n');
+    content.push_str(text);
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_reverses_join() {
+        let content = join("а = б\nб = а", "аб\n\nба");
+        assert_eq!(content, "а = б\nб = а\n\nаб\n\nба");
+        assert_eq!(split(&content), Some(("а = б\nб = а\n", "аб\n\nба")));
+        assert_eq!(split("а = б\n \t\r\nаб"), Some(("а = б\n", "аб")));
+        assert_eq!(split("а = б\nаб"), None);
+    }
+}
@@ -0,0 +1,95 @@
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Ошибки чтения и разбора алфавитов и построения шифров по ним.
+/// Ошибки других частей библиотеки (кодирования текста, генераторов,
+/// отдельных шифров) объявлены в их модулях.
+#[derive(Debug, Error)]
+pub enum CipherError {
+    #[error("Строка {line}: отсутствует знак равенства в '{content}'")]
+    MissingSeparator { line: usize, content: String },
+
+    #[error("Строка {line}: пустой ключ")]
+    EmptyKey { line: usize },
+
+    #[error("Строка {line}: пустое значение")]
+    EmptyValue { line: usize },
+
+    #[error("Строка {line}: дублирующийся ключ '{ch}'")]
+    DuplicateKey { line: usize, ch: char },
+
+    #[error("Строка {line}: дублирующееся значение '{ch}'")]
+    DuplicateValue { line: usize, ch: char },
+
+    #[error("Строка {line}: дублирующееся слово '{word}'")]
+    DuplicateWord { line: usize, word: String },
+
+    #[error("Строка {line}: перевод '{word}' уже используется для другого слова")]
+    DuplicateTranslation { line: usize, word: String },
+
+    #[error("Строка {line}: вес '{weight}' должен быть положительным целым числом")]
+    InvalidWeight { line: usize, weight: String },
+
+    #[error("Строка {line}: сумма весов слишком велика")]
+    WeightOverflow { line: usize },
+
+    #[error("Выровненный алфавит должен состоять из двух строк, найдено {0}")]
+    AlignedLineCount(usize),
+
+    #[error("Строки выровненного алфавита разной длины: {keys} и {values} символов")]
+    AlignedLengthMismatch { keys: usize, values: usize },
+
+    #[error("Некорректный JSON-алфавит: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Строка {line}: в записи CSV должно быть два поля, ключ и значение, найдено {count}")]
+    CsvFieldCount { line: usize, count: usize },
+
+    #[error("Строка {line}: кавычки поля CSV не закрыты")]
+    CsvUnterminatedQuote { line: usize },
+
+    #[error("JSON-алфавит должен быть объектом вида {{\"а\": \"б\"}}")]
+    JsonNotObject,
+
+    #[error("Значение ключа '{key}' в JSON-алфавите должно быть строкой")]
+    JsonValueNotString { key: String },
+
+    #[error("Символ {position} '{ch}' не имеет подстановки и прошел бы без изменений")]
+    Passthrough { position: usize, ch: char },
+
+    #[error("Циклическое включение алфавитов: {0}")]
+    IncludeCycle(String),
+
+    #[error("Не удалось прочитать включаемый алфавит {path}")]
+    IncludeRead { path: String, source: std::io::Error },
+
+    #[error("Ошибка во включаемом алфавите {path}")]
+    IncludeParse { path: String, source: Box<CipherError> },
+
+    #[error("Символ '{key}' определен по-разному: {first} и {second}")]
+    IncludeConflict { key: char, first: String, second: String },
+
+    #[error("Значение '{value}' назначено двум символам: {first} и {second}")]
+    IncludeDuplicateValue { value: char, first: String, second: String },
+
+    #[error("Символы встречаются и среди ключей, и среди значений алфавита: {0}")]
+    OverlappingSets(String),
+
+    #[error("Строка {line}: директива @order уже встречалась")]
+    RepeatedOrder { line: usize },
+
+    #[error("Строка {line}: символ '{ch}' повторяется в директиве @order")]
+    DuplicateOrderChar { line: usize, ch: char },
+
+    #[error("Не удалось прочитать файл {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Не удалось прочитать алфавит")]
+    Read(#[source] io::Error),
+}
@@ -0,0 +1,63 @@
+//! Преобразование одного поля в строках с разделителями, например одной
+//! колонки CSV без кавычек.
+
+/// Применяет `f` к полю с номером `field` (с единицы) в каждой строке текста,
+/// разделенной на поля строкой `delimiter`. Остальные поля, разделители и
+/// переводы строк (`\n` и `\r\n`) не меняются.
+///
+/// Возвращает результат и число непустых строк, в которых меньше `field`
+/// полей: такие строки передаются без изменений.
+pub fn map_field<E>(
+    text: &str,
+    delimiter: &str,
+    field: usize,
+    f: &mut impl FnMut(&str) -> Result<String, E>,
+) -> Result<(String, usize), E> {
+    let index = field.saturating_sub(1);
+    let mut result = String::with_capacity(text.len());
+    let mut short_lines = 0;
+
+    for (number, line) in text.split('\n').enumerate() {
+        if number > 0 {
+            result.push('\n');
+        }
+        let (line, ending) = match line.strip_suffix('\r') {
+            Some(stripped) => (stripped, "\r"),
+            None => (line, ""),
+        };
+
+        let mut fields: Vec<String> = line.split(delimiter).map(str::to_string).collect();
+        match fields.get_mut(index) {
+            Some(value) => *value = f(value)?,
+            None if !line.is_empty() => short_lines += 1,
+            None => {}
+        }
+        result.push_str(&fields.join(delimiter));
+        result.push_str(ending);
+    }
+
+    Ok((result, short_lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upper(value: &str) -> Result<String, ()> {
+        Ok(value.to_uppercase())
+    }
+
+    #[test]
+    fn maps_second_field_only() {
+        let text = "id,имя,город\r\n1,анна,тверь\n2\n\n3,,омск\n";
+        let (result, short_lines) = map_field(text, ",", 2, &mut upper).unwrap();
+        assert_eq!(result, "id,ИМЯ,город\r\n1,АННА,тверь\n2\n\n3,,омск\n");
+        assert_eq!(short_lines, 1);
+    }
+
+    #[test]
+    fn multi_char_delimiter() {
+        let (result, short_lines) = map_field("а::б::в", "::", 3, &mut upper).unwrap();
+        assert_eq!((result.as_str(), short_lines), ("а::б::В", 0));
+    }
+}
@@ -0,0 +1,356 @@
+//! Форматы файла алфавита и их автоматическое определение
+//!
+//! Поддерживаются четыре формата:
+//!
+//! - «ключ = значение» по одной подстановке в строке;
+//! - выровненный: две строки одинаковой длины, символ первой строки
+//!   заменяется символом второй, стоящим на той же позиции;
+//! - JSON-объект вида `{"а": "б"}`;
+//! - таблица CSV из двух колонок, ключа и значения.
+//!
+//! Во всех форматах пропускаются метка порядка байтов в начале, а в
+//! текстовых — пустые строки и комментарии, начинающиеся с `#`. В CSV
+//! комментариев нет: `#` может быть ключом. CSV не определяется по
+//! содержимому, его нужно указать явно.
+//!
+//! Текстовые форматы понимают директиву `@order абвг…`: она объявляет
+//! порядок символов алфавита для режимов, которым важен номер символа
+//! (см. [`Cipher::alphabet_order`](crate::Cipher::alphabet_order)).
+
+use serde_json::Value;
+
+use crate::include::directive;
+use crate::{parse_entries_with, CipherError, BOM};
+
+/// Формат файла алфавита
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AlphabetFormat {
+    /// Строки "ключ = значение"
+    KeyValue,
+    /// Две выровненные строки: ключи и значения
+    Aligned,
+    /// JSON-объект {"ключ": "значение"}
+    Json,
+    /// Таблица CSV: по строке "ключ,значение"
+    Csv,
+}
+
+impl AlphabetFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            AlphabetFormat::KeyValue => "key-value",
+            AlphabetFormat::Aligned => "aligned",
+            AlphabetFormat::Json => "json",
+            AlphabetFormat::Csv => "csv",
+        }
+    }
+
+    /// Определяет формат по содержимому:
+    ///
+    /// 1. первый значимый символ `{` — JSON;
+    /// 2. каждая значимая строка содержит `=` — «ключ = значение»;
+    /// 3. ровно две значимые строки — выровненный формат;
+    /// 4. иначе — «ключ = значение», чтобы ошибка указала на неверную строку.
+    ///
+    /// Выровненный алфавит, в обеих строках которого встречается `=`,
+    /// распознается как «ключ = значение»; для него формат нужно указать явно.
+    pub fn detect(content: &str) -> AlphabetFormat {
+        let content = content.strip_prefix(BOM).unwrap_or(content);
+        if content.trim_start().starts_with('{') {
+            return AlphabetFormat::Json;
+        }
+
+        let lines = significant_lines(content);
+        if lines.iter().all(|(_, line)| line.contains('=')) {
+            AlphabetFormat::KeyValue
+        } else if lines.len() == 2 {
+            AlphabetFormat::Aligned
+        } else {
+            AlphabetFormat::KeyValue
+        }
+    }
+}
+
+const ORDER_DIRECTIVE: &str = "@order";
+
+/// Одна подстановка алфавита и строки, в которых записаны ее символы.
+/// `value` равно `None`, если символ удаляется.
+pub(crate) struct Pair {
+    pub key: char,
+    /// Ключ целиком, если в нем больше одного символа
+    pub long_key: Option<String>,
+    pub key_line: usize,
+    pub value: Option<char>,
+    pub value_line: usize,
+}
+
+/// Разбирает алфавит указанного формата в список подстановок.
+/// Повторы символов здесь не проверяются. `csv_header` — первая запись
+/// CSV является заголовком и пропускается; в других форматах не действует.
+pub(crate) fn parse_pairs(
+    content: &str,
+    format: AlphabetFormat,
+    allow_empty_value: bool,
+    csv_header: bool,
+) -> Result<Vec<Pair>, CipherError> {
+    match format {
+        AlphabetFormat::KeyValue => parse_key_value(content, allow_empty_value),
+        AlphabetFormat::Aligned => parse_aligned(content),
+        AlphabetFormat::Json => parse_json(content, allow_empty_value),
+        AlphabetFormat::Csv => parse_csv(content, allow_empty_value, csv_header),
+    }
+}
+
+fn parse_key_value(content: &str, allow_empty_value: bool) -> Result<Vec<Pair>, CipherError> {
+    parse_entries_with(content, allow_empty_value)?
+        .into_iter()
+        .map(|entry| {
+            Ok(Pair {
+                key: entry.key.chars().next().ok_or(CipherError::EmptyKey { line: entry.line })?,
+                long_key: long_key(entry.key),
+                key_line: entry.line,
+                value: entry.value.chars().next(),
+                value_line: entry.line,
+            })
+        })
+        .collect()
+}
+
+fn parse_aligned(content: &str) -> Result<Vec<Pair>, CipherError> {
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+    let lines = significant_lines(content);
+
+    let [(key_line, keys), (value_line, values)] = lines[..] else {
+        return Err(CipherError::AlignedLineCount(lines.len()));
+    };
+
+    let key_count = keys.chars().count();
+    let value_count = values.chars().count();
+    if key_count != value_count {
+        return Err(CipherError::AlignedLengthMismatch {
+            keys: key_count,
+            values: value_count,
+        });
+    }
+
+    Ok(keys
+        .chars()
+        .zip(values.chars())
+        .map(|(key, value)| Pair {
+            key,
+            long_key: None,
+            key_line,
+            value: Some(value),
+            value_line,
+        })
+        .collect())
+}
+
+/// В JSON номер строки заменяется порядковым номером записи в объекте
+fn parse_json(content: &str, allow_empty_value: bool) -> Result<Vec<Pair>, CipherError> {
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+    let Value::Object(fields) = serde_json::from_str(content)? else {
+        return Err(CipherError::JsonNotObject);
+    };
+
+    fields
+        .into_iter()
+        .enumerate()
+        .map(|(index, (key, value))| {
+            let line = index + 1;
+            let Value::String(value) = value else {
+                return Err(CipherError::JsonValueNotString { key });
+            };
+            if value.is_empty() && !allow_empty_value {
+                return Err(CipherError::EmptyValue { line });
+            }
+            Ok(Pair {
+                key: key.chars().next().ok_or(CipherError::EmptyKey { line })?,
+                long_key: long_key(&key),
+                key_line: line,
+                value: value.chars().next(),
+                value_line: line,
+            })
+        })
+        .collect()
+}
+
+/// Номер строки — строка, в которой начинается запись
+fn parse_csv(content: &str, allow_empty_value: bool, header: bool) -> Result<Vec<Pair>, CipherError> {
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+
+    csv_records(content)?
+        .into_iter()
+        .skip(usize::from(header))
+        .map(|(line, fields)| {
+            let [key, value] = &fields[..] else {
+                return Err(CipherError::CsvFieldCount { line, count: fields.len() });
+            };
+            if value.is_empty() && !allow_empty_value {
+                return Err(CipherError::EmptyValue { line });
+            }
+            Ok(Pair {
+                key: key.chars().next().ok_or(CipherError::EmptyKey { line })?,
+                long_key: long_key(key),
+                key_line: line,
+                value: value.chars().next(),
+                value_line: line,
+            })
+        })
+        .collect()
+}
+
+/// Записи CSV с номерами строк, в которых они начинаются. Поле в кавычках
+/// может содержать запятые, переводы строк и кавычки, записанные дважды.
+/// Записи `\n` и `\r\n` разделяют одинаково, пустые строки пропускаются.
+fn csv_records(content: &str) -> Result<Vec<(usize, Vec<String>)>, CipherError> {
+    let mut records = Vec::new();
+    let mut chars = content.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut was_quoted = false;
+
+        loop {
+            match chars.next() {
+                None if quoted => return Err(CipherError::CsvUnterminatedQuote { line: start }),
+                None => break,
+                Some('"') if quoted => {
+                    if chars.next_if_eq(&'"').is_some() {
+                        field.push('"');
+                    } else {
+                        quoted = false;
+                    }
+                }
+                Some(c) if quoted => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+                Some('"') if field.is_empty() && !was_quoted => {
+                    quoted = true;
+                    was_quoted = true;
+                }
+                Some(',') => {
+                    fields.push(std::mem::take(&mut field));
+                    was_quoted = false;
+                }
+                Some('\r') if chars.peek() == Some(&'\n') => {}
+                Some('\n') => {
+                    line += 1;
+                    break;
+                }
+                Some(c) => field.push(c),
+            }
+        }
+
+        let blank = fields.is_empty() && field.is_empty() && !was_quoted;
+        if !blank {
+            fields.push(field);
+            records.push((start, fields));
+        }
+    }
+
+    Ok(records)
+}
+
+/// Ключи длиннее одного символа с номерами строк.
+///
+/// Шифр заменяет отдельные символы, а не последовательности, поэтому из
+/// такого ключа используется только первый символ: `th = @` означает
+/// `t = @`. Более длинные ключи не имеют приоритета перед короткими, и
+/// `th` рядом с `t` дает ошибку повторяющегося ключа. Алфавит, который
+/// не удается разобрать, предупреждений не дает — его ошибку сообщит разбор.
+pub fn multi_char_keys(content: &str, format: AlphabetFormat, csv_header: bool) -> Vec<(usize, String)> {
+    parse_pairs(content, format, true, csv_header)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|pair| pair.long_key.map(|key| (pair.key_line, key)))
+        .collect()
+}
+
+fn long_key(key: &str) -> Option<String> {
+    (key.chars().nth(1).is_some()).then(|| key.to_string())
+}
+
+/// Порядок символов из директивы `@order` текстового алфавита; пустой,
+/// если директивы нет. Пробельные символы в списке не учитываются.
+/// Директива записывается не больше одного раза, символы в ней не
+/// повторяются. Символы порядка не обязаны быть ключами алфавита.
+pub(crate) fn parse_order(content: &str, format: AlphabetFormat) -> Result<Vec<char>, CipherError> {
+    if matches!(format, AlphabetFormat::Json | AlphabetFormat::Csv) {
+        return Ok(Vec::new());
+    }
+
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+    let mut order: Option<Vec<char>> = None;
+    for (index, line) in content.lines().enumerate() {
+        let Some(list) = order_directive(line) else {
+            continue;
+        };
+        if order.is_some() {
+            return Err(CipherError::RepeatedOrder { line: index + 1 });
+        }
+
+        let mut chars = Vec::new();
+        for ch in list.chars().filter(|c| !c.is_whitespace()) {
+            if chars.contains(&ch) {
+                return Err(CipherError::DuplicateOrderChar { line: index + 1, ch });
+            }
+            chars.push(ch);
+        }
+        order = Some(chars);
+    }
+    Ok(order.unwrap_or_default())
+}
+
+/// Список символов из строки `@order список`
+pub(crate) fn order_directive(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(ORDER_DIRECTIVE)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+/// Непустые строки, кроме комментариев и директив `@include` и `@order`,
+/// с номерами, начиная с единицы
+fn significant_lines(content: &str) -> Vec<(usize, &str)> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| {
+            !line.is_empty() && !line.starts_with('#') && directive(line).is_none() && order_directive(line).is_none()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_char_keys_lists_long_keys_with_lines() {
+        let content = "t = 1\nth = @\n# the = !\nx = y\n";
+        assert_eq!(multi_char_keys(content, AlphabetFormat::KeyValue, false), vec![(2, "th".to_string())]);
+    }
+
+    #[test]
+    fn detects_each_format() {
+        assert_eq!(AlphabetFormat::detect("\u{feff} {\"а\": \"б\"}"), AlphabetFormat::Json);
+        assert_eq!(AlphabetFormat::detect("# ключ\nа = б\nб = а\n"), AlphabetFormat::KeyValue);
+        assert_eq!(AlphabetFormat::detect("# ключ\nабв\n\nбва\n"), AlphabetFormat::Aligned);
+        assert_eq!(AlphabetFormat::detect("а = б\nвг\nд = е\n"), AlphabetFormat::KeyValue);
+    }
+
+    #[test]
+    fn aligned_lines_with_equals_need_explicit_format() {
+        assert_eq!(AlphabetFormat::detect("а=б\nб=а\n"), AlphabetFormat::KeyValue);
+    }
+}
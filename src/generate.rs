@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use thiserror::Error;
+
+/// Ошибки в наборе символов или ключевом слове генератора
+#[derive(Debug, Error)]
+pub enum CharsetError {
+    #[error("Символ '{0}' ключевого слова отсутствует в наборе символов")]
+    KeywordOutsideCharset(char),
+
+    #[error("В наборе символов нет пар строчных и заглавных букв")]
+    NoCasePairs,
+
+    #[error("Символ '{0}' повторяется в наборе символов")]
+    DuplicateChar(char),
+
+    #[error("Набор символов пуст")]
+    Empty,
+}
+
+/// Русский алфавит, используемый генераторами по умолчанию
+pub const RUSSIAN: &str = "абвгдеёжзийклмнопрстуфхцчшщъыьэюя";
+
+/// Строит смешанный алфавит по ключевому слову: сначала неповторяющиеся
+/// символы слова, затем остальные символы набора в исходном порядке
+/// (или в обратном, если `reverse_fill`).
+///
+/// Возвращает алфавит шифра той же длины, что и `charset`: символ
+/// `charset[i]` заменяется на `result[i]`.
+pub fn keyword_alphabet(keyword: &str, charset: &[char], reverse_fill: bool) -> Result<Vec<char>, CharsetError> {
+    check_charset(charset)?;
+    if charset.is_empty() {
+        return Err(CharsetError::Empty);
+    }
+
+    let mut used = HashSet::new();
+    let mut mixed = Vec::with_capacity(charset.len());
+
+    for c in keyword.chars() {
+        if !charset.contains(&c) {
+            return Err(CharsetError::KeywordOutsideCharset(c));
+        }
+        if used.insert(c) {
+            mixed.push(c);
+        }
+    }
+
+    let rest: Vec<char> = charset.iter().copied().filter(|c| !used.contains(c)).collect();
+    if reverse_fill {
+        mixed.extend(rest.into_iter().rev());
+    } else {
+        mixed.extend(rest);
+    }
+
+    Ok(mixed)
+}
+
+/// Случайно перемешивает набор символов. Как и в [`keyword_alphabet`],
+/// символ `charset[i]` заменяется на `result[i]`.
+pub fn random_alphabet<R: Rng + ?Sized>(charset: &[char], rng: &mut R) -> Result<Vec<char>, CharsetError> {
+    check_charset(charset)?;
+
+    let mut mixed = charset.to_vec();
+    mixed.shuffle(rng);
+    Ok(mixed)
+}
+
+/// Случайный одноразовый блокнот из `length` символов набора, пригодный для
+/// [`OneTimePadCipher`](crate::otp::OneTimePadCipher). Символы выбираются
+/// независимо и равновероятно.
+pub fn random_pad<R: Rng + ?Sized>(charset: &[char], length: usize, rng: &mut R) -> Result<String, CharsetError> {
+    check_charset(charset)?;
+    if charset.is_empty() {
+        return Err(CharsetError::Empty);
+    }
+
+    Ok((0..length).map(|_| charset[rng.random_range(0..charset.len())]).collect())
+}
+
+/// Случайный алфавит, согласованный по регистру: если `а` заменяется на `м`,
+/// то `А` заменяется на `М`. Строчные буквы, заглавная пара которых есть
+/// в наборе, перемешиваются между собой, а заглавные получают подстановку
+/// своей строчной пары. Остальные символы перемешиваются отдельно.
+pub fn random_linked_case_alphabet<R: Rng + ?Sized>(charset: &[char], rng: &mut R) -> Result<Vec<char>, CharsetError> {
+    check_charset(charset)?;
+
+    let pairs = case_pairs(charset);
+    if pairs.is_empty() {
+        return Err(CharsetError::NoCasePairs);
+    }
+
+    let upper_of: HashMap<char, char> = pairs.iter().copied().collect();
+    let paired: HashSet<char> = pairs.iter().flat_map(|&(lower, upper)| [lower, upper]).collect();
+    let lower: Vec<char> = pairs.iter().map(|&(lower, _)| lower).collect();
+    let others: Vec<char> = charset.iter().copied().filter(|c| !paired.contains(c)).collect();
+
+    let mut mapping = HashMap::new();
+    for (&original, mixed) in lower.iter().zip(random_alphabet(&lower, rng)?) {
+        mapping.insert(original, mixed);
+        mapping.insert(upper_of[&original], upper_of[&mixed]);
+    }
+    mapping.extend(others.iter().copied().zip(random_alphabet(&others, rng)?));
+
+    Ok(charset.iter().map(|c| mapping[c]).collect())
+}
+
+/// Пары строчной и заглавной буквы, обе из которых есть в наборе, в порядке
+/// строчных букв в наборе
+pub fn case_pairs(charset: &[char]) -> Vec<(char, char)> {
+    let present: HashSet<char> = charset.iter().copied().collect();
+    charset
+        .iter()
+        .filter_map(|&c| {
+            let mut upper = c.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(upper), None) if upper != c && present.contains(&upper) => Some((c, upper)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Начальное значение генератора случайных чисел из пароля: первые восемь
+/// байт SHA-256. Один и тот же пароль всегда дает одно и то же значение.
+///
+/// Это способ запомнить алфавит, а не получение криптографического ключа:
+/// хеш не замедлен и не использует соль, поэтому короткий пароль легко
+/// подобрать перебором.
+pub fn seed_from_password(password: &str) -> u64 {
+    seed_from_bytes(password.as_bytes())
+}
+
+/// Начальное значение генератора из произвольных данных, например
+/// содержимого общего файла: первые восемь байт их SHA-256
+pub fn seed_from_bytes(data: &[u8]) -> u64 {
+    let digest = Sha256::digest(data);
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Проверяет, что в наборе символов нет повторов
+pub fn check_charset(charset: &[char]) -> Result<(), CharsetError> {
+    let mut seen = HashSet::new();
+    for &c in charset {
+        if !seen.insert(c) {
+            return Err(CharsetError::DuplicateChar(c));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn seeded_alphabet_is_stable() {
+        let charset: Vec<char> = "абвгдеж".chars().collect();
+        let alphabet = random_alphabet(&charset, &mut ChaCha20Rng::seed_from_u64(42)).unwrap();
+        assert_eq!(alphabet.iter().collect::<String>(), "двгежба");
+    }
+
+    #[test]
+    fn same_seed_reproduces_alphabet() {
+        let charset: Vec<char> = RUSSIAN.chars().collect();
+        let seed = seed_from_password("пароль");
+        let first = random_alphabet(&charset, &mut ChaCha20Rng::seed_from_u64(seed)).unwrap();
+        let second = random_alphabet(&charset, &mut ChaCha20Rng::seed_from_u64(seed)).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, random_alphabet(&charset, &mut ChaCha20Rng::seed_from_u64(seed + 1)).unwrap());
+    }
+
+    #[test]
+    fn keyword_alphabet_textbook_example() {
+        let charset: Vec<char> = "abcdefghijklmnopqrstuvwxyz".chars().collect();
+        let mixed = keyword_alphabet("zebras", &charset, false).unwrap();
+        assert_eq!(mixed.iter().collect::<String>(), "zebrascdfghijklmnopqtuvwxy");
+        let reversed = keyword_alphabet("zebras", &charset, true).unwrap();
+        assert_eq!(reversed.iter().collect::<String>(), "zebrasyxwvutqponmlkjihgfdc");
+    }
+
+    #[test]
+    fn keyword_outside_charset_is_rejected() {
+        let charset: Vec<char> = "абв".chars().collect();
+        assert!(matches!(keyword_alphabet("аг", &charset, false), Err(CharsetError::KeywordOutsideCharset('г'))));
+    }
+
+    #[test]
+    fn password_seed_is_deterministic() {
+        assert_eq!(seed_from_password("пароль"), seed_from_password("пароль"));
+        assert_ne!(seed_from_password("пароль"), seed_from_password("Пароль"));
+    }
+
+    #[test]
+    fn linked_case_alphabet_keeps_case_pairs() {
+        let charset: Vec<char> = "абвгАБВГ".chars().collect();
+        let alphabet = random_linked_case_alphabet(&charset, &mut ChaCha20Rng::seed_from_u64(3)).unwrap();
+        for (lower, upper) in case_pairs(&charset) {
+            let lower_index = charset.iter().position(|&c| c == lower).unwrap();
+            let upper_index = charset.iter().position(|&c| c == upper).unwrap();
+            assert_eq!(alphabet[upper_index].to_lowercase().next(), Some(alphabet[lower_index]));
+            assert!(alphabet[upper_index].is_uppercase());
+        }
+    }
+
+    #[test]
+    fn linked_case_needs_case_pairs() {
+        let charset: Vec<char> = "абв123".chars().collect();
+        assert!(matches!(
+            random_linked_case_alphabet(&charset, &mut ChaCha20Rng::seed_from_u64(3)),
+            Err(CharsetError::NoCasePairs)
+        ));
+    }
+
+    #[test]
+    fn pad_has_requested_length_over_charset() {
+        let charset: Vec<char> = "абв".chars().collect();
+        let pad = random_pad(&charset, 100, &mut ChaCha20Rng::seed_from_u64(7)).unwrap();
+        assert_eq!(pad.chars().count(), 100);
+        assert!(pad.chars().all(|c| charset.contains(&c)), "{}", pad);
+        assert!(random_pad(&[], 10, &mut ChaCha20Rng::seed_from_u64(7)).is_err());
+    }
+
+    #[test]
+    fn seed_from_bytes_depends_only_on_content() {
+        assert_eq!(seed_from_bytes(b"secret\n"), seed_from_bytes(b"secret\n"));
+        assert_ne!(seed_from_bytes(b"secret\n"), seed_from_bytes(b"secret"));
+        assert_eq!(seed_from_bytes("пароль".as_bytes()), seed_from_password("пароль"));
+    }
+
+    #[test]
+    fn keyword_alphabet_rejects_empty_charset() {
+        assert!(matches!(keyword_alphabet("", &[], false), Err(CharsetError::Empty)));
+    }
+}
@@ -0,0 +1,112 @@
+//! Заголовок зашифрованного текста: первая строка вида
+//! `#cipher/1 mode=substitution key=3f9a0c1e5b7d2468` с режимом шифрования и
+//! отпечатком ключа. По заголовку расшифровка проверяет, что выбраны тот же
+//! режим и тот же ключ, что и при шифровании.
+
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use thiserror::Error;
+
+/// Заголовок записан неверно
+#[derive(Debug, Error)]
+#[error("Некорректный заголовок: {0}")]
+pub struct InvalidHeader(pub String);
+
+const PREFIX: &str = "#cipher/1";
+
+/// Режим и отпечаток ключа из заголовка
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub mode: String,
+    pub fingerprint: String,
+}
+
+impl Header {
+    /// Разбирает строку заголовка. Поля, кроме `mode` и `key`, пропускаются,
+    /// чтобы следующие версии формата могли добавлять свои.
+    pub fn parse(line: &str) -> Result<Header, InvalidHeader> {
+        let fields = fields(line).ok_or_else(|| InvalidHeader(format!("строка не начинается с {}", PREFIX)))?;
+
+        let (mut mode, mut fingerprint) = (None, None);
+        for field in fields.split_whitespace() {
+            match field.split_once('=') {
+                Some(("mode", value)) => mode = Some(value.to_string()),
+                Some(("key", value)) => fingerprint = Some(value.to_string()),
+                Some(_) => {}
+                None => return Err(InvalidHeader(format!("поле '{}' не имеет вида имя=значение", field))),
+            }
+        }
+
+        Ok(Header {
+            mode: mode.ok_or_else(|| InvalidHeader("нет поля mode".to_string()))?,
+            fingerprint: fingerprint.ok_or_else(|| InvalidHeader("нет поля key".to_string()))?,
+        })
+    }
+
+    /// Отделяет заголовок от остального текста. `None`, если первая строка
+    /// текста — не заголовок; ошибка, если заголовок записан неверно.
+    pub fn split(text: &str) -> Result<Option<(Header, &str)>, InvalidHeader> {
+        let (line, rest) = match text.split_once('\n') {
+            Some((line, rest)) => (line.strip_suffix('\r').unwrap_or(line), rest),
+            None => (text, ""),
+        };
+        if fields(line).is_none() {
+            return Ok(None);
+        }
+        Ok(Some((Header::parse(line)?, rest)))
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} mode={} key={}", PREFIX, self.mode, self.fingerprint)
+    }
+}
+
+/// Поля заголовка после `#cipher/1`; `None`, если строка — не заголовок
+fn fields(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(PREFIX)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest)
+}
+
+/// Отпечаток данных: первые восемь байт SHA-256 в шестнадцатеричной записи.
+/// Отпечаток различает ключи, но не защищает их: по нему можно проверить
+/// догадку о ключе.
+pub fn fingerprint(data: &[u8]) -> String {
+    Sha256::digest(data)[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cipher;
+
+    #[test]
+    fn header_round_trips_through_text() {
+        let header = Header {
+            mode: "substitution".to_string(),
+            fingerprint: "3f9a0c1e5b7d2468".to_string(),
+        };
+        let text = format!("{}\nба\n", header);
+        assert_eq!(Header::split(&text).unwrap(), Some((header, "ба\n")));
+        assert_eq!(Header::split("ба\n").unwrap(), None);
+        assert!(Header::split("#cipher/1 mode=substitution\nба").is_err());
+    }
+
+    #[test]
+    fn same_key_in_other_layout_has_same_fingerprint() {
+        let key = Cipher::parse("а = б\nб = а\n").unwrap();
+        let reordered = Cipher::parse("# тот же ключ\nб = а\n\nа = б\n").unwrap();
+        assert_eq!(key.fingerprint(), reordered.fingerprint());
+        assert_eq!(key.fingerprint().len(), 16);
+    }
+
+    #[test]
+    fn different_key_has_different_fingerprint() {
+        let key = Cipher::parse("а = б\nб = а\n").unwrap();
+        let other = Cipher::parse("а = в\nв = а\n").unwrap();
+        assert_ne!(key.fingerprint(), other.fingerprint());
+    }
+}
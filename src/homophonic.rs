@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::Rng;
+
+use crate::{parse_entries, read_alphabet_file, CipherError};
+
+/// Омофонический шифр: каждому символу соответствует несколько вариантов
+/// шифра, из которых при шифровании случайно выбирается один.
+///
+/// Строка алфавита `а = x:3,y:1` означает, что `а` шифруется в `x` в трех
+/// случаях из четырех и в `y` в одном. Вес можно опустить, тогда он равен 1.
+/// Так как `,` и `:` разделяют варианты и веса, сами они вариантами быть не могут.
+#[derive(Debug)]
+pub struct HomophonicCipher {
+    encrypt_map: HashMap<char, Homophones>,
+    decrypt_map: HashMap<char, char>,
+}
+
+#[derive(Debug)]
+struct Homophones {
+    symbols: Vec<char>,
+    weights: WeightedIndex<u32>,
+}
+
+impl HomophonicCipher {
+    pub fn from_file(filename: &str) -> Result<Self, CipherError> {
+        Self::parse(&read_alphabet_file(filename)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, CipherError> {
+        let mut encrypt_map = HashMap::new();
+        let mut decrypt_map = HashMap::new();
+
+        for entry in parse_entries(content)? {
+            let original = entry.key.chars().next()
+                .ok_or(CipherError::EmptyKey { line: entry.line })?;
+
+            if encrypt_map.contains_key(&original) {
+                return Err(CipherError::DuplicateKey {
+                    line: entry.line,
+                    ch: original,
+                });
+            }
+
+            let mut symbols = Vec::new();
+            let mut weights = Vec::new();
+
+            for option in entry.value.split(',') {
+                let (symbol, weight) = match option.split_once(':') {
+                    Some((symbol, weight)) => (symbol.trim(), parse_weight(weight.trim(), entry.line)?),
+                    None => (option.trim(), 1),
+                };
+
+                let symbol = symbol.chars().next()
+                    .ok_or(CipherError::EmptyValue { line: entry.line })?;
+
+                if decrypt_map.contains_key(&symbol) {
+                    return Err(CipherError::DuplicateValue {
+                        line: entry.line,
+                        ch: symbol,
+                    });
+                }
+
+                decrypt_map.insert(symbol, original);
+                symbols.push(symbol);
+                weights.push(weight);
+            }
+
+            let weights = WeightedIndex::new(&weights)
+                .map_err(|_| CipherError::WeightOverflow { line: entry.line })?;
+
+            encrypt_map.insert(original, Homophones { symbols, weights });
+        }
+
+        Ok(HomophonicCipher {
+            encrypt_map,
+            decrypt_map,
+        })
+    }
+
+    /// Шифрует текст, выбирая вариант для каждого символа с учетом весов
+    pub fn encrypt<R: Rng + ?Sized>(&self, text: &str, rng: &mut R) -> String {
+        text.chars()
+            .map(|c| match self.encrypt_map.get(&c) {
+                Some(homophones) => homophones.symbols[homophones.weights.sample(rng)],
+                None => c,
+            })
+            .collect()
+    }
+
+    pub fn decrypt(&self, text: &str) -> String {
+        text.chars()
+            .map(|c| *self.decrypt_map.get(&c).unwrap_or(&c))
+            .collect()
+    }
+}
+
+fn parse_weight(weight: &str, line: usize) -> Result<u32, CipherError> {
+    match weight.parse::<u32>() {
+        Ok(weight) if weight > 0 => Ok(weight),
+        _ => Err(CipherError::InvalidWeight {
+            line,
+            weight: weight.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn choice_follows_weights() {
+        let cipher = HomophonicCipher::parse("а = x:3,y:1\n").unwrap();
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let encrypted = cipher.encrypt(&"а".repeat(10_000), &mut rng);
+
+        let x = encrypted.chars().filter(|&c| c == 'x').count();
+        let y = encrypted.chars().filter(|&c| c == 'y').count();
+        assert_eq!(x + y, 10_000);
+        // Ожидается 7500 из 10000 (стандартное отклонение около 43)
+        assert!((7_200..=7_800).contains(&x), "x: {}, y: {}", x, y);
+    }
+
+    #[test]
+    fn every_homophone_decrypts_back() {
+        let cipher = HomophonicCipher::parse("а = x,y,z\nб = w\n").unwrap();
+        let encrypted = cipher.encrypt("ааааааббаа", &mut ChaCha20Rng::seed_from_u64(1));
+        assert_eq!(cipher.decrypt(&encrypted), "ааааааббаа");
+        assert_eq!(cipher.decrypt("xyzw"), "аааб");
+    }
+
+    #[test]
+    fn invalid_weight_is_rejected() {
+        assert!(matches!(HomophonicCipher::parse("а = x:0\n"), Err(CipherError::InvalidWeight { line: 1, .. })));
+    }
+}
@@ -0,0 +1,303 @@
+//! Директива `@include` в файле алфавита: строка `@include общий.txt`
+//! подключает подстановки другого файла. Путь отсчитывается от каталога
+//! файла, в котором записана директива; включенный файл может сам
+//! содержать `@include`.
+//!
+//! Директивы понимают текстовые форматы («ключ = значение» и выровненный);
+//! в JSON-алфавите строка с `@include` остается частью содержимого. Каждый
+//! файл разбирается в своем формате, а результат объединяется в алфавит
+//! «ключ = значение». Подстановки самого файла считаются записанными после
+//! подстановок включенных в него файлов, поэтому при
+//! [`IncludeConflict::Override`] они уточняют общий алфавит.
+//!
+//! Директива `@order` переносится в объединенный алфавит только из
+//! основного файла; во включенных файлах она не действует.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::format::{order_directive, parse_pairs, AlphabetFormat};
+use crate::{CipherError, BOM};
+
+const DIRECTIVE: &str = "@include";
+
+/// Что делать, если символ определен в нескольких файлах по-разному
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum IncludeConflict {
+    /// Считать это ошибкой
+    #[default]
+    Error,
+    /// Использовать определение, записанное позже
+    Override,
+}
+
+/// Подставляет включенные файлы в алфавит `content`, прочитанный из `path`.
+///
+/// Возвращает `None`, если директив `@include` нет, — тогда алфавит
+/// разбирается как обычно. Иначе возвращает объединенный алфавит в формате
+/// «ключ = значение». Одинаковые определения символа в разных файлах
+/// конфликтом не считаются; значение, назначенное двум символам, — всегда ошибка.
+pub fn expand(
+    content: &str,
+    path: &Path,
+    format: AlphabetFormat,
+    allow_empty_value: bool,
+    conflict: IncludeConflict,
+) -> Result<Option<String>, CipherError> {
+    if matches!(format, AlphabetFormat::Json | AlphabetFormat::Csv) || !has_directive(content) {
+        return Ok(None);
+    }
+
+    let mut merged = Merged {
+        allow_empty_value,
+        conflict,
+        entries: Vec::new(),
+        index: HashMap::new(),
+    };
+    let mut stack = vec![canonical(path)?];
+    merged.add(content, path, format, &mut stack)?;
+    merged.check_values()?;
+
+    let order = content
+        .strip_prefix(BOM)
+        .unwrap_or(content)
+        .lines()
+        .filter(|line| order_directive(line).is_some())
+        .map(|line| format!("{}\n", line.trim()));
+    let entries = merged.entries.iter().map(|entry| match entry.value {
+        Some(value) => format!("{} = {}\n", entry.key, value),
+        None => format!("{} =\n", entry.key),
+    });
+    Ok(Some(order.chain(entries).collect()))
+}
+
+struct Entry {
+    key: char,
+    value: Option<char>,
+    /// Файл и строка определения для сообщений об ошибках
+    origin: String,
+}
+
+struct Merged {
+    allow_empty_value: bool,
+    conflict: IncludeConflict,
+    entries: Vec<Entry>,
+    /// Позиция символа в `entries`
+    index: HashMap<char, usize>,
+}
+
+impl Merged {
+    /// Добавляет подстановки файла: сначала файлов из директив `@include`
+    /// по порядку, затем остальных строк, разобранных в формате файла.
+    /// `stack` — цепочка включений, которая привела к этому файлу.
+    fn add(&mut self, content: &str, path: &Path, format: AlphabetFormat, stack: &mut Vec<PathBuf>) -> Result<(), CipherError> {
+        let content = content.strip_prefix(BOM).unwrap_or(content);
+        let directory = path.parent().unwrap_or(Path::new(""));
+
+        let (targets, own) = split(content);
+
+        for target in targets.into_iter().map(|target| directory.join(target)) {
+            let target_canonical = canonical(&target)?;
+            if stack.contains(&target_canonical) {
+                let chain: Vec<String> = stack
+                    .iter()
+                    .chain([&target_canonical])
+                    .map(|path| path.display().to_string())
+                    .collect();
+                return Err(CipherError::IncludeCycle(chain.join(" -> ")));
+            }
+
+            let included = fs::read_to_string(&target).map_err(|source| CipherError::IncludeRead {
+                path: target.display().to_string(),
+                source,
+            })?;
+            let included_format = AlphabetFormat::detect(&included);
+
+            stack.push(target_canonical);
+            if included_format == AlphabetFormat::Json {
+                self.add_pairs(&included, &target, included_format)?;
+            } else {
+                self.add(&included, &target, included_format, stack)?;
+            }
+            stack.pop();
+        }
+
+        if own.trim().is_empty() {
+            return Ok(());
+        }
+        self.add_pairs(&own, path, format)
+    }
+
+    fn add_pairs(&mut self, content: &str, path: &Path, format: AlphabetFormat) -> Result<(), CipherError> {
+        let pairs = parse_pairs(content, format, self.allow_empty_value, false).map_err(|source| CipherError::IncludeParse {
+            path: path.display().to_string(),
+            source: Box::new(source),
+        })?;
+
+        for pair in pairs {
+            let entry = Entry {
+                key: pair.key,
+                value: pair.value,
+                origin: format!("{}:{}", path.display(), pair.key_line),
+            };
+            match self.index.get(&pair.key) {
+                None => {
+                    self.index.insert(pair.key, self.entries.len());
+                    self.entries.push(entry);
+                }
+                Some(&position) if self.entries[position].value == entry.value => {}
+                Some(&position) => match self.conflict {
+                    IncludeConflict::Override => self.entries[position] = entry,
+                    IncludeConflict::Error => {
+                        return Err(CipherError::IncludeConflict {
+                            key: pair.key,
+                            first: self.entries[position].origin.clone(),
+                            second: entry.origin,
+                        })
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Значения объединенного алфавита не должны повторяться, иначе
+    /// расшифровка неоднозначна
+    fn check_values(&self) -> Result<(), CipherError> {
+        let mut seen: HashMap<char, &Entry> = HashMap::new();
+        for entry in &self.entries {
+            let Some(value) = entry.value else {
+                continue;
+            };
+            if let Some(first) = seen.insert(value, entry) {
+                return Err(CipherError::IncludeDuplicateValue {
+                    value,
+                    first: first.origin.clone(),
+                    second: entry.origin.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Файлы, подключенные к алфавиту `path` директивами `@include`, в том числе
+/// через другие включенные файлы. Файл, который не удалось прочитать, в
+/// список попадает, но его собственные включения неизвестны. Циклы не
+/// считаются ошибкой: каждый файл попадает в список один раз. Пути
+/// приводятся к каноническому виду, если файл существует.
+pub fn included_files(path: &Path) -> Vec<PathBuf> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut files = Vec::new();
+    let mut pending = vec![path.clone()];
+    while let Some(file) = pending.pop() {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        if matches!(AlphabetFormat::detect(&content), AlphabetFormat::Json | AlphabetFormat::Csv) {
+            continue;
+        }
+        let directory = file.parent().unwrap_or(Path::new(""));
+        for target in split(content.strip_prefix(BOM).unwrap_or(&content)).0 {
+            let target = directory.join(target);
+            let target = fs::canonicalize(&target).unwrap_or(target);
+            if target != path && !files.contains(&target) {
+                files.push(target.clone());
+                pending.push(target);
+            }
+        }
+    }
+    files
+}
+
+/// Разделяет алфавит на пути из директив `@include` и остальной текст.
+/// Строки директив в остальном тексте заменяются пустыми, чтобы номера
+/// строк в сообщениях об ошибках совпадали с файлом.
+pub fn split(content: &str) -> (Vec<&str>, String) {
+    let mut targets = Vec::new();
+    let mut own = String::with_capacity(content.len());
+    for line in content.lines() {
+        match directive(line) {
+            Some(target) => targets.push(target),
+            None => own.push_str(line),
+        }
+        own.push('\n');
+    }
+    (targets, own)
+}
+
+fn has_directive(content: &str) -> bool {
+    content.lines().any(|line| directive(line).is_some())
+}
+
+/// Путь из строки `@include путь`
+pub(crate) fn directive(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(DIRECTIVE)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+fn canonical(path: &Path) -> Result<PathBuf, CipherError> {
+    fs::canonicalize(path).map_err(|source| CipherError::IncludeRead {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cipher;
+
+    fn write(dir: &Path, name: &str, content: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn include_merges_relative_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("common")).unwrap();
+        write(&dir.path().join("common"), "base.txt", "а = б\nб = а\n");
+        let key = write(dir.path(), "key.txt", "@include common/base.txt\nв = г\nг = в\n");
+
+        let cipher = Cipher::from_file(&key).unwrap();
+        assert_eq!(cipher.encrypt("абвг"), "багв");
+        assert_eq!(included_files(Path::new(&key)), vec![fs::canonicalize(dir.path().join("common/base.txt")).unwrap()]);
+    }
+
+    #[test]
+    fn include_cycle_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = write(dir.path(), "first.txt", "@include second.txt\nа = б\n");
+        write(dir.path(), "second.txt", "@include first.txt\nб = а\n");
+
+        match Cipher::from_file(&first) {
+            Err(CipherError::IncludeCycle(chain)) => {
+                assert!(chain.contains("first.txt") && chain.contains("second.txt"), "{}", chain)
+            }
+            other => panic!("ожидалась ошибка цикла: {:?}", other),
+        }
+        assert_eq!(included_files(Path::new(&first)).len(), 1);
+    }
+
+    #[test]
+    fn conflicting_definitions_error_or_override() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "base.txt", "а = б\nб = а\n");
+        let content = "@include base.txt\nа = в\nв = а\nб = г\nг = б\n";
+        let key = write(dir.path(), "key.txt", content);
+        let expand = |conflict| expand(content, Path::new(&key), AlphabetFormat::KeyValue, false, conflict);
+
+        let err = expand(IncludeConflict::Error).unwrap_err();
+        assert!(matches!(err, CipherError::IncludeConflict { key: 'а', .. }), "{:?}", err);
+
+        let merged = expand(IncludeConflict::Override).unwrap().unwrap();
+        let cipher = Cipher::parse_as(&merged, AlphabetFormat::KeyValue).unwrap();
+        assert_eq!(cipher.encrypt("абвг"), "вгаб");
+    }
+}
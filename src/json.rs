@@ -0,0 +1,53 @@
+//! Шифрование строк внутри JSON-документа с сохранением его структуры
+
+use serde_json::{Map, Value};
+
+/// Применяет `f` ко всем строковым значениям документа (и к ключам объектов,
+/// если `keys`). Числа, логические значения и `null` не меняются.
+pub fn map_strings<E>(
+    value: Value,
+    keys: bool,
+    f: &mut impl FnMut(&str) -> Result<String, E>,
+) -> Result<Value, E> {
+    Ok(match value {
+        Value::String(text) => Value::String(f(&text)?),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| map_strings(item, keys, f))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(fields) => {
+            let mut mapped = Map::with_capacity(fields.len());
+            for (key, item) in fields {
+                let key = if keys { f(&key)? } else { key };
+                mapped.insert(key, map_strings(item, keys, f)?);
+            }
+            Value::Object(mapped)
+        }
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn upper(text: &str) -> Result<String, ()> {
+        Ok(text.to_uppercase())
+    }
+
+    #[test]
+    fn maps_strings_in_nested_document() {
+        let document = json!({"имя": "аб", "список": ["в", 1, {"г": "д"}], "флаг": true, "пусто": null, "число": 2.5});
+        assert_eq!(
+            map_strings(document.clone(), false, &mut upper).unwrap(),
+            json!({"имя": "АБ", "список": ["В", 1, {"г": "Д"}], "флаг": true, "пусто": null, "число": 2.5})
+        );
+        assert_eq!(
+            map_strings(document, true, &mut upper).unwrap(),
+            json!({"ИМЯ": "АБ", "СПИСОК": ["В", 1, {"Г": "Д"}], "ФЛАГ": true, "ПУСТО": null, "ЧИСЛО": 2.5})
+        );
+    }
+}
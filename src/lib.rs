@@ -0,0 +1,1356 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+
+pub mod bifid;
+pub mod blocks;
+mod builder;
+pub mod chunked;
+pub mod combined;
+mod error;
+pub mod fields;
+pub mod format;
+pub mod generate;
+pub mod header;
+pub mod homophonic;
+pub mod include;
+pub mod json;
+pub mod numeric;
+pub mod otp;
+pub mod positional;
+pub mod rle;
+pub mod rotor;
+pub mod score;
+pub mod text;
+pub mod word;
+
+pub use builder::CipherBuilder;
+pub use error::CipherError;
+pub use format::AlphabetFormat;
+pub use include::IncludeConflict;
+
+/// Метка порядка байтов, которую добавляют некоторые редакторы Windows
+pub const BOM: char = '\u{FEFF}';
+
+/// Направление преобразования
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+/// Статистика преобразования текста, которую возвращает [`Cipher::analyze`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Всего символов во входном тексте
+    pub total: usize,
+    /// Символов, для которых нашлась подстановка
+    pub substituted: usize,
+    /// Символы без подстановки и число их вхождений, в порядке возрастания
+    pub unmapped: BTreeMap<char, usize>,
+    /// Замененные (в том числе удаленные) символы и число их вхождений, в
+    /// порядке возрастания
+    pub substitutions: BTreeMap<char, usize>,
+}
+
+impl Stats {
+    /// Число символов, переданных без изменений
+    pub fn unmapped_count(&self) -> usize {
+        self.total - self.substituted
+    }
+
+    /// Замененные символы и число их вхождений, от самых частых к редким;
+    /// символы с одинаковым числом вхождений — в порядке возрастания
+    pub fn substitutions_by_count(&self) -> Vec<(char, usize)> {
+        let mut counts: Vec<(char, usize)> = self.substitutions.iter().map(|(&c, &count)| (c, count)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Добавляет подсчеты `other`, например статистику следующей части текста
+    pub fn merge(&mut self, other: Stats) {
+        self.total += other.total;
+        self.substituted += other.substituted;
+        for (c, count) in other.unmapped {
+            *self.unmapped.entry(c).or_insert(0) += count;
+        }
+        for (c, count) in other.substitutions {
+            *self.substitutions.entry(c).or_insert(0) += count;
+        }
+    }
+
+    /// Доля символов без подстановки, от 0 до 1
+    pub fn unmapped_ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.unmapped_count() as f64 / self.total as f64
+        }
+    }
+
+    /// Статистика в виде JSON для других программ:
+    ///
+    /// ```json
+    /// {"total": 5, "substituted": 4, "unmapped": 1,
+    ///  "unmapped_chars": [{"char": "!", "codepoint": "U+0021", "count": 1}]}
+    /// ```
+    ///
+    /// Символы в `unmapped_chars` идут в порядке возрастания кодовых точек.
+    pub fn to_json(&self) -> serde_json::Value {
+        let unmapped_chars: Vec<serde_json::Value> = self
+            .unmapped
+            .iter()
+            .map(|(&c, &count)| {
+                serde_json::json!({
+                    "char": c.to_string(),
+                    "codepoint": text::codepoint(c),
+                    "count": count,
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "total": self.total,
+            "substituted": self.substituted,
+            "unmapped": self.unmapped_count(),
+            "unmapped_chars": unmapped_chars,
+        })
+    }
+}
+
+/// Класс символов Unicode, которым можно ограничить подстановку
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CharClass {
+    /// Буквы любого алфавита
+    Letters,
+    /// Цифры
+    Digits,
+    /// Буквы и цифры
+    Alnum,
+}
+
+impl CharClass {
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            CharClass::Letters => c.is_alphabetic(),
+            CharClass::Digits => c.is_numeric(),
+            CharClass::Alnum => c.is_alphanumeric(),
+        }
+    }
+}
+
+/// Настройки разбора алфавита для [`Cipher::parse_with`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Формат алфавита; `None` — определить по содержимому
+    pub format: Option<AlphabetFormat>,
+    /// Разрешить пустое значение: такой символ удаляется при шифровании
+    pub allow_empty_value: bool,
+    /// Требовать, чтобы ни один символ не был одновременно ключом и значением
+    pub disjoint_sets: bool,
+    /// Первая запись алфавита в формате CSV — заголовок
+    pub csv_header: bool,
+}
+
+/// Результат поиска символа в алфавите, который возвращает [`Cipher::lookup`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    /// Символ заменяется указанным
+    Mapped(char),
+    /// Символ удаляется (пустое значение в алфавите)
+    Deleted,
+    /// Подстановки нет, символ остается без изменений
+    Unmapped,
+}
+
+/// Что делать с символом, для которого нет подстановки
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnmappedPolicy {
+    /// Оставить без изменений
+    #[default]
+    Keep,
+    /// Сменить регистр буквы на противоположный (см. [`text::swap_case`]).
+    /// Повторная смена регистра возвращает букву, поэтому расшифровка
+    /// восстанавливает текст.
+    SwapCase,
+}
+
+impl UnmappedPolicy {
+    fn apply(self, c: char) -> char {
+        match self {
+            UnmappedPolicy::Keep => c,
+            UnmappedPolicy::SwapCase => text::swap_case(c),
+        }
+    }
+}
+
+/// Изменение одной подстановки между двумя алфавитами, которое возвращает
+/// [`Cipher::compare`]. Значение `None` означает, что символ удаляется.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyChange {
+    Added { key: char, value: Option<char> },
+    Removed { key: char, value: Option<char> },
+    Changed { key: char, old: Option<char>, new: Option<char> },
+}
+
+#[derive(Debug)]
+pub struct Cipher {
+    encrypt_map: HashMap<char, char>,
+    decrypt_map: HashMap<char, char>,
+    /// Символы, которые удаляются при шифровании. Восстановить их при
+    /// расшифровке невозможно.
+    deleted: HashSet<char>,
+    /// Порядок символов из директивы `@order`
+    order: Vec<char>,
+    /// Преобразование символов без подстановки. Строгие методы
+    /// ([`Cipher::encrypt_strict`]) такие символы по-прежнему отклоняют.
+    unmapped: UnmappedPolicy,
+}
+
+impl Cipher {
+    /// Построитель шифра из отдельных подстановок (см. [`CipherBuilder`])
+    pub fn builder() -> CipherBuilder {
+        CipherBuilder::new()
+    }
+
+    /// Читает алфавит из файла. Директивы `@include` подключают другие
+    /// файлы (см. [`include`]); разные определения одного символа — ошибка.
+    pub fn from_file(filename: &str) -> Result<Self, CipherError> {
+        let content = read_alphabet_file(filename)?;
+        let format = AlphabetFormat::detect(&content);
+        match include::expand(&content, Path::new(filename), format, false, IncludeConflict::Error)? {
+            Some(merged) => Self::parse_as(&merged, AlphabetFormat::KeyValue),
+            None => Self::from_reader(content.as_bytes()),
+        }
+    }
+
+    /// Читает алфавит из любого источника: сетевого потока, встроенного
+    /// ресурса, `Cursor` в тестах. Формат определяется по содержимому, поэтому
+    /// источник читается до конца перед разбором.
+    pub fn from_reader(mut reader: impl BufRead) -> Result<Self, CipherError> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content).map_err(CipherError::Read)?;
+        Self::parse(&content)
+    }
+
+    /// Разбирает алфавит из строки, определяя формат по содержимому
+    /// (см. [`AlphabetFormat::detect`])
+    pub fn parse(content: &str) -> Result<Self, CipherError> {
+        Self::parse_as(content, AlphabetFormat::detect(content))
+    }
+
+    /// Разбирает алфавит заданного формата из строки
+    pub fn parse_as(content: &str, format: AlphabetFormat) -> Result<Self, CipherError> {
+        Self::parse_with(
+            content,
+            ParseOptions {
+                format: Some(format),
+                ..ParseOptions::default()
+            },
+        )
+    }
+
+    pub fn parse_with(content: &str, options: ParseOptions) -> Result<Self, CipherError> {
+        let format = options.format.unwrap_or_else(|| AlphabetFormat::detect(content));
+        let order = format::parse_order(content, format)?;
+        let pairs = format::parse_pairs(content, format, options.allow_empty_value, options.csv_header)?;
+        Self::from_pairs(pairs, order, options.disjoint_sets)
+    }
+
+    /// Собирает шифр из подстановок, проверяя, что ключи и значения не
+    /// повторяются (и, если `disjoint_sets`, не пересекаются). Общая проверка
+    /// для файлов алфавита и [`CipherBuilder`].
+    pub(crate) fn from_pairs(pairs: Vec<format::Pair>, order: Vec<char>, disjoint_sets: bool) -> Result<Self, CipherError> {
+        let mut encrypt_map = HashMap::new();
+        let mut decrypt_map = HashMap::new();
+        let mut deleted = HashSet::new();
+
+        for pair in pairs {
+            if encrypt_map.contains_key(&pair.key) || deleted.contains(&pair.key) {
+                return Err(CipherError::DuplicateKey {
+                    line: pair.key_line,
+                    ch: pair.key,
+                });
+            }
+
+            let Some(value) = pair.value else {
+                deleted.insert(pair.key);
+                continue;
+            };
+            if decrypt_map.contains_key(&value) {
+                return Err(CipherError::DuplicateValue {
+                    line: pair.value_line,
+                    ch: value,
+                });
+            }
+
+            encrypt_map.insert(pair.key, value);
+            decrypt_map.insert(value, pair.key);
+        }
+
+        if disjoint_sets {
+            let mut overlapping: Vec<char> = decrypt_map
+                .keys()
+                .copied()
+                .filter(|value| encrypt_map.contains_key(value) || deleted.contains(value))
+                .collect();
+            if !overlapping.is_empty() {
+                overlapping.sort_unstable();
+                let list: Vec<String> = overlapping.iter().map(|c| format!("'{}'", c)).collect();
+                return Err(CipherError::OverlappingSets(list.join(", ")));
+            }
+        }
+
+        Ok(Cipher {
+            encrypt_map,
+            decrypt_map,
+            deleted,
+            order,
+            unmapped: UnmappedPolicy::default(),
+        })
+    }
+
+    pub fn encrypt(&self, text: &str) -> String {
+        self.encrypt_iter(text.chars()).collect()
+    }
+
+    pub fn decrypt(&self, text: &str) -> String {
+        self.decrypt_iter(text.chars()).collect()
+    }
+
+    /// Шифрует текст, требуя подстановку для каждого символа. Первый
+    /// символ без подстановки возвращается как ошибка с его номером
+    /// (начиная с единицы); удаляемые символы подстановкой считаются.
+    pub fn encrypt_strict(&self, text: &str) -> Result<String, CipherError> {
+        self.substitute_strict(Direction::Encrypt, text)
+    }
+
+    /// Расшифровывает текст, требуя подстановку для каждого символа,
+    /// как [`Cipher::encrypt_strict`]
+    pub fn decrypt_strict(&self, text: &str) -> Result<String, CipherError> {
+        self.substitute_strict(Direction::Decrypt, text)
+    }
+
+    fn substitute_strict(&self, direction: Direction, text: &str) -> Result<String, CipherError> {
+        let map = self.map(direction);
+        let deleted = self.deleted_for(direction);
+        let mut result = String::with_capacity(text.len());
+
+        for (index, c) in text.chars().enumerate() {
+            match map.get(&c) {
+                Some(&value) => result.push(value),
+                None if deleted.is_some_and(|deleted| deleted.contains(&c)) => {}
+                None => return Err(CipherError::Passthrough { position: index + 1, ch: c }),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Подсчитывает, сколько символов текста будет заменено, а сколько
+    /// пройдет без изменений
+    pub fn analyze(&self, text: &str, direction: Direction) -> Stats {
+        let map = self.map(direction);
+        let mut stats = Stats::default();
+
+        for c in text.chars() {
+            stats.total += 1;
+            if map.contains_key(&c) || (direction == Direction::Encrypt && self.deleted.contains(&c)) {
+                stats.substituted += 1;
+                *stats.substitutions.entry(c).or_insert(0) += 1;
+            } else {
+                *stats.unmapped.entry(c).or_insert(0) += 1;
+            }
+        }
+
+        stats
+    }
+
+    /// Что шифр делает с одним символом в направлении `direction`
+    pub fn lookup(&self, c: char, direction: Direction) -> Lookup {
+        match self.map(direction).get(&c) {
+            Some(&substituted) => Lookup::Mapped(substituted),
+            None if self.deleted_for(direction).is_some_and(|deleted| deleted.contains(&c)) => Lookup::Deleted,
+            None => Lookup::Unmapped,
+        }
+    }
+
+    pub(crate) fn map(&self, direction: Direction) -> &HashMap<char, char> {
+        match direction {
+            Direction::Encrypt => &self.encrypt_map,
+            Direction::Decrypt => &self.decrypt_map,
+        }
+    }
+
+    /// Удаляемые при шифровании символы: для них в алфавите указано пустое значение
+    fn deleted_for(&self, direction: Direction) -> Option<&HashSet<char>> {
+        match direction {
+            Direction::Encrypt if !self.deleted.is_empty() => Some(&self.deleted),
+            _ => None,
+        }
+    }
+
+    /// Оставляет только подстановки, которые понадобятся, чтобы обработать
+    /// `text` в направлении `direction`: при шифровании — с ключами из
+    /// текста, при расшифровке — со значениями из него. Результат для этого
+    /// текста не меняется, а большой алфавит занимает меньше памяти. Для
+    /// другого текста или нескольких раундов подряд урезанный шифр непригоден.
+    pub fn retain_used(&mut self, text: &str, direction: Direction) {
+        let used: HashSet<char> = text.chars().collect();
+        match direction {
+            Direction::Encrypt => {
+                self.encrypt_map.retain(|original, _| used.contains(original));
+                self.decrypt_map.retain(|_, original| used.contains(original));
+                self.deleted.retain(|original| used.contains(original));
+            }
+            Direction::Decrypt => {
+                self.decrypt_map.retain(|substituted, _| used.contains(substituted));
+                self.encrypt_map.retain(|_, substituted| used.contains(substituted));
+                self.deleted.clear();
+            }
+        }
+        self.encrypt_map.shrink_to_fit();
+        self.decrypt_map.shrink_to_fit();
+        self.deleted.shrink_to_fit();
+    }
+
+    /// Число подстановок в алфавите, включая удаления
+    pub fn len(&self) -> usize {
+        self.encrypt_map.len() + self.deleted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.encrypt_map.is_empty() && self.deleted.is_empty()
+    }
+
+    /// `true`, если для символа есть подстановка (или удаление) при шифровании
+    pub fn contains(&self, c: char) -> bool {
+        self.encrypt_map.contains_key(&c) || self.deleted.contains(&c)
+    }
+
+    /// `true`, если каждый символ алфавита заменяется сам на себя
+    pub fn is_identity(&self) -> bool {
+        self.deleted.is_empty()
+            && self
+                .encrypt_map
+                .iter()
+                .all(|(original, substituted)| original == substituted)
+    }
+
+    /// Порядок символов, объявленный директивой `@order` текстового
+    /// алфавита; пустой, если порядок не объявлен. Алфавит «ключ = значение»
+    /// сам по себе не упорядочен, а режимам, которые работают с номерами
+    /// символов (роторы, сдвиг Цезаря), нужен именно порядок.
+    pub fn alphabet_order(&self) -> Vec<char> {
+        self.order.clone()
+    }
+
+    /// Символы, которые удаляются при шифровании, в порядке возрастания
+    pub fn deleted(&self) -> Vec<char> {
+        let mut deleted: Vec<char> = self.deleted.iter().copied().collect();
+        deleted.sort_unstable();
+        deleted
+    }
+
+    /// Символы, которые заменяются сами на себя, в порядке возрастания
+    pub fn fixed_points(&self) -> Vec<char> {
+        let mut points: Vec<char> = self
+            .encrypt_map
+            .iter()
+            .filter(|(original, substituted)| original == substituted)
+            .map(|(&original, _)| original)
+            .collect();
+        points.sort_unstable();
+        points
+    }
+
+    /// Непересекающиеся циклы подстановки: в цикле `[a, x, m]` символ `a`
+    /// заменяется на `x`, `x` — на `m`, а `m` — снова на `a`. Неподвижные
+    /// символы дают циклы длины 1. Каждый цикл начинается с наименьшего
+    /// символа, циклы упорядочены по нему же.
+    ///
+    /// Символы, которые не возвращаются к себе (значение не является ключом
+    /// алфавита или символ удаляется), ни в один цикл не входят.
+    pub fn cycles(&self) -> Vec<Vec<char>> {
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+
+        for (start, _) in self.mappings() {
+            if visited.contains(&start) {
+                continue;
+            }
+
+            let mut cycle = vec![start];
+            let mut current = start;
+            let closed = loop {
+                match self.encrypt_map.get(&current) {
+                    Some(&next) if next == start => break true,
+                    Some(&next) if !cycle.contains(&next) => {
+                        cycle.push(next);
+                        current = next;
+                    }
+                    _ => break false,
+                }
+            };
+
+            if closed {
+                visited.extend(cycle.iter().copied());
+                cycles.push(cycle);
+            }
+        }
+
+        cycles
+    }
+
+    /// Порядок подстановки — наименьшее общее кратное длин циклов: столько раз
+    /// нужно применить шифр, чтобы вернуться к исходному тексту. `None`, если
+    /// подстановка не является перестановкой (есть символы вне циклов) или
+    /// порядок не помещается в `u128`.
+    pub fn order(&self) -> Option<u128> {
+        let cycles = self.cycles();
+        let in_cycles: usize = cycles.iter().map(Vec::len).sum();
+        if in_cycles != self.len() {
+            return None;
+        }
+
+        cycles.iter().try_fold(1u128, |order, cycle| {
+            let length = cycle.len() as u128;
+            (order / gcd(order, length)).checked_mul(length)
+        })
+    }
+
+    /// Сдвиг, которым подстановка совпадает с шифром Цезаря над набором
+    /// `charset`: символ с номером `i` в наборе заменяется символом с номером
+    /// `i + сдвиг` по модулю длины набора. Заглавные варианты символов набора
+    /// должны сдвигаться так же. Символы вне набора не учитываются.
+    ///
+    /// `None`, если из набора в алфавите меньше двух символов или хотя бы
+    /// один из них заменяется не по общему сдвигу.
+    pub fn caesar_shift(&self, charset: &[char]) -> Option<usize> {
+        let uppercase: Vec<char> = charset.iter().flat_map(|c| c.to_uppercase()).collect();
+        let sets: Vec<&[char]> = if uppercase.len() == charset.len() && uppercase != charset {
+            vec![charset, &uppercase]
+        } else {
+            vec![charset]
+        };
+
+        let mut shift = None;
+        let mut checked = 0;
+        for set in sets {
+            for (index, original) in set.iter().enumerate() {
+                let Some(substituted) = self.encrypt_map.get(original) else {
+                    continue;
+                };
+                let position = set.iter().position(|c| c == substituted)?;
+                let offset = (position + set.len() - index) % set.len();
+                if *shift.get_or_insert(offset) != offset {
+                    return None;
+                }
+                checked += 1;
+            }
+        }
+
+        if checked < 2 {
+            return None;
+        }
+        shift
+    }
+
+    /// Все подстановки, отсортированные по исходному символу.
+    ///
+    /// Порядок обхода `HashMap` меняется от запуска к запуску, поэтому все,
+    /// что видит пользователь, должно строиться через этот метод.
+    pub fn mappings(&self) -> Vec<(char, char)> {
+        let mut mappings: Vec<(char, char)> = self
+            .encrypt_map
+            .iter()
+            .map(|(&original, &substituted)| (original, substituted))
+            .collect();
+        mappings.sort_unstable();
+        mappings
+    }
+
+    /// Алфавит в каноническом виде: по одной строке "ключ = значение",
+    /// отсортированные по ключу. Удаляемые символы записываются с пустым
+    /// значением.
+    pub fn to_canonical_string(&self) -> String {
+        self.entries()
+            .into_iter()
+            .map(|(original, substituted)| match substituted {
+                Some(substituted) => format!("{} = {}\n", original, substituted),
+                None => format!("{} =\n", original),
+            })
+            .collect()
+    }
+
+    /// Отпечаток ключа (см. [`header::fingerprint`]) по канонической записи
+    /// [`Cipher::to_canonical_string`]: формат файла, комментарии и порядок
+    /// строк на него не влияют.
+    pub fn fingerprint(&self) -> String {
+        header::fingerprint(self.to_canonical_string().as_bytes())
+    }
+
+    /// Различия между этим алфавитом и более новым `newer`, отсортированные
+    /// по исходному символу. Формат записи алфавитов не учитывается.
+    pub fn compare(&self, newer: &Cipher) -> Vec<KeyChange> {
+        let old: HashMap<char, Option<char>> = self.entries().into_iter().collect();
+        let new: HashMap<char, Option<char>> = newer.entries().into_iter().collect();
+
+        let mut keys: Vec<char> = old.keys().chain(new.keys()).copied().collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        keys.into_iter()
+            .filter_map(|key| match (old.get(&key), new.get(&key)) {
+                (None, Some(&value)) => Some(KeyChange::Added { key, value }),
+                (Some(&value), None) => Some(KeyChange::Removed { key, value }),
+                (Some(&old), Some(&new)) if old != new => Some(KeyChange::Changed { key, old, new }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Подстановки и удаления, отсортированные по исходному символу;
+    /// `None` — символ удаляется
+    fn entries(&self) -> Vec<(char, Option<char>)> {
+        let mut entries: Vec<(char, Option<char>)> = self
+            .mappings()
+            .into_iter()
+            .map(|(original, substituted)| (original, Some(substituted)))
+            .chain(self.deleted.iter().map(|&original| (original, None)))
+            .collect();
+        entries.sort_unstable();
+        entries
+    }
+
+    /// Подстановка в виде графа Graphviz (DOT): вершина для каждого символа
+    /// и ребро от исходного символа к заменяющему. Подстановка разбивается
+    /// на циклы, и на рисунке они видны как замкнутые контуры. Удаляемые
+    /// символы ведут в отдельную точку.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cipher {\n");
+
+        for (original, substituted) in self.mappings() {
+            dot.push_str(&format!("    {} -> {};\n", dot_id(original), dot_id(substituted)));
+        }
+
+        let deleted = self.deleted();
+        if !deleted.is_empty() {
+            dot.push_str("    deleted [shape=point, label=\"\"];\n");
+            for original in deleted {
+                dot.push_str(&format!("    {} -> deleted [style=dashed];\n", dot_id(original)));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Алфавит в виде таблицы CSV для редактирования в электронных таблицах:
+    /// по строке "ключ,значение", отсортированные по ключу, с заголовком, если
+    /// `header`. У удаляемых символов значение пустое. Результат читается
+    /// обратно форматом [`AlphabetFormat::Csv`].
+    pub fn to_csv(&self, header: bool) -> String {
+        let mut csv = String::new();
+        if header {
+            csv.push_str("ключ,значение\n");
+        }
+        for (original, substituted) in self.entries() {
+            let value = substituted.map(csv_field).unwrap_or_default();
+            csv.push_str(&format!("{},{}\n", csv_field(original), value));
+        }
+        csv
+    }
+
+    /// Шифрует только символы, для которых `substitutable` возвращает `true`;
+    /// остальные передаются без изменений, даже если для них есть подстановка
+    pub fn encrypt_where(&self, text: &str, substitutable: impl Fn(char) -> bool) -> String {
+        substitute_where(&self.encrypt_map, self.deleted_for(Direction::Encrypt), self.unmapped, text, substitutable)
+    }
+
+    /// Расшифровывает только символы, для которых `substitutable` возвращает `true`
+    pub fn decrypt_where(&self, text: &str, substitutable: impl Fn(char) -> bool) -> String {
+        substitute_where(&self.decrypt_map, None, self.unmapped, text, substitutable)
+    }
+
+    /// Шифрует текст, после каждых `chunk_chars` символов вызывая
+    /// `progress(обработано, всего)` — например, чтобы показать ход
+    /// преобразования большого текста. Последний вызов всегда сообщает
+    /// `всего` обработанных символов.
+    pub fn encrypt_with_progress(&self, text: &str, chunk_chars: usize, progress: impl FnMut(usize, usize)) -> String {
+        self.substitute_with_progress(Direction::Encrypt, text, chunk_chars, progress)
+    }
+
+    /// Расшифровывает текст, сообщая о ходе работы так же, как
+    /// [`Cipher::encrypt_with_progress`]
+    pub fn decrypt_with_progress(&self, text: &str, chunk_chars: usize, progress: impl FnMut(usize, usize)) -> String {
+        self.substitute_with_progress(Direction::Decrypt, text, chunk_chars, progress)
+    }
+
+    fn substitute_with_progress(
+        &self,
+        direction: Direction,
+        text: &str,
+        chunk_chars: usize,
+        mut progress: impl FnMut(usize, usize),
+    ) -> String {
+        let map = self.map(direction);
+        let deleted = self.deleted_for(direction);
+        let total = text.chars().count();
+        let chunk_chars = chunk_chars.max(1);
+        let mut result = String::with_capacity(text.len());
+
+        // Счет идет по входным символам, поэтому удаленные тоже учитываются
+        for (index, c) in text.chars().enumerate() {
+            if !deleted.is_some_and(|deleted| deleted.contains(&c)) {
+                result.push(map.get(&c).copied().unwrap_or_else(|| self.unmapped.apply(c)));
+            }
+            let processed = index + 1;
+            if processed.is_multiple_of(chunk_chars) {
+                progress(processed, total);
+            }
+        }
+        if total == 0 || !total.is_multiple_of(chunk_chars) {
+            progress(total, total);
+        }
+
+        result
+    }
+
+    /// Шифрует строку на месте.
+    ///
+    /// Если ни один символ не удаляется и каждая подстановка сохраняет длину
+    /// символа в байтах UTF-8 (например, кириллица на кириллицу или латиница
+    /// на латиницу), символы заменяются прямо в буфере строки без выделения
+    /// памяти. Иначе строка собирается заново, как в [`Cipher::encrypt`].
+    pub fn encrypt_in_place(&self, text: &mut String) {
+        self.substitute_in_place(Direction::Encrypt, text);
+    }
+
+    /// Расшифровывает строку на месте при тех же условиях, что и
+    /// [`Cipher::encrypt_in_place`]
+    pub fn decrypt_in_place(&self, text: &mut String) {
+        self.substitute_in_place(Direction::Decrypt, text);
+    }
+
+    fn substitute_in_place(&self, direction: Direction, text: &mut String) {
+        let map = self.map(direction);
+        let same_width = map.iter().all(|(key, value)| key.len_utf8() == value.len_utf8());
+        if !same_width || self.deleted_for(direction).is_some() || self.unmapped != UnmappedPolicy::Keep {
+            *text = match direction {
+                Direction::Encrypt => self.encrypt(text),
+                Direction::Decrypt => self.decrypt(text),
+            };
+            return;
+        }
+
+        let mut bytes = std::mem::take(text).into_bytes();
+        let mut position = 0;
+        while position < bytes.len() {
+            let width = utf8_width(bytes[position]);
+            let c = std::str::from_utf8(&bytes[position..position + width])
+                .ok()
+                .and_then(|symbol| symbol.chars().next())
+                .expect("буфер строки содержит корректный UTF-8");
+            if let Some(value) = map.get(&c) {
+                value.encode_utf8(&mut bytes[position..position + width]);
+            }
+            position += width;
+        }
+        *text = String::from_utf8(bytes).expect("замена символа той же длины сохраняет корректный UTF-8");
+    }
+
+    /// Шифрует символы по мере их запроса, не собирая результат в строку
+    pub fn encrypt_iter<I>(&self, chars: I) -> Substitute<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = char>,
+    {
+        Substitute {
+            chars: chars.into_iter(),
+            map: &self.encrypt_map,
+            deleted: self.deleted_for(Direction::Encrypt),
+            unmapped: self.unmapped,
+        }
+    }
+
+    /// Расшифровывает символы по мере их запроса, не собирая результат в строку
+    pub fn decrypt_iter<I>(&self, chars: I) -> Substitute<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = char>,
+    {
+        Substitute {
+            chars: chars.into_iter(),
+            map: &self.decrypt_map,
+            deleted: None,
+            unmapped: self.unmapped,
+        }
+    }
+
+    /// Шифрует текст, дописывая результат в `out`, например в `String`,
+    /// которая переиспользуется между вызовами: память выделяет вызывающий
+    /// код, а не шифр. Ошибку возвращает только сам `out`.
+    pub fn encrypt_into(&self, input: &str, out: &mut impl fmt::Write) -> fmt::Result {
+        self.encrypt_iter(input.chars()).try_for_each(|c| out.write_char(c))
+    }
+
+    /// Расшифровывает текст, дописывая результат в `out`, как
+    /// [`Cipher::encrypt_into`]
+    pub fn decrypt_into(&self, input: &str, out: &mut impl fmt::Write) -> fmt::Result {
+        self.decrypt_iter(input.chars()).try_for_each(|c| out.write_char(c))
+    }
+}
+
+/// Строка файла алфавита вида "ключ = значение"
+pub(crate) struct Entry<'a> {
+    /// Номер строки, начиная с единицы
+    pub line: usize,
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+/// Читает файл алфавита; ошибка чтения запоминает путь к файлу
+pub(crate) fn read_alphabet_file(filename: &str) -> Result<String, CipherError> {
+    fs::read_to_string(filename).map_err(|source| CipherError::Io {
+        path: filename.into(),
+        source,
+    })
+}
+
+/// Разбирает строки "ключ = значение", пропуская пустые строки, комментарии
+/// и метку порядка байтов в начале
+pub(crate) fn parse_entries(content: &str) -> Result<Vec<Entry<'_>>, CipherError> {
+    parse_entries_with(content, false)
+}
+
+/// То же, что [`parse_entries`], но с `allow_empty_value` строка "ключ ="
+/// дает запись с пустым значением, а не ошибку
+pub(crate) fn parse_entries_with(content: &str, allow_empty_value: bool) -> Result<Vec<Entry<'_>>, CipherError> {
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+    let mut entries = Vec::new();
+
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || format::order_directive(line).is_some() {
+            continue;
+        }
+
+        let equals_pos = line.find('=').ok_or_else(|| CipherError::MissingSeparator {
+            line: line_number + 1,
+            content: line.to_string(),
+        })?;
+
+        let key = line[..equals_pos].trim();
+        let value = line[equals_pos + 1..].trim();
+
+        if key.is_empty() {
+            return Err(CipherError::EmptyKey { line: line_number + 1 });
+        }
+        if value.is_empty() && !allow_empty_value {
+            return Err(CipherError::EmptyValue { line: line_number + 1 });
+        }
+
+        entries.push(Entry {
+            line: line_number + 1,
+            key,
+            value,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Символ как поле CSV: в кавычках, если в нем есть запятая, кавычка или
+/// пробельный символ, с удвоенными кавычками
+fn csv_field(c: char) -> String {
+    match c {
+        '"' => r#""""""#.to_string(),
+        ',' => r#"",""#.to_string(),
+        _ if c.is_whitespace() => format!("\"{}\"", c),
+        _ => c.to_string(),
+    }
+}
+
+/// Символ как идентификатор вершины DOT: строка в кавычках, в которой
+/// экранированы кавычки, обратная косая черта и переводы строк
+fn dot_id(c: char) -> String {
+    let escaped = match c {
+        '"' => r#"\""#.to_string(),
+        '\\' => r"\\".to_string(),
+        '\n' => r"\n".to_string(),
+        '\r' => r"\r".to_string(),
+        _ => c.to_string(),
+    };
+    format!("\"{}\"", escaped)
+}
+
+/// Длина символа UTF-8 в байтах по его первому байту
+fn utf8_width(first: u8) -> usize {
+    match first {
+        0x00..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    }
+}
+
+/// Записывает пары в формате файла алфавита, по одной на строку
+pub fn format_entries(pairs: impl IntoIterator<Item = (char, char)>) -> String {
+    pairs
+        .into_iter()
+        .map(|(original, substituted)| format!("{} = {}\n", original, substituted))
+        .collect()
+}
+
+fn substitute_where(
+    map: &HashMap<char, char>,
+    deleted: Option<&HashSet<char>>,
+    unmapped: UnmappedPolicy,
+    text: &str,
+    substitutable: impl Fn(char) -> bool,
+) -> String {
+    text.chars()
+        .filter_map(|c| {
+            if !substitutable(c) {
+                return Some(c);
+            }
+            if deleted.is_some_and(|deleted| deleted.contains(&c)) {
+                return None;
+            }
+            Some(map.get(&c).copied().unwrap_or_else(|| unmapped.apply(c)))
+        })
+        .collect()
+}
+
+/// Ленивое посимвольное преобразование, которое возвращают
+/// [`Cipher::encrypt_iter`] и [`Cipher::decrypt_iter`]
+#[derive(Debug, Clone)]
+pub struct Substitute<'a, I> {
+    chars: I,
+    map: &'a HashMap<char, char>,
+    deleted: Option<&'a HashSet<char>>,
+    unmapped: UnmappedPolicy,
+}
+
+impl<I: Iterator<Item = char>> Iterator for Substitute<'_, I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            let c = self.chars.next()?;
+            if self.deleted.is_some_and(|deleted| deleted.contains(&c)) {
+                continue;
+            }
+            return Some(self.map.get(&c).copied().unwrap_or_else(|| self.unmapped.apply(c)));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.chars.size_hint();
+        match self.deleted {
+            Some(_) => (0, upper),
+            None => (lower, upper),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_separator_reports_line_and_content() {
+        match Cipher::parse("а = б\nвг\nд = е\n") {
+            Err(CipherError::MissingSeparator { line, content }) => assert_eq!((line, content.as_str()), (2, "вг")),
+            other => panic!("ожидалась MissingSeparator, получено {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_key_reports_line() {
+        assert!(matches!(Cipher::parse("а = б\n = в\n"), Err(CipherError::EmptyKey { line: 2 })));
+    }
+
+    #[test]
+    fn duplicate_key_reports_line_and_char() {
+        assert!(matches!(
+            Cipher::parse("а = б\nб = в\nа = г\n"),
+            Err(CipherError::DuplicateKey { line: 3, ch: 'а' })
+        ));
+    }
+
+    #[test]
+    fn duplicate_value_reports_line_and_char() {
+        assert!(matches!(
+            Cipher::parse("а = б\nв = б\n"),
+            Err(CipherError::DuplicateValue { line: 2, ch: 'б' })
+        ));
+    }
+
+    #[test]
+    fn canonical_string_does_not_depend_on_hash_order() {
+        let content: String = ('а'..='я').zip(('а'..='я').rev()).map(|(key, value)| format!("{} = {}\n", key, value)).collect();
+        // Каждая HashMap получает свое случайное состояние хеширования
+        let first = Cipher::parse(&content).unwrap();
+        let second = Cipher::parse(&content).unwrap();
+        assert_eq!(first.to_canonical_string().as_bytes(), second.to_canonical_string().as_bytes());
+        assert_eq!(first.mappings(), second.mappings());
+        assert_eq!(first.to_canonical_string(), content);
+    }
+
+    #[test]
+    fn bom_before_first_line_is_ignored() {
+        let cipher = Cipher::parse("\u{feff}а = б\nб = а\n").unwrap();
+        assert_eq!(cipher.mappings(), [('а', 'б'), ('б', 'а')]);
+    }
+
+    #[test]
+    fn io_error_keeps_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.txt");
+
+        let err = Cipher::from_file(missing.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("missing.txt"), "{}", err);
+        match err {
+            CipherError::Io { path, source } => {
+                assert_eq!(path, missing);
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            other => panic!("ожидалась Io, получено {:?}", other),
+        }
+    }
+
+    #[test]
+    fn iterator_adapters_match_string_methods() {
+        let options = ParseOptions {
+            allow_empty_value: true,
+            ..ParseOptions::default()
+        };
+        let cipher = Cipher::parse_with("а = б\nб = а\nъ =\n", options).unwrap();
+        let text = "абъв аб";
+        let encrypted: String = cipher.encrypt_iter(text.chars()).collect();
+        assert_eq!(encrypted, cipher.encrypt(text));
+        let decrypted: String = cipher.decrypt_iter(encrypted.chars()).collect();
+        assert_eq!(decrypted, cipher.decrypt(&encrypted));
+        assert_eq!(decrypted, "абв аб");
+    }
+
+    #[test]
+    fn char_classes_on_mixed_input() {
+        let cipher = Cipher::parse("а = б\nб = а\n1 = 2\n2 = 1\n! = ?\n").unwrap();
+        let text = "а1!Z б2";
+        assert_eq!(cipher.encrypt_where(text, |c| CharClass::Letters.contains(c)), "б1!Z а2");
+        assert_eq!(cipher.encrypt_where(text, |c| CharClass::Digits.contains(c)), "а2!Z б1");
+        assert_eq!(cipher.encrypt_where(text, |c| CharClass::Alnum.contains(c)), "б2!Z а1");
+        assert_eq!(cipher.decrypt_where("б1!Z а2", |c| CharClass::Letters.contains(c)), text);
+    }
+
+    #[test]
+    fn identity_map_is_all_fixed_points() {
+        let cipher = Cipher::parse("а = а\nб = б\n").unwrap();
+        assert!(cipher.is_identity());
+        assert_eq!(cipher.fixed_points(), vec!['а', 'б']);
+    }
+
+    #[test]
+    fn fixed_point_free_map() {
+        let cipher = Cipher::parse("а = б\nб = в\nв = а\n").unwrap();
+        assert!(!cipher.is_identity());
+        assert!(cipher.fixed_points().is_empty());
+    }
+
+    #[test]
+    fn mixed_map_lists_fixed_points() {
+        let cipher = Cipher::parse("а = б\nб = а\nв = в\nг = г\n").unwrap();
+        assert!(!cipher.is_identity());
+        assert_eq!(cipher.fixed_points(), vec!['в', 'г']);
+    }
+
+    #[test]
+    fn excluded_char_passes_through_but_stays_mapped() {
+        let cipher = Cipher::parse("а = б\nб = а\nв = г\nг = в\n").unwrap();
+        let excluded = ['а'];
+        assert_eq!(cipher.encrypt_where("абв", |c| !excluded.contains(&c)), "ааг");
+        assert_eq!(cipher.encrypt("абв"), "баг");
+    }
+
+    #[test]
+    fn progress_is_reported_every_chunk() {
+        let cipher = Cipher::parse("а = б\nб = а\n").unwrap();
+        let text = "аб".repeat(5);
+
+        let mut calls = Vec::new();
+        let encrypted = cipher.encrypt_with_progress(&text, 3, |processed, total| calls.push((processed, total)));
+        assert_eq!(encrypted, cipher.encrypt(&text));
+        assert_eq!(calls, vec![(3, 10), (6, 10), (9, 10), (10, 10)]);
+
+        let mut calls = 0;
+        cipher.decrypt_with_progress(&encrypted, 5, |_, _| calls += 1);
+        assert_eq!(calls, 2);
+
+        let mut calls = Vec::new();
+        cipher.encrypt_with_progress("", 3, |processed, total| calls.push((processed, total)));
+        assert_eq!(calls, vec![(0, 0)]);
+    }
+
+    #[test]
+    fn dot_output_is_well_formed_and_escaped() {
+        let options = ParseOptions {
+            allow_empty_value: true,
+            ..ParseOptions::default()
+        };
+        let cipher = Cipher::parse_with("\" = \\\n\\ = \"\nа = б\nб = а\nъ =\n", options).unwrap();
+        assert_eq!(
+            cipher.to_dot(),
+            r#"digraph cipher {
+    "\"" -> "\\";
+    "\\" -> "\"";
+    "а" -> "б";
+    "б" -> "а";
+    deleted [shape=point, label=""];
+    "ъ" -> deleted [style=dashed];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn cycles_of_known_permutation() {
+        let cipher = Cipher::parse("a = x\nx = m\nm = a\nb = y\ny = b\nc = c\n").unwrap();
+        assert_eq!(cipher.cycles(), vec![vec!['a', 'x', 'm'], vec!['b', 'y'], vec!['c']]);
+        assert_eq!(cipher.order(), Some(6));
+    }
+
+    #[test]
+    fn pure_transposition_has_order_two() {
+        let cipher = Cipher::parse("а = б\nб = а\nв = г\nг = в\n").unwrap();
+        assert_eq!(cipher.cycles(), vec![vec!['а', 'б'], vec!['в', 'г']]);
+        assert_eq!(cipher.order(), Some(2));
+    }
+
+    #[test]
+    fn open_chain_is_not_a_permutation() {
+        let cipher = Cipher::parse("а = б\nб = в\nг = д\nд = г\n").unwrap();
+        assert_eq!(cipher.cycles(), vec![vec!['г', 'д']]);
+        assert_eq!(cipher.order(), None);
+    }
+
+    #[test]
+    fn in_place_matches_encrypt() {
+        let same_width = Cipher::parse("а = б\nб = а\nx = y\ny = x\n").unwrap();
+        let mixed_width = Cipher::parse("а = a\na = а\nб = б\n").unwrap();
+        let options = ParseOptions {
+            allow_empty_value: true,
+            ..ParseOptions::default()
+        };
+        let deleting = Cipher::parse_with("а = б\nб = а\nъ =\n", options).unwrap();
+
+        for cipher in [&same_width, &mixed_width, &deleting] {
+            let text = "аб xy ъa!";
+            let mut buffer = text.to_string();
+            cipher.encrypt_in_place(&mut buffer);
+            assert_eq!(buffer, cipher.encrypt(text));
+            let expected = cipher.decrypt(&buffer);
+            cipher.decrypt_in_place(&mut buffer);
+            assert_eq!(buffer, expected);
+        }
+    }
+
+    #[test]
+    fn in_place_fast_path_keeps_buffer() {
+        let cipher = Cipher::parse("а = б\nб = а\n").unwrap();
+        let mut buffer = "абв".repeat(100);
+        let pointer = buffer.as_ptr();
+        cipher.encrypt_in_place(&mut buffer);
+        assert_eq!(buffer, "бав".repeat(100));
+        assert_eq!(buffer.as_ptr(), pointer);
+    }
+
+    #[test]
+    fn strict_reports_single_unmapped_char() {
+        let cipher = Cipher::parse("а = б\nб = а\n").unwrap();
+        assert_eq!(cipher.encrypt_strict("абба").unwrap(), "бааб");
+        assert!(matches!(
+            cipher.encrypt_strict("аб!ба"),
+            Err(CipherError::Passthrough { position: 3, ch: '!' })
+        ));
+        assert!(matches!(
+            cipher.decrypt_strict("баx"),
+            Err(CipherError::Passthrough { position: 3, ch: 'x' })
+        ));
+    }
+
+    #[test]
+    fn compare_lists_each_kind_of_change() {
+        let options = ParseOptions {
+            allow_empty_value: true,
+            ..ParseOptions::default()
+        };
+        let old = Cipher::parse_with("а = б\nб = а\nв = г\nд =\n", options).unwrap();
+        let new = Cipher::parse_with("# новая версия\nб = а\nа = б\nв = е\nд = ж\nё = ё\n", options).unwrap();
+        assert_eq!(
+            old.compare(&new),
+            vec![
+                KeyChange::Changed { key: 'в', old: Some('г'), new: Some('е') },
+                KeyChange::Changed { key: 'д', old: None, new: Some('ж') },
+                KeyChange::Added { key: 'ё', value: Some('ё') },
+            ]
+        );
+        assert_eq!(new.compare(&new), vec![]);
+        assert_eq!(new.compare(&old)[2], KeyChange::Removed { key: 'ё', value: Some('ё') });
+    }
+
+    #[test]
+    fn from_reader_reads_cursor() {
+        let cipher = Cipher::from_reader(std::io::Cursor::new("# ключ\nа = б\nб = а\n")).unwrap();
+        assert_eq!(cipher.encrypt("аб"), "ба");
+
+        let aligned = Cipher::from_reader(std::io::Cursor::new("аб\nба\n")).unwrap();
+        assert_eq!(aligned.encrypt("аб"), "ба");
+    }
+
+    #[test]
+    fn from_reader_reports_invalid_utf8() {
+        let err = Cipher::from_reader(std::io::Cursor::new(&[0xff, 0xfe][..])).unwrap_err();
+        assert!(matches!(err, CipherError::Read(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn caesar_shift_detects_shift_in_both_cases() {
+        let charset: Vec<char> = "абвгд".chars().collect();
+        let shifted = Cipher::parse("а = г\nб = д\nв = а\nг = б\nд = в\nА = Г\nБ = Д\nВ = А\nГ = Б\nД = В\n").unwrap();
+        assert_eq!(shifted.caesar_shift(&charset), Some(3));
+
+        let wrong_upper = Cipher::parse("а = г\nб = д\nв = а\nг = б\nд = в\nА = Б\nБ = А\n").unwrap();
+        assert_eq!(wrong_upper.caesar_shift(&charset), None);
+
+        let random = Cipher::parse("а = в\nб = а\nв = д\nг = б\nд = г\n").unwrap();
+        assert_eq!(random.caesar_shift(&charset), None);
+    }
+
+    #[test]
+    fn caesar_shift_needs_two_characters_of_charset() {
+        let charset: Vec<char> = "абвгд".chars().collect();
+        let single = Cipher::parse("а = б\nx = y\n").unwrap();
+        assert_eq!(single.caesar_shift(&charset), None);
+    }
+
+    #[test]
+    fn all_comments_file_parses_to_empty_cipher() {
+        let cipher = Cipher::parse("# только комментарии\n\n   \n# и пустые строки\n").unwrap();
+        assert!(cipher.is_empty());
+        assert_eq!(cipher.len(), 0);
+    }
+
+    #[test]
+    fn encrypt_into_reuses_string_buffer() {
+        let cipher = Cipher::parse("а = б\nб = а\nв = г\nг = в\n").unwrap();
+        let mut buffer = String::with_capacity(64);
+        let allocation = buffer.as_ptr();
+
+        for (input, expected) in [("абвг", "багв"), ("где", "вде"), ("", "")] {
+            buffer.clear();
+            cipher.encrypt_into(input, &mut buffer).unwrap();
+            assert_eq!(buffer, expected);
+            assert_eq!(buffer, cipher.encrypt(input));
+
+            buffer.clear();
+            cipher.decrypt_into(expected, &mut buffer).unwrap();
+            assert_eq!(buffer, cipher.decrypt(expected));
+        }
+        assert_eq!(buffer.as_ptr(), allocation);
+
+        // Результат дописывается к тому, что уже есть в буфере
+        buffer.clear();
+        cipher.encrypt_into("аб", &mut buffer).unwrap();
+        cipher.encrypt_into("вг", &mut buffer).unwrap();
+        assert_eq!(buffer, "багв");
+    }
+
+    #[test]
+    fn disjoint_sets_accepts_disjoint_map() {
+        let options = ParseOptions { disjoint_sets: true, ..ParseOptions::default() };
+        let cipher = Cipher::parse_with("а = x\nб = y\nв = z\n", options).unwrap();
+        assert_eq!(cipher.encrypt("абв"), "xyz");
+    }
+
+    #[test]
+    fn disjoint_sets_lists_overlapping_chars() {
+        let options = ParseOptions { disjoint_sets: true, ..ParseOptions::default() };
+        match Cipher::parse_with("а = б\nб = x\nв = а\n", options) {
+            Err(CipherError::OverlappingSets(list)) => assert_eq!(list, "'а', 'б'"),
+            other => panic!("{:?}", other),
+        }
+
+        // Удаляемый символ тоже считается символом открытого текста
+        let options = ParseOptions { disjoint_sets: true, allow_empty_value: true, ..ParseOptions::default() };
+        assert!(matches!(Cipher::parse_with("а = б\nб =\n", options), Err(CipherError::OverlappingSets(_))));
+        // Без флага пересечение разрешено
+        assert!(Cipher::parse("а = б\nб = а\n").is_ok());
+    }
+
+    #[test]
+    fn csv_round_trips_special_characters() {
+        let csv = "\" \",_\n\"\"\"\",\",\"\n\",\",\"\"\"\"\nа,\nб,а\n";
+        let options = ParseOptions {
+            format: Some(AlphabetFormat::Csv),
+            allow_empty_value: true,
+            ..ParseOptions::default()
+        };
+        let cipher = Cipher::parse_with(csv, options).unwrap();
+        assert_eq!(cipher.entries(), [(' ', Some('_')), ('"', Some(',')), (',', Some('"')), ('а', None), ('б', Some('а'))]);
+        assert_eq!(cipher.to_csv(false), csv);
+
+        let with_header = cipher.to_csv(true);
+        assert_eq!(with_header, format!("ключ,значение\n{}", csv));
+        let reparsed = Cipher::parse_with(&with_header, ParseOptions { csv_header: true, ..options }).unwrap();
+        assert_eq!(reparsed.entries(), cipher.entries());
+    }
+
+    #[test]
+    fn pruned_cipher_gives_same_output() {
+        let key = "а = б\nб = а\nв = г\nг = в\nд = е\nе = д\nж =\nз =\n";
+        let parse = || Cipher::parse_with(key, ParseOptions { allow_empty_value: true, ..ParseOptions::default() }).unwrap();
+        let full = parse();
+        let text = "где жаба, ж?";
+
+        let mut pruned = parse();
+        pruned.retain_used(text, Direction::Encrypt);
+        assert_eq!(pruned.encrypt(text), full.encrypt(text));
+        assert!(pruned.len() < full.len());
+        assert_eq!(pruned.lookup('з', Direction::Encrypt), Lookup::Unmapped);
+
+        let encrypted = full.encrypt(text);
+        let mut pruned = parse();
+        pruned.retain_used(&encrypted, Direction::Decrypt);
+        assert_eq!(pruned.decrypt(&encrypted), full.decrypt(&encrypted));
+        assert!(pruned.len() < full.len());
+    }
+
+    #[test]
+    fn alphabet_order_matches_declaration() {
+        let cipher = Cipher::parse("# порядок не по алфавиту\n@order в а г б\nа = б\nб = а\n").unwrap();
+        assert_eq!(cipher.alphabet_order(), ['в', 'а', 'г', 'б']);
+        assert_eq!(cipher.len(), 2);
+
+        assert!(Cipher::parse("а = б\n").unwrap().alphabet_order().is_empty());
+        assert!(matches!(
+            Cipher::parse("@order аб\n@order ба\nа = б\n"),
+            Err(CipherError::RepeatedOrder { line: 2 })
+        ));
+        assert_eq!(Cipher::parse("а = б\n@order аб\n").unwrap().alphabet_order(), ['а', 'б']);
+        assert!(matches!(
+            Cipher::parse("@order аба\nа = б\n"),
+            Err(CipherError::DuplicateOrderChar { line: 1, ch: 'а' })
+        ));
+    }
+}
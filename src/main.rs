@@ -1,23 +1,101 @@
 use clap::Parser;
-use std::collections::HashMap;
 use std::fs;
-use std::io::Write;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use anyhow::{Result, Context};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use cipher::blocks::{self, PartialBlock};
+use cipher::generate::{self, CharsetError, RUSSIAN};
+use cipher::header::{self, Header};
+use cipher::score::Language;
+use cipher::text::{InputEncoding, LineEnding, OutputCase};
+use cipher::{chunked, combined, fields, format, format_entries, include, json, numeric, rle, score, text, AlphabetFormat, CharClass, Cipher, CipherError, Direction, IncludeConflict, KeyChange, ParseOptions, BOM};
+use modes::{Engine, Mode};
+use profile::Profiler;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 
+mod batch;
+mod modes;
+#[cfg(feature = "network")]
+mod network;
+mod profile;
+mod split;
+mod stream;
+mod validate;
+#[cfg(feature = "watch")]
+mod watch;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
+#[command(group(clap::ArgGroup::new("inspection").args(["coverage", "info", "report_unmapped_to"]).multiple(true)))]
+#[command(group(clap::ArgGroup::new("positional").args(["alternating", "cycle_alphabets"])))]
+#[command(group(clap::ArgGroup::new("seed_source").args(["seed", "seed_file"])))]
+#[command(group(clap::ArgGroup::new("text_label").args(["marker", "header"])))]
 struct Args {
     /// Файл с алфавитом шифрования в формате "ключ = значение" (пробелы вокруг = разрешены)
     #[arg(short, long)]
-    alphabet: String,
+    alphabet: Option<String>,
 
-    /// Текст для обработки (не указывайте, если используете --input)
-    text: Option<String>,
+    /// Подстановка "КЛЮЧ=ЗНАЧЕНИЕ" вместо файла алфавита (можно указать несколько раз)
+    #[arg(
+        long = "map",
+        value_name = "КЛЮЧ=ЗНАЧЕНИЕ",
+        value_parser = parse_map,
+        conflicts_with_all = ["alphabet", "stdin_combined", "alphabet_format"]
+    )]
+    map: Vec<String>,
 
-    /// Файл для чтения входного текста
-    #[arg(short, long, conflicts_with = "text")]
-    input: Option<String>,
+    /// Текст для обработки; несколько слов объединяются через пробел
+    /// (не указывайте, если используете --input)
+    text: Vec<String>,
+
+    /// Файл для чтения входного текста. Если файлов несколько, каждый
+    /// обрабатывается отдельно, а результаты объединяются по порядку через
+    /// --append-separator
+    #[arg(short, long, value_name = "ФАЙЛ", conflicts_with = "text")]
+    input: Vec<String>,
+
+    /// Прочитать из stdin алфавит, затем пустую строку, затем текст для обработки
+    #[arg(long, conflicts_with_all = ["alphabet", "text", "input", "input_glob"])]
+    stdin_combined: bool,
+
+    /// Записать в файл алфавит и результат, разделенные пустой строкой:
+    /// получатель расшифрует его одной командой с --stdin-combined
+    #[arg(long, value_name = "ФАЙЛ", conflicts_with_all = ["homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets", "input_glob"])]
+    bundle: Option<String>,
+
+    /// Обрабатывать входной файл по частям, не загружая его в память целиком
+    #[arg(long)]
+    stream: bool,
+
+    /// Размер буфера чтения и записи в байтах для --stream (не меньше 4)
+    #[arg(long, value_name = "BYTES", default_value_t = chunked::DEFAULT_BUFFER_SIZE, value_parser = parse_buffer_size)]
+    buffer_size: usize,
+
+    /// Завершиться с ошибкой, если результат больше BYTES байт. При потоковой
+    /// обработке размер проверяется по мере записи, и обработка прекращается сразу
+    #[arg(long, value_name = "BYTES")]
+    max_output_size: Option<u64>,
+
+    /// Обрабатывать входной файл потоково, если он больше BYTES, и в памяти, если меньше
+    #[arg(long, value_name = "BYTES", conflicts_with = "stream")]
+    limit_memory: Option<u64>,
+
+    /// Сообщить в stderr, выбрана обработка в памяти или потоковая
+    #[arg(long, hide = true)]
+    report_path: bool,
+
+    /// Шаблон входных файлов, например "docs/**/*.txt"; результаты сохраняются
+    /// в каталог --output с сохранением структуры подкаталогов
+    #[arg(long, conflicts_with_all = ["text", "input", "append", "preview"], requires = "output")]
+    input_glob: Option<String>,
+
+    /// Прерывать обработку по --input-glob на первом файле, который не удалось прочитать
+    #[arg(long, requires = "input_glob")]
+    fail_fast: bool,
 
     /// Зашифровать текст
     #[arg(short, long, conflicts_with = "decrypt")]
@@ -27,148 +105,1022 @@ struct Args {
     #[arg(short, long, conflicts_with = "encrypt")]
     decrypt: bool,
 
+    /// Расшифровать текст, если он начинается с маркера --marker или
+    /// заголовка --header, иначе завершиться с ошибкой
+    #[arg(long, requires = "text_label", conflicts_with_all = ["encrypt", "decrypt"])]
+    decrypt_auto: bool,
+
+    /// Строка-маркер: при шифровании добавляется первой строкой результата,
+    /// при расшифровке отрезается
+    #[arg(long)]
+    marker: Option<String>,
+
+    /// Заголовок с режимом и отпечатком ключа: при шифровании добавляется
+    /// первой строкой результата, при расшифровке сверяется с выбранными
+    /// режимом и ключом и отрезается
+    #[arg(long, conflicts_with_all = ["tabular", "verify_roundtrip_file"])]
+    header: bool,
+
     /// Файл для сохранения результата (если не указан, результат выводится на экран)
     #[arg(short, long)]
     output: Option<String>,
 
+    /// Записывать результат в файлы по --output-template, начиная новый файл
+    /// после каждых N строк. Входной файл обрабатывается потоково
+    #[arg(long, value_name = "N", value_parser = parse_positive, requires_all = ["output_template", "input"], conflicts_with_all = ["output", "preview", "input_glob"])]
+    split_lines: Option<usize>,
+
+    /// Шаблон имен файлов для --split-lines: {n} заменяется номером файла, начиная с 1
+    #[arg(long, value_name = "ШАБЛОН", value_parser = parse_template, requires = "split_lines")]
+    output_template: Option<String>,
+
+    /// Выбрать формат файла --output по расширению: .b64 — base64, .json —
+    /// строка JSON, .dot — граф подстановки Graphviz; остальные — обычный текст
+    #[arg(long, requires = "output", conflicts_with_all = ["append", "preview", "input_glob"])]
+    auto_format: bool,
+
     /// Добавить результат в конец файла (вместо перезаписи)
     #[arg(short = 'A', long, requires = "output")]
     append: bool,
+
+    /// Перенести права доступа входного файла на файл результата
+    #[arg(long, requires = "output", conflicts_with_all = ["append", "preview", "stdin_combined"])]
+    preserve_metadata: bool,
+
+    /// Вместе с правами перенести и время изменения входного файла
+    #[arg(long, requires = "preserve_metadata")]
+    preserve_mtime: bool,
+
+    /// Разделитель записей в режиме добавления и результатов нескольких --input;
+    /// понимает \n, \r, \t, \0 и \\
+    #[arg(long, value_name = "СТРОКА", default_value = "\\n", value_parser = parse_escapes)]
+    append_separator: String,
+
+    /// Ставить разделитель только между записями, а не после каждой
+    #[arg(long, requires = "append")]
+    no_trailing_separator: bool,
+
+    /// Кодировка входных файлов; результат всегда записывается в UTF-8
+    #[arg(long, value_enum, value_name = "КОДИРОВКА", default_value_t = InputEncoding::Utf8)]
+    input_encoding: InputEncoding,
+
+    /// Заменять недопустимые в кодировке --input-encoding байты символом
+    /// U+FFFD, а не завершаться с ошибкой
+    #[arg(long)]
+    lossy: bool,
+
+    /// Сохранять метку порядка байтов (BOM) в начале входного файла
+    #[arg(long)]
+    keep_bom: bool,
+
+    /// Перед обработкой привести переводы строк CRLF и CR к LF
+    #[arg(long)]
+    normalize_eol_input: bool,
+
+    /// Завершиться с ошибкой, если во входном тексте есть строка длиннее N
+    /// символов, указав ее номер
+    #[arg(long, value_name = "N", value_parser = parse_positive)]
+    max_line_length: Option<usize>,
+
+    /// Вместо ошибки обрезать строки длиннее --max-line-length до N символов
+    #[arg(long, requires = "max_line_length")]
+    truncate_lines: bool,
+
+    /// Записать переводы строк результата в указанном виде
+    #[arg(long, value_enum, value_name = "ВИД")]
+    line_ending: Option<LineEnding>,
+
+    /// Заменить каждую серию пробелов, табуляций и переводов строк одним пробелом перед обработкой
+    #[arg(long, conflicts_with = "strip_whitespace")]
+    collapse_whitespace: bool,
+
+    /// Удалить все пробельные символы перед обработкой
+    #[arg(long)]
+    strip_whitespace: bool,
+
+    /// Заменять пробелы символом C перед шифрованием и восстанавливать их
+    /// после расшифровки. C не должен встречаться в открытом тексте и в алфавите
+    #[arg(
+        long,
+        value_name = "C",
+        value_parser = parse_space_as,
+        conflicts_with_all = ["strip_whitespace", "json_values", "field", "preserve_indent"]
+    )]
+    space_as: Option<char>,
+
+    /// Разобрать вход как JSON и зашифровать только строковые значения, сохранив структуру
+    #[arg(long)]
+    json_values: bool,
+
+    /// Вместе с --json-values шифровать также ключи объектов
+    #[arg(long, requires = "json_values")]
+    json_keys: bool,
+
+    /// При шифровании после каждого символа с вероятностью RATE (от 0 до 1)
+    /// вставлять «пустой» символ из --null-set
+    #[arg(long, value_name = "RATE", requires = "null_set", value_parser = parse_unit_interval)]
+    insert_nulls: Option<f64>,
+
+    /// При расшифровке удалить «пустые» символы --null-set перед обратной подстановкой
+    #[arg(long, requires = "null_set")]
+    strip_nulls: bool,
+
+    /// Набор «пустых» символов; он не должен пересекаться с символами шифра,
+    /// иначе --strip-nulls удалит и их
+    #[arg(long, value_name = "СИМВОЛЫ", value_parser = parse_null_set)]
+    null_set: Option<String>,
+
+    /// При шифровании начать шифротекст с N случайных символов шифра (соли),
+    /// при расшифровке отбросить первые N символов. Одинаковые тексты дают
+    /// разный шифротекст, но стойкость шифра от этого не растет
+    #[arg(long, value_name = "N", value_parser = parse_positive, conflicts_with_all = ["homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"])]
+    salt_output: Option<usize>,
+
+    /// Записывать шифротекст шестнадцатеричными кодами символов через пробел
+    /// (при расшифровке — читать его из такой записи)
+    #[arg(long, conflicts_with = "json_values")]
+    numeric: bool,
+
+    /// Входной текст — коды символов в системе счисления N (2, 8, 10 или 16)
+    /// через пробел: коды переводятся в символы, шифруются и записываются
+    /// в той же системе счисления
+    #[arg(long, value_name = "N", value_parser = parse_radix, conflicts_with_all = ["numeric", "json_values"])]
+    input_radix: Option<u32>,
+
+    /// Перед подстановкой переставить символы текста в обратном порядке
+    /// (при расшифровке — после нее)
+    #[arg(long, conflicts_with = "json_values")]
+    reverse_input: bool,
+
+    /// После подстановки переставить символы результата в обратном порядке
+    /// (при расшифровке — перед ней)
+    #[arg(long, conflicts_with = "json_values")]
+    reverse_output: bool,
+
+    /// После подстановки разбить результат на блоки по N символов и
+    /// переставить их перестановкой из --seed (при расшифровке — вернуть
+    /// порядок перед подстановкой)
+    #[arg(long, value_name = "N", value_parser = parse_positive, requires = "seed_source", conflicts_with_all = ["json_values", "field"])]
+    shuffle_blocks: Option<usize>,
+
+    /// Что делать с последним неполным блоком --shuffle-blocks
+    #[arg(long, value_enum, value_name = "ВАРИАНТ", default_value_t = PartialBlock::Keep, requires = "shuffle_blocks")]
+    partial_block: PartialBlock,
+
+    /// Шифровать только поле с номером N (с единицы) в каждой строке;
+    /// строки, в которых меньше N полей, не меняются
+    #[arg(long, value_name = "N", value_parser = parse_positive, conflicts_with = "json_values")]
+    field: Option<usize>,
+
+    /// Разделитель полей для --field; понимает \n, \r, \t, \0 и \\
+    #[arg(long, value_name = "СТРОКА", default_value = ",", value_parser = parse_escapes, requires = "field")]
+    delimiter: String,
+
+    /// Не шифровать отступ в начале каждой строки: ведущие пробельные
+    /// символы переносятся в результат без изменений
+    #[arg(long, conflicts_with_all = ["json_values", "field"])]
+    preserve_indent: bool,
+
+    /// Привести результат подстановки к одному регистру (до --interleave, --rle и --numeric)
+    #[arg(long, value_enum, value_name = "РЕГИСТР", default_value_t = OutputCase::Preserve)]
+    output_case: OutputCase,
+
+    /// При шифровании чередовать символы шифротекста с символами файла-приманки
+    /// (приманка повторяется по кругу или обрезается до длины шифротекста)
+    #[arg(long, value_name = "ФАЙЛ", conflicts_with_all = ["decrypt", "decrypt_auto", "json_values"])]
+    interleave: Option<String>,
+
+    /// При расшифровке отбросить приманку: оставить каждый второй символ, начиная с первого
+    #[arg(long, conflicts_with_all = ["encrypt", "json_values"])]
+    deinterleave: bool,
+
+    /// Разделять символы шифротекста строкой, например "а-б-в" (при
+    /// расшифровке — убрать разделитель); понимает \n, \r, \t, \0 и \\
+    #[arg(long, value_name = "СТРОКА", value_parser = parse_char_delimiter, conflicts_with_all = ["json_values", "field", "preserve_indent"])]
+    char_delimiter: Option<String>,
+
+    /// Сжимать серии одинаковых символов шифротекста в запись "символ×число×"
+    /// (при расшифровке — разворачивать их)
+    #[arg(long, conflicts_with = "json_values")]
+    rle: bool,
+
+    /// Разделитель для --rle; он не должен встречаться в шифротексте
+    #[arg(long, value_name = "СИМВОЛ", default_value_t = '×', requires = "rle")]
+    rle_delimiter: char,
+
+    /// Заменять только символы указанного класса (можно указать несколько раз)
+    #[arg(long, value_enum)]
+    only_class: Vec<CharClass>,
+
+    /// Символы, которые всегда остаются без изменений, даже если для них есть подстановка
+    #[arg(long, value_name = "СИМВОЛЫ")]
+    exclude_chars: Option<String>,
+
+    /// Вывести алфавит в каноническом отсортированном виде и завершить работу
+    #[arg(long)]
+    dump: bool,
+
+    /// Вывести подстановку как граф Graphviz (DOT) и завершить работу
+    #[arg(long)]
+    emit_dot: bool,
+
+    /// Вывести алфавит как таблицу CSV "ключ,значение" и завершить работу;
+    /// такую таблицу читает --alphabet-format csv
+    #[arg(long)]
+    emit_csv: bool,
+
+    /// Таблица CSV алфавита начинается со строки заголовка: --emit-csv
+    /// записывает ее, а --alphabet-format csv пропускает
+    #[arg(long)]
+    csv_header: bool,
+
+    /// После чтения входного текста оставить в памяти только подстановки
+    /// символов, которые в нем встречаются. Результат не меняется
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "numeric", "input_radix", "json_values", "collapse_whitespace", "normalize_eol_input", "space_as", "salt_output", "bundle",
+            "homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets",
+        ]
+    )]
+    limit_alphabet_to_input: bool,
+
+    /// Сравнить два файла алфавита и вывести добавленные, удаленные и
+    /// измененные подстановки, затем завершить работу
+    #[arg(long, num_args = 2, value_names = ["СТАРЫЙ", "НОВЫЙ"])]
+    compare_keys: Vec<String>,
+
+    /// Проверить, что алфавит по-прежнему шифрует ОТКРЫТЫЙ в ШИФРОТЕКСТ и
+    /// расшифровывает ШИФРОТЕКСТ обратно, с учетом остальных опций; при
+    /// расхождении завершиться с ошибкой, указав первое различие
+    #[arg(long, num_args = 2, value_names = ["ОТКРЫТЫЙ", "ШИФРОТЕКСТ"], conflicts_with_all = ["text", "input", "input_glob", "output", "stdin_combined"])]
+    verify_roundtrip_file: Vec<String>,
+
+    /// Расшифровать текст каждым из алфавитов и вывести варианты от самого
+    /// похожего на английский текст к наименее похожему (критерий хи-квадрат
+    /// по частотам букв)
+    #[arg(
+        long,
+        value_name = "ФАЙЛ",
+        num_args = 1..,
+        conflicts_with_all = ["alphabet", "map", "stdin_combined", "input_glob", "output", "homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"]
+    )]
+    decrypt_guess: Vec<String>,
+
+    /// Подобрать ключ по частотам букв шифротекста и частотам букв языка
+    /// --lang и вывести его как файл алфавита (в --output или на экран)
+    #[arg(
+        long,
+        conflicts_with_all = ["alphabet", "map", "stdin_combined", "input_glob", "decrypt_guess", "homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"]
+    )]
+    suggest_key: bool,
+
+    /// Язык открытого текста для --suggest-key
+    #[arg(long, value_enum, value_name = "ЯЗЫК", default_value_t = Language::En, requires = "suggest_key")]
+    lang: Language,
+
+    /// Переписать файл алфавита в каноническом отсортированном виде (в --output
+    /// или на месте) и завершить работу
+    #[arg(long, value_name = "ФАЙЛ", conflicts_with_all = ["append", "preview"])]
+    canonicalize_keyfile: Option<String>,
+
+    /// Сохранить комментарии при --canonicalize-keyfile: они переносятся в начало файла
+    #[arg(long, requires = "canonicalize_keyfile")]
+    keep_comments: bool,
+
+    /// Вывести разложение подстановки на циклы и ее порядок и завершить работу
+    #[arg(long)]
+    cycles: bool,
+
+    /// Оценить стойкость ключа: неподвижные символы, самый длинный цикл,
+    /// совпадение с шифром Цезаря над --charset — и завершить работу
+    #[arg(long)]
+    key_strength: bool,
+
+    /// Что делать, если включенные через @include файлы определяют символ
+    /// по-разному: error — ошибка, override — действует определение, записанное позже
+    #[arg(long, value_enum, value_name = "ПРАВИЛО", default_value_t = IncludeConflict::Error)]
+    include_conflict: IncludeConflict,
+
+    /// Разрешить пустое значение в алфавите ("ключ ="): такой символ удаляется
+    /// при шифровании и не восстанавливается при расшифровке. Позиционные
+    /// режимы (--alternating, --cycle-alphabets) удаления не принимают
+    #[arg(long, conflicts_with_all = ["homophonic", "positional", "word_mode", "bifid"])]
+    allow_empty_value: bool,
+
+    /// Ошибка, если символ встречается в алфавите и как ключ, и как значение:
+    /// тогда ни один символ шифротекста не совпадает с символом открытого текста
+    #[arg(long, conflicts_with_all = ["homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"])]
+    disjoint_sets: bool,
+
+    /// Разрешить алфавит без подстановок: вместо ошибки выводится предупреждение
+    #[arg(long)]
+    allow_empty: bool,
+
+    /// Вывести число подстановок в алфавите (включая удаления) и завершить работу
+    #[arg(long)]
+    print_alphabet_size: bool,
+
+    /// Не предупреждать о ключах алфавита длиннее одного символа: из такого
+    /// ключа используется только первый символ, длинные ключи приоритета
+    /// не имеют
+    #[arg(long, alias = "no-prefix-warn")]
+    no_long_key_warn: bool,
+
+    /// Формат файла алфавита; по умолчанию определяется по содержимому
+    #[arg(long, value_enum, value_name = "ФОРМАТ")]
+    alphabet_format: Option<AlphabetFormat>,
+
+    /// Вывести формат файла алфавита, определенный по содержимому, и завершить работу
+    #[arg(long, conflicts_with = "alphabet_format")]
+    detect_format: bool,
+
+    /// Сравнить алфавит с корпусом текста: какая доля различных символов
+    /// корпуса имеет подстановку и какие символы без нее встречаются чаще всего
+    #[arg(long, value_name = "ФАЙЛ")]
+    coverage: Option<String>,
+
+    /// Вывести сведения об алфавите (число подстановок, неподвижные символы) и завершить работу
+    #[arg(long)]
+    info: bool,
+
+    /// Завершиться с ошибкой, если символов без подстановки больше N
+    #[arg(long, value_name = "N")]
+    max_unmapped: Option<usize>,
+
+    /// Завершиться с ошибкой, если доля символов без подстановки больше P процентов
+    #[arg(long, value_name = "P", value_parser = parse_percent)]
+    max_unmapped_pct: Option<f64>,
+
+    /// Записать в файл различные символы без подстановки и число их
+    /// вхождений во всем обработанном тексте
+    #[arg(long, value_name = "ФАЙЛ", conflicts_with = "input_glob")]
+    report_unmapped_to: Option<String>,
+
+    /// Завершиться с ошибкой на первом символе, для которого нет подстановки,
+    /// указав его позицию, вместо того чтобы оставить его без изменений
+    #[arg(long, conflicts_with_all = ["only_class", "exclude_chars", "homophonic", "alternating", "word_mode", "bifid"])]
+    fail_on_passthrough: bool,
+
+    /// Завершиться с ошибкой, если в шифруемом тексте (отдельном поле --field
+    /// или значении --json-values) есть перевод строки, который прошел бы без
+    /// изменений, указав его позицию
+    #[arg(long)]
+    no_passthrough_newlines: bool,
+
+    /// Менять регистр букв, для которых нет подстановки, вместо того чтобы
+    /// оставлять их без изменений (при расшифровке регистр возвращается)
+    #[arg(long, conflicts_with_all = ["fail_on_passthrough", "homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"])]
+    swap_case_unmapped: bool,
+
+    /// Выводить в stderr решение для каждого символа: позицию, символ,
+    /// правило (подстановка, удаление, исключение, нет подстановки) и результат
+    #[arg(long, conflicts_with_all = ["homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"])]
+    trace: bool,
+
+    /// Вывести в stderr, сколько раз сработала каждая подстановка, от самых
+    /// частых к редким
+    #[arg(long, conflicts_with_all = ["homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"])]
+    per_char_stats: bool,
+
+    /// Вывести в stderr, сколько строк обработано и сколько из них изменила подстановка
+    #[arg(long, conflicts_with = "json_values")]
+    line_summary: bool,
+
+    /// Вместо результата вывести статистику подстановки входного текста в JSON:
+    /// число символов, замененных и без подстановки, и список символов без подстановки
+    #[arg(long, conflicts_with_all = ["output", "preview", "input_glob", "homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"])]
+    stats_json: bool,
+
+    /// Вместо результата вывести долю символов входного текста, имеющих
+    /// подстановку, и завершиться с кодом по порогам --warn-ratio и --fail-ratio
+    #[arg(
+        long,
+        requires = "warn_ratio",
+        conflicts_with_all = ["output", "preview", "input_glob", "stats_json", "homophonic", "alternating", "word_mode", "bifid", "otp", "rotor", "cycle_alphabets"]
+    )]
+    count_only_mapped_ratio: bool,
+
+    /// Доля (от 0 до 1), ниже которой --count-only-mapped-ratio завершается
+    /// с кодом --warn-exit-code
+    #[arg(long, value_name = "ДОЛЯ", value_parser = parse_unit_interval, requires = "count_only_mapped_ratio")]
+    warn_ratio: Option<f64>,
+
+    /// Доля (от 0 до 1), ниже которой --count-only-mapped-ratio завершается
+    /// с кодом --fail-exit-code; не больше --warn-ratio
+    #[arg(long, value_name = "ДОЛЯ", value_parser = parse_unit_interval, requires = "count_only_mapped_ratio")]
+    fail_ratio: Option<f64>,
+
+    /// Код завершения, если доля ниже --warn-ratio, но не ниже --fail-ratio
+    #[arg(long, value_name = "КОД", default_value_t = 2, value_parser = parse_exit_code)]
+    warn_exit_code: u8,
+
+    /// Код завершения, если доля ниже --fail-ratio
+    #[arg(long, value_name = "КОД", default_value_t = 3, value_parser = parse_exit_code)]
+    fail_exit_code: u8,
+
+    /// Дописывать к символам в выводе --coverage, --info и --report-unmapped-to
+    /// их кодовые точки (U+XXXX), чтобы различать невидимые и похожие символы
+    #[arg(long, requires = "inspection")]
+    show_hex: bool,
+
+    /// Омофоническая подстановка: значения вида "x:3,y:1" с весами вариантов
+    #[arg(long, conflicts_with_all = ["only_class", "exclude_chars", "max_unmapped", "max_unmapped_pct", "report_unmapped_to", "dump", "emit_dot", "emit_csv", "cycles", "key_strength", "coverage", "alphabet_format"])]
+    homophonic: bool,
+
+    /// Двойная подстановка: символы на четных позициях (начиная с нулевой) шифруются
+    /// алфавитом --alphabet-even, на нечетных — алфавитом --alphabet-odd
+    #[arg(
+        long,
+        visible_alias = "double-substitution",
+        requires_all = ["alphabet_even", "alphabet_odd"],
+        conflicts_with_all = ["alphabet", "map", "stdin_combined", "homophonic", "dump", "emit_dot", "emit_csv", "cycles", "key_strength", "info", "coverage", "max_unmapped", "max_unmapped_pct", "report_unmapped_to"]
+    )]
+    alternating: bool,
+
+    /// Файл алфавита для четных позиций в режиме --alternating
+    #[arg(long, value_name = "ФАЙЛ", requires = "alternating")]
+    alphabet_even: Option<String>,
+
+    /// Файл алфавита для нечетных позиций в режиме --alternating
+    #[arg(long, value_name = "ФАЙЛ", requires = "alternating")]
+    alphabet_odd: Option<String>,
+
+    /// В режимах --alternating и --cycle-alphabets символы без подстановки не
+    /// занимают позицию, и чередование алфавитов продолжается со следующего
+    /// заменяемого символа
+    #[arg(long, requires = "positional")]
+    skip_unmapped_positions: bool,
+
+    /// Заменять целые слова: файл алфавита — словарь вида "привет = здравствуй"
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "homophonic", "alternating", "only_class", "exclude_chars",
+            "max_unmapped", "max_unmapped_pct", "report_unmapped_to", "dump", "emit_dot", "emit_csv", "cycles", "key_strength", "info", "coverage", "alphabet_format",
+        ]
+    )]
+    word_mode: bool,
+
+    /// В режиме --word-mode сравнивать слова без учета регистра и переносить
+    /// регистр исходного слова на замену
+    #[arg(long, requires = "word_mode")]
+    preserve_case: bool,
+
+    /// Шифр Деластелля (bifid) по квадрату Полибия 5×5 из ключевого слова --key
+    #[arg(
+        long,
+        requires = "key",
+        conflicts_with_all = [
+            "alphabet", "map", "stdin_combined", "homophonic", "alternating", "word_mode",
+            "only_class", "exclude_chars", "max_unmapped", "max_unmapped_pct", "report_unmapped_to",
+            "dump", "emit_dot", "emit_csv", "cycles", "key_strength", "info", "coverage", "alphabet_format", "detect_format",
+        ]
+    )]
+    bifid: bool,
+
+    /// Ключевое слово, с которого начинается квадрат Полибия
+    #[arg(long, value_name = "СЛОВО", requires = "bifid")]
+    key: Option<String>,
+
+    /// Длина блока перемешивания; без нее перемешивается весь текст сразу
+    #[arg(long, value_name = "N", requires = "bifid", value_parser = parse_period)]
+    period: Option<usize>,
+
+    /// Две латинские буквы, объединяемые в квадрате 5×5: вторая шифруется как первая
+    #[arg(long, value_name = "ПАРА", default_value = "IJ", value_parser = parse_merge, requires = "bifid")]
+    bifid_merge: (char, char),
+
+    /// Одноразовый блокнот из файла: номер каждого символа набора --charset
+    /// складывается с номером очередного символа блокнота. Блокнот должен быть
+    /// не короче текста
+    #[arg(
+        long,
+        value_name = "ФАЙЛ",
+        conflicts_with_all = [
+            "alphabet", "map", "stdin_combined", "homophonic", "alternating", "word_mode", "bifid",
+            "only_class", "exclude_chars", "max_unmapped", "max_unmapped_pct", "report_unmapped_to",
+            "dump", "emit_dot", "emit_csv", "cycles", "key_strength", "info", "coverage", "alphabet_format", "detect_format",
+            "allow_empty_value", "fail_on_passthrough",
+        ]
+    )]
+    otp: Option<String>,
+
+    /// Роторный шифр: символ проходит через алфавиты роторов по порядку, а
+    /// роторы после каждого символа поворачиваются, как колеса счетчика.
+    /// Каждый алфавит должен быть перестановкой набора --charset
+    #[arg(
+        long,
+        value_name = "ФАЙЛ",
+        num_args = 1..,
+        conflicts_with_all = [
+            "alphabet", "map", "stdin_combined", "homophonic", "alternating", "word_mode", "bifid", "otp",
+            "only_class", "exclude_chars", "max_unmapped", "max_unmapped_pct", "report_unmapped_to",
+            "dump", "emit_dot", "emit_csv", "cycles", "key_strength", "info", "coverage", "detect_format",
+            "allow_empty_value", "fail_on_passthrough",
+        ]
+    )]
+    rotor: Vec<String>,
+
+    /// На сколько позиций первый ротор поворачивается после каждого символа
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = parse_positive, requires = "rotor")]
+    rotor_step: usize,
+
+    /// Шифровать символ на позиции i алфавитом с номером i по модулю числа
+    /// алфавитов: первый символ — первым алфавитом, второй — вторым и так далее
+    #[arg(
+        long,
+        value_name = "ФАЙЛ",
+        num_args = 2..,
+        conflicts_with_all = [
+            "alphabet", "map", "stdin_combined", "homophonic", "alternating", "word_mode", "bifid", "otp", "rotor",
+            "only_class", "exclude_chars", "max_unmapped", "max_unmapped_pct", "report_unmapped_to",
+            "dump", "emit_dot", "emit_csv", "cycles", "key_strength", "info", "coverage", "detect_format",
+        ]
+    )]
+    cycle_alphabets: Vec<String>,
+
+    /// Применить шифр N раз подряд. Для подстановки-перестановки N сокращается
+    /// по модулю ее порядка, поэтому большие N обрабатываются мгновенно
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    rounds: u64,
+
+    /// Начальное значение генератора случайных чисел для воспроизводимого результата
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Вывести начальное значение генератора из содержимого файла (SHA-256):
+    /// один и тот же файл дает тот же результат, что и одно --seed
+    #[arg(long, value_name = "ФАЙЛ", conflicts_with_all = ["seed", "password"])]
+    seed_file: Option<String>,
+
+    /// Вывести исходный и преобразованный текст построчно в две колонки
+    #[arg(long, conflicts_with = "input_glob")]
+    tabular: bool,
+
+    /// Разделитель колонок для --tabular; понимает \n, \r, \t, \0 и \\
+    #[arg(long, value_name = "СТРОКА", default_value = " | ", value_parser = parse_escapes, requires = "tabular")]
+    column_separator: String,
+
+    /// Вывести в stderr энтропию Шеннона (бит на символ) входного текста и результата
+    #[arg(long, conflicts_with = "input_glob")]
+    measure_entropy: bool,
+
+    /// Проверить алфавит, входные данные и возможность записи результата,
+    /// ничего не записывая, и завершить работу
+    #[arg(long)]
+    dry_validate: bool,
+
+    /// Вывести на экран только первые N символов результата, не записывая его целиком
+    #[arg(long, value_name = "N")]
+    preview: Option<usize>,
+
+    /// Сгенерировать файл алфавита вместо шифрования (сохраняется в --output или выводится на экран)
+    #[arg(long)]
+    generate: bool,
+
+    /// Сгенерировать случайный одноразовый блокнот из N символов набора
+    /// --charset для --otp (сохраняется в --output или выводится на экран)
+    #[arg(long, value_name = "N", value_parser = parse_positive, conflicts_with = "generate")]
+    generate_pad: Option<usize>,
+
+    /// Ключевое слово для смешанного алфавита: его символы идут первыми.
+    /// Без него алфавит генерируется случайно
+    #[arg(long, requires = "generate")]
+    keyword: Option<String>,
+
+    /// Пароль, из которого выводится начальное значение генератора случайного
+    /// алфавита: один и тот же пароль всегда дает один и тот же алфавит
+    #[arg(long, requires = "generate", conflicts_with_all = ["keyword", "seed"])]
+    password: Option<String>,
+
+    /// Согласовать случайный алфавит по регистру: если "а" заменяется на "м",
+    /// то "А" заменяется на "М"
+    #[arg(long, requires = "generate", conflicts_with = "keyword")]
+    link_case: bool,
+
+    /// Дополнять алфавит после ключевого слова в обратном порядке
+    #[arg(long, requires = "keyword")]
+    reverse_fill: bool,
+
+    /// Набор символов, над которым строится алфавит, действует блокнот --otp
+    /// или роторы --rotor. По умолчанию для --rotor и --key-strength берется
+    /// порядок из директивы @order алфавита, иначе строчный русский алфавит
+    #[arg(long)]
+    charset: Option<String>,
+
+    /// Вывести в stderr время каждого этапа обработки
+    #[arg(long)]
+    profile: bool,
+
+    /// Показать доступные режимы шифрования и завершить работу
+    #[arg(long)]
+    list_modes: bool,
+
+    /// Сколько секунд ждать загрузки --input по адресу http:// или https://
+    #[cfg(feature = "network")]
+    #[arg(long, value_name = "СЕКУНДЫ", default_value_t = 30)]
+    timeout: u64,
+
+    /// Перезапускать преобразование при изменении файла алфавита или входного файла
+    #[cfg(feature = "watch")]
+    #[arg(short, long)]
+    watch: bool,
 }
 
-#[derive(Debug)]
-struct Cipher {
-    encrypt_map: HashMap<char, char>,
-    decrypt_map: HashMap<char, char>,
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+
+    // Дальше начальное значение берется только из --seed
+    if let Some(seed_file) = &args.seed_file {
+        let content = fs::read(seed_file)
+            .with_context(|| format!("Не удалось прочитать файл начального значения: {}", seed_file))?;
+        args.seed = Some(generate::seed_from_bytes(&content));
+    }
+
+    #[cfg(feature = "watch")]
+    if args.watch {
+        return watch::run(&args);
+    }
+
+    let result = run(&args);
+    if let Some(&ExitCode(code)) = result.as_ref().err().and_then(|err| err.downcast_ref()) {
+        std::process::exit(code.into());
+    }
+    result
 }
 
-impl Cipher {
-    fn from_file(filename: &str) -> Result<Self> {
-        let content = fs::read_to_string(filename)
-            .with_context(|| format!("Не удалось прочитать файл: {}", filename))?;
-        
-        let mut encrypt_map = HashMap::new();
-        let mut decrypt_map = HashMap::new();
-
-        for (line_number, line) in content.lines().enumerate() {
-            let line = line.trim();
-            
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
+/// Код завершения по --warn-ratio и --fail-ratio. Вместо
+/// немедленного выхода `run` возвращает его как ошибку, чтобы --watch
+/// продолжал наблюдение; `main` завершается с этим кодом без сообщения.
+#[derive(Debug, thiserror::Error)]
+#[error("доля символов с подстановкой ниже порога, код завершения {0}")]
+struct ExitCode(u8);
 
-            let equals_pos = match line.find('=') {
-                Some(pos) => pos,
-                None => anyhow::bail!(
-                    "Строка {}: отсутствует знак равенства в '{}'", 
-                    line_number + 1, 
-                    line
-                ),
-            };
+fn run(args: &Args) -> Result<()> {
+    if args.list_modes {
+        modes::print_list();
+        return Ok(());
+    }
 
-            let key_part = &line[..equals_pos].trim();
-            let value_part = &line[equals_pos + 1..].trim();
+    if args.generate {
+        return generate_alphabet(args);
+    }
 
-            if key_part.is_empty() {
-                anyhow::bail!(
-                    "Строка {}: пустой ключ в '{}'", 
-                    line_number + 1, 
-                    line
-                );
+    if let Some(length) = args.generate_pad {
+        return generate_pad(args, length);
+    }
+
+    if args.dump {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        print!("{}", cipher.to_canonical_string());
+        return Ok(());
+    }
+
+    if args.emit_dot {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        print!("{}", cipher.to_dot());
+        return Ok(());
+    }
+
+    if args.emit_csv {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        print!("{}", cipher.to_csv(args.csv_header));
+        return Ok(());
+    }
+
+    if let (OutputFormat::Dot, Some(output_file)) = (OutputFormat::from_args(args), &args.output) {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        fs::write(output_file, cipher.to_dot())
+            .with_context(|| format!("Не удалось записать граф в файл: {}", output_file))?;
+        println!("Граф подстановки сохранен в файл: {}", output_file);
+        return Ok(());
+    }
+
+    if args.detect_format {
+        let alphabet = Alphabet::read(args)?;
+        println!("{}", alphabet.format(args).name());
+        return Ok(());
+    }
+
+    if let Some(corpus_file) = &args.coverage {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        let corpus = read_input_file(args, corpus_file)?;
+        print_coverage(&cipher, &corpus, args.show_hex);
+        return Ok(());
+    }
+
+    if args.cycles {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        print_cycles(&cipher);
+        return Ok(());
+    }
+
+    if args.key_strength {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        let charset = ordered_charset(args, cipher.alphabet_order());
+        print_key_strength(&cipher, &charset);
+        return Ok(());
+    }
+
+    if let Some(key_file) = &args.canonicalize_keyfile {
+        return canonicalize_keyfile(args, key_file);
+    }
+
+    if let [old_file, new_file] = &args.compare_keys[..] {
+        let old = Alphabet::from_file(old_file)?.cipher(args)?;
+        let new = Alphabet::from_file(new_file)?.cipher(args)?;
+        print_comparison(&old.compare(&new));
+        return Ok(());
+    }
+
+    if args.info {
+        let cipher = Alphabet::read(args)?.cipher(args)?;
+        print_info(&cipher, args.show_hex);
+        return Ok(());
+    }
+
+    if args.print_alphabet_size {
+        let cipher = Alphabet::read(args)?.cipher_allowing_empty(args)?;
+        println!("{}", cipher.len());
+        return Ok(());
+    }
+
+    if args.dry_validate {
+        return validate::run(args);
+    }
+
+    let direction = if args.decrypt || args.decrypt_auto {
+        Direction::Decrypt
+    } else {
+        Direction::Encrypt
+    };
+
+    if let [plain_file, cipher_file] = &args.verify_roundtrip_file[..] {
+        return verify_roundtrip(args, plain_file, cipher_file);
+    }
+
+    if !args.decrypt_guess.is_empty() {
+        return decrypt_guess(args);
+    }
+
+    if args.suggest_key {
+        return suggest_key(args);
+    }
+
+    if let Some(pattern) = &args.input_glob {
+        return batch::run_glob(args, pattern, direction);
+    }
+
+    // Права переносятся с единственного входного файла
+    let metadata_source = match (args.preserve_metadata, &args.input[..]) {
+        (false, _) => None,
+        (true, [input_file]) => Some(input_file),
+        (true, _) => anyhow::bail!("Для --preserve-metadata укажите ровно один входной файл --input"),
+    };
+
+    let mut profiler = Profiler::new(args.profile);
+
+    let over_limit = exceeds_memory_limit(args)?;
+    if args.report_path {
+        let path = if args.stream || over_limit || args.split_lines.is_some() { "потоковая" } else { "в памяти" };
+        eprintln!("Обработка: {}", path);
+    }
+
+    if args.stream || over_limit || args.split_lines.is_some() {
+        if let Some(option) = stream::blocker(args) {
+            if args.split_lines.is_some() {
+                anyhow::bail!("--split-lines обрабатывает файл потоково и несовместим с {}", option);
             }
-            if value_part.is_empty() {
-                anyhow::bail!(
-                    "Строка {}: пустое значение в '{}'", 
-                    line_number + 1, 
-                    line
-                );
+            if args.stream {
+                anyhow::bail!("Потоковая обработка (--stream) несовместима с {}", option);
             }
+            anyhow::bail!(
+                "Входной файл больше --limit-memory, но потоковая обработка несовместима с {}",
+                option
+            );
+        }
+        stream::run(args, direction, &mut profiler)?;
+        if let (Some(source), Some(target)) = (metadata_source, &args.output) {
+            copy_metadata(args, Path::new(source), Path::new(target))?;
+        }
+        profiler.report();
+        return Ok(());
+    }
 
-            let original = key_part.chars().next()
-                .with_context(|| format!("Строка {}: не удалось извлечь ключ", line_number + 1))?;
-            let substituted = value_part.chars().next()
-                .with_context(|| format!("Строка {}: не удалось извлечь значение", line_number + 1))?;
-
-            if encrypt_map.contains_key(&original) {
-                anyhow::bail!(
-                    "Строка {}: дублирующийся ключ '{}'", 
-                    line_number + 1, 
-                    original
-                );
+    let (alphabets, inputs) = if args.stdin_combined {
+        let (alphabet, input_text) = profiler.stage("read", read_combined_stdin)?;
+        (vec![alphabet], vec![input_text])
+    } else {
+        // Проверяем, что указан либо текст, либо входные файлы
+        let text = (!args.text.is_empty()).then(|| args.text.join(" "));
+        let inputs = match (text, &args.input[..]) {
+            (Some(text), []) => vec![text],
+            (None, []) => {
+                anyhow::bail!("Не указан текст для обработки. Используйте текстовый аргумент или --input");
             }
-            if decrypt_map.contains_key(&substituted) {
-                anyhow::bail!(
-                    "Строка {}: дублирующееся значение '{}'", 
-                    line_number + 1, 
-                    substituted
-                );
+            (None, input_files) => input_files
+                .iter()
+                .map(|input_file| profiler.stage("read", || read_input_file(args, input_file)))
+                .collect::<Result<Vec<_>>>()?,
+            (Some(_), _) => {
+                anyhow::bail!("Нельзя одновременно использовать --input и текстовый аргумент");
             }
+        };
+        (profiler.stage("read", || Mode::from_args(args).read_alphabets(args))?, inputs)
+    };
+
+    let mut engine = profiler.stage("load", || Mode::from_args(args).load(args, &alphabets))?;
+    let header = text_header(args, &alphabets)?;
 
-            encrypt_map.insert(original, substituted);
-            decrypt_map.insert(substituted, original);
+    // Этапы, которые до подстановки вносят символы, отсутствующие во входном
+    // тексте (--numeric, --collapse-whitespace и другие), с флагом несовместимы.
+    // Со второго раунда заменяются уже значения, поэтому раунд должен быть один
+    if let (true, Engine::Substitution { cipher, .. }) = (args.limit_alphabet_to_input, &mut engine) {
+        if args.rounds != 1 {
+            anyhow::bail!("--limit-alphabet-to-input несовместим с --rounds: после первого раунда нужны подстановки символов, которых нет во входном тексте");
         }
+        profiler.stage("limit", || cipher.retain_used(&inputs.concat(), direction));
+    }
 
-        Ok(Cipher {
-            encrypt_map,
-            decrypt_map,
-        })
+    if args.stats_json {
+        let Engine::Substitution { cipher, .. } = &engine else {
+            anyhow::bail!("--stats-json доступен только для простой подстановки");
+        };
+        let stats = cipher.analyze(&inputs.concat(), direction);
+        println!("{}", serde_json::to_string_pretty(&stats.to_json())?);
+        return Ok(());
+    }
+    if args.count_only_mapped_ratio {
+        let Engine::Substitution { cipher, .. } = &engine else {
+            anyhow::bail!("--count-only-mapped-ratio доступен только для простой подстановки");
+        };
+        let ratio = 1.0 - cipher.analyze(&inputs.concat(), direction).unmapped_ratio();
+        let code = mapped_ratio_exit_code(args, ratio)?;
+        println!("{:.4}", ratio);
+        if code != 0 {
+            return Err(ExitCode(code).into());
+        }
+        return Ok(());
     }
+    let input_entropy = args.measure_entropy.then(|| text::shannon_entropy(&inputs.concat()));
 
-    fn encrypt(&self, text: &str) -> String {
-        text.chars()
-            .map(|c| *self.encrypt_map.get(&c).unwrap_or(&c))
-            .collect()
+    // Несколько входных файлов обрабатываются по отдельности, как при
+    // --input-glob, но результаты собираются в один вывод
+    let mut sections = Vec::with_capacity(inputs.len());
+    for input_text in inputs {
+        let input_text = match (&header, direction) {
+            (Some(expected), Direction::Decrypt) => strip_header(&input_text, expected)?.to_string(),
+            _ => input_text,
+        };
+        let original = args.tabular.then(|| input_text.clone());
+        let mut section = transform(args, &mut engine, direction, input_text, &mut profiler)?;
+        if let Some(original) = original {
+            section = profiler.stage("tabular", || text::side_by_side(&original, &section, &args.column_separator));
+        }
+        if let (Some(header), Direction::Encrypt) = (&header, direction) {
+            section = format!("{}\n{}", header, section);
+        }
+        sections.push(section);
     }
+    engine.finish(args, direction)?;
+    let result = sections.join(&args.append_separator);
+    let output = OutputFormat::from_args(args).apply(&result)?;
 
-    fn decrypt(&self, text: &str) -> String {
-        text.chars()
-            .map(|c| *self.decrypt_map.get(&c).unwrap_or(&c))
-            .collect()
+    check_output_size(args, output.len() as u64)?;
+
+    if let Some(bundle_file) = &args.bundle {
+        let Engine::Substitution { cipher, .. } = &engine else {
+            anyhow::bail!("--bundle доступен только для простой подстановки");
+        };
+        profiler.stage("bundle", || write_bundle(bundle_file, cipher, &result))?;
     }
-}
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+    if let Some(input_entropy) = input_entropy {
+        eprintln!("Энтропия входного текста: {:.4} бит/символ", input_entropy);
+        eprintln!("Энтропия результата: {:.4} бит/символ", text::shannon_entropy(&result));
+    }
 
-    // Проверяем, что указан либо текст, либо входной файл
-    let input_text = match (&args.text, &args.input) {
-        (Some(text), None) => text.clone(),
-        (None, Some(input_file)) => {
-            fs::read_to_string(input_file)
-                .with_context(|| format!("Не удалось прочитать входной файл: {}", input_file))?
-        }
-        (Some(_), Some(_)) => {
-            anyhow::bail!("Нельзя одновременно использовать --input и текстовый аргумент");
-        }
-        (None, None) => {
-            anyhow::bail!("Не указан текст для обработки. Используйте текстовый аргумент или --input");
+    if let Some(limit) = args.preview {
+        println!("{}", preview(&result, limit));
+    } else {
+        profiler.stage("write", || write_output(args, &output))?;
+        if let (Some(source), Some(target)) = (metadata_source, &args.output) {
+            copy_metadata(args, Path::new(source), Path::new(target))?;
         }
-    };
+    }
 
-    let cipher = Cipher::from_file(&args.alphabet)?;
+    profiler.report();
+    Ok(())
+}
 
-    let result = if args.decrypt {
-        cipher.decrypt(&input_text)
-    } else {
-        cipher.encrypt(&input_text)
+/// `true`, если входной файл больше бюджета --limit-memory и его нужно
+/// обработать потоково, а не загружать целиком
+fn exceeds_memory_limit(args: &Args) -> Result<bool> {
+    let (Some(limit), [input_file]) = (args.limit_memory, &args.input[..]) else {
+        return Ok(false);
     };
+    if is_url(input_file) {
+        return Ok(false);
+    }
+    let size = fs::metadata(input_file)
+        .with_context(|| format!("Не удалось получить размер входного файла: {}", input_file))?
+        .len();
+    Ok(size > limit)
+}
+
+/// Ошибка, если результат размером `size` байт больше --max-output-size
+fn check_output_size(args: &Args, size: u64) -> Result<()> {
+    match args.max_output_size {
+        Some(limit) if size > limit => anyhow::bail!(
+            "Результат больше --max-output-size: {} байт при ограничении {}",
+            size,
+            limit
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Переносит права доступа входного файла, а с --preserve-mtime и время
+/// изменения, на записанный файл результата. Время меняется первым: после
+/// переноса прав файл может стать доступным только для чтения.
+fn copy_metadata(args: &Args, source: &Path, target: &Path) -> Result<()> {
+    let metadata = fs::metadata(source)
+        .with_context(|| format!("Не удалось получить свойства входного файла: {}", source.display()))?;
 
+    if args.preserve_mtime {
+        let modified = metadata
+            .modified()
+            .with_context(|| format!("Не удалось получить время изменения файла: {}", source.display()))?;
+        fs::File::options()
+            .write(true)
+            .open(target)
+            .and_then(|file| file.set_modified(modified))
+            .with_context(|| format!("Не удалось изменить время изменения файла: {}", target.display()))?;
+    }
+
+    fs::set_permissions(target, metadata.permissions())
+        .with_context(|| format!("Не удалось изменить права доступа к файлу: {}", target.display()))
+}
+
+/// Формат файла --output, который --auto-format выбирает по расширению
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Base64,
+    Json,
+    Dot,
+}
+
+impl OutputFormat {
+    /// Формат по расширению --output, если задан --auto-format. Явные флаги
+    /// формата важнее расширения: с --numeric и --input-radix результат
+    /// записывается как есть, а с --json-values он уже является JSON.
+    fn from_args(args: &Args) -> OutputFormat {
+        let (true, Some(output_file)) = (args.auto_format, &args.output) else {
+            return OutputFormat::Text;
+        };
+        if args.numeric || args.input_radix.is_some() {
+            return OutputFormat::Text;
+        }
+
+        let extension = Path::new(output_file)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("b64") => OutputFormat::Base64,
+            Some("json") if !args.json_values => OutputFormat::Json,
+            Some("dot") => OutputFormat::Dot,
+            _ => OutputFormat::Text,
+        }
+    }
+
+    /// Записывает результат в этом формате. Граф .dot строится по алфавиту,
+    /// а не по результату, поэтому здесь результат не меняется.
+    fn apply(self, result: &str) -> Result<String> {
+        Ok(match self {
+            OutputFormat::Text | OutputFormat::Dot => result.to_string(),
+            OutputFormat::Base64 => BASE64.encode(result),
+            OutputFormat::Json => serde_json::to_string(result).context("Не удалось сериализовать JSON")?,
+        })
+    }
+}
+
+fn write_output(args: &Args, result: &str) -> Result<()> {
     match &args.output {
         Some(output_file) => {
             if args.append {
-                let mut file = fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(output_file)
-                    .with_context(|| format!("Не удалось открыть файл для добавления: {}", output_file))?;
-                
-                writeln!(file, "{}", result)
+                let mut file = open_append(output_file)?;
+                let (before, after) = append_separators(args, &file, output_file)?;
+
+                write!(file, "{}{}{}", before, result, after)
                     .with_context(|| format!("Не удалось записать в файл: {}", output_file))?;
                 
                 println!("Результат добавлен в файл: {}", output_file);
             } else {
                 // Режим перезаписи файла
-                fs::write(output_file, &result)
+                fs::write(output_file, result)
                     .with_context(|| format!("Не удалось записать результат в файл: {}", output_file))?;
                 println!("Результат сохранен в файл: {}", output_file);
             }
@@ -180,3 +1132,1146 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Записывает алфавит в каноническом виде и результат в один файл,
+/// который читает --stdin-combined
+fn write_bundle(bundle_file: &str, cipher: &Cipher, result: &str) -> Result<()> {
+    let content = combined::join(&cipher.to_canonical_string(), result);
+    fs::write(bundle_file, content)
+        .with_context(|| format!("Не удалось записать файл: {}", bundle_file))?;
+    eprintln!("Алфавит и результат сохранены в файл: {}", bundle_file);
+    Ok(())
+}
+
+fn generate_alphabet(args: &Args) -> Result<()> {
+    let charset: Vec<char> = args.charset.as_deref().unwrap_or(RUSSIAN).chars().collect();
+    // random_alphabet принимает и пустой набор (так перемешиваются символы
+    // без пары регистра), но алфавит из пустого набора — ошибка. Проверка
+    // идет до вывода начального значения
+    if charset.is_empty() {
+        return Err(CharsetError::Empty.into());
+    }
+
+    let mixed = match &args.keyword {
+        Some(keyword) => generate::keyword_alphabet(keyword, &charset, args.reverse_fill)?,
+        None => {
+            let seed = args
+                .password
+                .as_deref()
+                .map(generate::seed_from_password)
+                .or(args.seed)
+                .unwrap_or_else(seed_from_time);
+            // Алфавит выводится в stdout или в файл, поэтому начальное значение
+            // сообщается в stderr: с ним алфавит можно получить повторно
+            eprintln!("Начальное значение генератора: {} (повторить: --seed {})", seed, seed);
+            let mut rng = seeded_rng(Some(seed));
+            if args.link_case {
+                generate::random_linked_case_alphabet(&charset, &mut rng)?
+            } else {
+                generate::random_alphabet(&charset, &mut rng)?
+            }
+        }
+    };
+    let content = format_entries(charset.into_iter().zip(mixed));
+
+    match &args.output {
+        Some(output_file) => {
+            fs::write(output_file, &content)
+                .with_context(|| format!("Не удалось записать алфавит в файл: {}", output_file))?;
+            println!("Алфавит сохранен в файл: {}", output_file);
+        }
+        None => print!("{}", content),
+    }
+
+    Ok(())
+}
+
+/// Пишет случайный блокнот. Без --seed используется системный генератор
+/// случайных чисел: блокнот, полученный из известного начального значения,
+/// годится только для проверок.
+fn generate_pad(args: &Args, length: usize) -> Result<()> {
+    let charset: Vec<char> = args.charset.as_deref().unwrap_or(RUSSIAN).chars().collect();
+    let pad = generate::random_pad(&charset, length, &mut seeded_rng(args.seed))?;
+
+    eprintln!("Предупреждение: используйте блокнот только один раз, повторное использование раскрывает зашифрованный текст");
+    match &args.output {
+        Some(output_file) => {
+            fs::write(output_file, &pad)
+                .with_context(|| format!("Не удалось записать блокнот в файл: {}", output_file))?;
+            println!("Блокнот сохранен в файл: {}", output_file);
+        }
+        None => println!("{}", pad),
+    }
+
+    Ok(())
+}
+
+/// Начальное значение генератора из текущего времени, для одноразовых алфавитов
+fn seed_from_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Открывает файл для добавления, при сборке с фичей `locking` — под блокировкой
+fn open_append(output_file: &str) -> Result<fs::File> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(output_file)
+        .with_context(|| format!("Не удалось открыть файл для добавления: {}", output_file))?;
+
+    // Блокировка не дает строкам нескольких одновременно запущенных
+    // процессов перемешаться; она снимается при закрытии файла
+    #[cfg(feature = "locking")]
+    fs2::FileExt::lock_exclusive(&file)
+        .with_context(|| format!("Не удалось заблокировать файл: {}", output_file))?;
+
+    Ok(file)
+}
+
+/// Что записать до и после новой записи в режиме добавления. По умолчанию
+/// каждая запись завершается разделителем; с --no-trailing-separator
+/// разделитель ставится перед новой записью, если файл не пуст.
+fn append_separators<'a>(args: &'a Args, file: &fs::File, output_file: &str) -> Result<(&'a str, &'a str)> {
+    let separator = args.append_separator.as_str();
+    if !args.no_trailing_separator {
+        return Ok(("", separator));
+    }
+
+    let length = file
+        .metadata()
+        .with_context(|| format!("Не удалось получить размер файла: {}", output_file))?
+        .len();
+    Ok((if length == 0 { "" } else { separator }, ""))
+}
+
+/// Полный цикл обработки одного текста: подготовка входа, шифр выбранного
+/// режима и оформление результата. Каждый включенный этап выполняется
+/// под своим именем, чтобы `--profile` мог показать его время.
+fn transform(
+    args: &Args,
+    engine: &mut Engine,
+    direction: Direction,
+    input_text: String,
+    profiler: &mut Profiler,
+) -> Result<String> {
+    let mut text = input_text;
+
+    if args.normalize_eol_input {
+        text = profiler.stage("eol", || text::normalize_eol(&text));
+    }
+
+    if let Some(max) = args.max_line_length {
+        if args.truncate_lines {
+            let (truncated_text, truncated) = profiler.stage("lines", || text::truncate_lines(&text, max));
+            if truncated > 0 {
+                eprintln!("Строк длиннее {} символов обрезано: {}", max, truncated);
+            }
+            text = truncated_text;
+        } else if let Some((line, length)) = profiler.stage("lines", || text::find_long_line(&text, max)) {
+            anyhow::bail!(
+                "Строка {} длиннее --max-line-length: {} символов, допустимо не более {}. С --truncate-lines такие строки обрезаются",
+                line,
+                length,
+                max
+            );
+        }
+    }
+
+    if let (Some(marker), Direction::Decrypt) = (&args.marker, direction) {
+        text = profiler.stage("unmark", || match strip_marker(&text, marker) {
+            Some(stripped) => Ok(stripped.to_string()),
+            None if args.decrypt_auto => anyhow::bail!(
+                "Входной текст не начинается с маркера '{}': не удается определить, что он зашифрован",
+                marker
+            ),
+            None => Ok(text.clone()),
+        })?;
+    }
+
+    if let Some(radix) = args.input_radix {
+        text = profiler.stage("decode", || numeric::decode_radix(&text, radix)).context("Ошибка в числовой записи")?;
+    }
+
+    if let (true, Direction::Decrypt) = (args.numeric, direction) {
+        text = profiler.stage("decode", || numeric::decode(&text)).context("Ошибка в числовой записи")?;
+    }
+
+    if let (true, Direction::Decrypt) = (args.rle, direction) {
+        text = profiler.stage("unrle", || rle::decode(&text, args.rle_delimiter)).context("Ошибка в сжатой записи")?;
+    }
+
+    if let (true, Direction::Decrypt) = (args.deinterleave, direction) {
+        text = profiler.stage("deinterleave", || text::deinterleave(&text));
+    }
+
+    if let (Some(delimiter), Direction::Decrypt) = (&args.char_delimiter, direction) {
+        text = profiler.stage("undelimit", || text::split_chars(&text, delimiter)).map_err(|position| {
+            anyhow::anyhow!("После символа {} нет разделителя --char-delimiter {:?}", position, delimiter)
+        })?;
+    }
+
+    if args.collapse_whitespace {
+        text = profiler.stage("normalize", || text::collapse_whitespace(&text));
+    } else if args.strip_whitespace {
+        text = profiler.stage("strip", || text::strip_whitespace(&text));
+    }
+
+    if let (true, Direction::Decrypt) = (args.strip_nulls, direction) {
+        let nulls: Vec<char> = args.null_set.as_deref().unwrap_or_default().chars().collect();
+        text = profiler.stage("strip-nulls", || text::remove_chars(&text, &nulls));
+    }
+
+    // Обращение порядка само себе обратно, поэтому при расшифровке
+    // --reverse-output снимается до подстановки, а --reverse-input — после
+    let (reverse_before, reverse_after) = match direction {
+        Direction::Encrypt => (args.reverse_input, args.reverse_output),
+        Direction::Decrypt => (args.reverse_output, args.reverse_input),
+    };
+
+    if let (Some(size), Direction::Decrypt) = (args.shuffle_blocks, direction) {
+        let mut rng = seeded_rng(args.seed);
+        text = profiler.stage("unshuffle", || blocks::unshuffle(&text, size, args.partial_block, &mut rng));
+    }
+
+    if let (Some(length), Direction::Decrypt) = (args.salt_output, direction) {
+        if text.chars().count() < length {
+            anyhow::bail!("Текст короче соли --salt-output ({} символов)", length);
+        }
+        text = profiler.stage("unsalt", || text.chars().skip(length).collect());
+    }
+
+    if reverse_before {
+        text = profiler.stage("reverse", || text::reverse(&text));
+    }
+
+    // Символ замены, который алфавит заменяет или в который переводит
+    // другой символ, после расшифровки не отличить от пробела
+    if let (Some(replacement), Engine::Substitution { cipher, .. }) = (args.space_as, &engine) {
+        if cipher.contains(replacement) || cipher.mappings().iter().any(|&(_, value)| value == replacement) {
+            anyhow::bail!(
+                "Символ {:?} для --space-as встречается в алфавите: пробелы не удастся однозначно восстановить",
+                replacement
+            );
+        }
+    }
+
+    if let (Some(replacement), Direction::Encrypt) = (args.space_as, direction) {
+        if let Some(position) = text.chars().position(|c| c == replacement) {
+            anyhow::bail!(
+                "Символ {:?} для --space-as уже встречается в тексте (символ {}): после расшифровки он стал бы пробелом",
+                replacement,
+                position + 1
+            );
+        }
+        text = profiler.stage("space", || text.replace(' ', replacement.encode_utf8(&mut [0; 4])));
+    }
+
+    let before_cipher = args.line_summary.then(|| text.clone());
+
+    text = profiler.stage("cipher", || {
+        if args.json_values {
+            let document: serde_json::Value = serde_json::from_str(&text)
+                .context("Входной текст не является корректным JSON")?;
+            let document = json::map_strings(document, args.json_keys, &mut |value| {
+                engine.apply(args, direction, value)
+            })?;
+            serde_json::to_string_pretty(&document).context("Не удалось сериализовать JSON")
+        } else if let Some(field) = args.field {
+            let (result, short_lines) = fields::map_field(&text, &args.delimiter, field, &mut |value| {
+                engine.apply(args, direction, value)
+            })?;
+            if short_lines > 0 {
+                eprintln!("Строк, в которых меньше {} полей, оставлено без изменений: {}", field, short_lines);
+            }
+            Ok(result)
+        } else if args.preserve_indent {
+            text::map_indented(&text, &mut |line| engine.apply(args, direction, line))
+        } else {
+            engine.apply(args, direction, &text)
+        }
+    })?;
+
+    if let Some(before) = before_cipher {
+        match text::changed_lines(&before, &text) {
+            Some((lines, changed)) => eprintln!(
+                "Строк обработано: {}, изменено: {}, без изменений: {}",
+                lines,
+                changed,
+                lines - changed
+            ),
+            None => eprintln!("Подстановка изменила число строк, построчная сводка недоступна"),
+        }
+    }
+
+    if let (Some(replacement), Direction::Decrypt) = (args.space_as, direction) {
+        text = profiler.stage("space", || text.replace(replacement, " "));
+    }
+
+    if reverse_after {
+        text = profiler.stage("reverse", || text::reverse(&text));
+    }
+
+    if let (Some(length), Direction::Encrypt) = (args.salt_output, direction) {
+        let Engine::Substitution { cipher, .. } = &engine else {
+            anyhow::bail!("--salt-output доступен только для простой подстановки");
+        };
+        let mut charset: Vec<char> = cipher.mappings().into_iter().map(|(_, substituted)| substituted).collect();
+        charset.sort_unstable();
+        charset.dedup();
+        let mut rng = seeded_rng(args.seed);
+        let salt = profiler.stage("salt", || generate::random_pad(&charset, length, &mut rng))
+            .context("Не удалось создать соль --salt-output")?;
+        text = salt + &text;
+    }
+
+    if let (Some(size), Direction::Encrypt) = (args.shuffle_blocks, direction) {
+        let mut rng = seeded_rng(args.seed);
+        text = profiler.stage("shuffle", || blocks::shuffle(&text, size, args.partial_block, &mut rng));
+    }
+
+    if let (Some(rate), Direction::Encrypt) = (args.insert_nulls, direction) {
+        let nulls: Vec<char> = args.null_set.as_deref().unwrap_or_default().chars().collect();
+        let mut rng = seeded_rng(args.seed);
+        text = profiler.stage("nulls", || text::insert_nulls(&text, &nulls, rate, &mut rng));
+    }
+
+    if args.output_case != OutputCase::Preserve {
+        text = profiler.stage("case", || text::change_case(&text, args.output_case));
+    }
+
+    if let (Some(delimiter), Direction::Encrypt) = (&args.char_delimiter, direction) {
+        if let Some(c) = text.chars().find(|&c| delimiter.contains(c)) {
+            anyhow::bail!(
+                "Символ {:?} разделителя --char-delimiter встречается в шифротексте: разделенную запись нельзя будет прочитать однозначно",
+                c
+            );
+        }
+        text = profiler.stage("delimit", || text::join_chars(&text, delimiter));
+    }
+
+    if let (Some(decoy_file), Direction::Encrypt) = (&args.interleave, direction) {
+        let decoy: Vec<char> = read_input_file(args, decoy_file)?.chars().collect();
+        if decoy.is_empty() {
+            anyhow::bail!("Файл приманки пуст: {}", decoy_file);
+        }
+        text = profiler.stage("interleave", || text::interleave(&text, &decoy));
+    }
+
+    if let (true, Direction::Encrypt) = (args.rle, direction) {
+        text = profiler.stage("rle", || rle::encode(&text, args.rle_delimiter)).context("Не удалось сжать серии")?;
+    }
+
+    if let (true, Direction::Encrypt) = (args.numeric, direction) {
+        text = profiler.stage("encode", || numeric::encode(&text));
+    }
+
+    if let Some(radix) = args.input_radix {
+        text = profiler.stage("encode", || numeric::encode_radix(&text, radix));
+    }
+
+    if let (Some(marker), Direction::Encrypt) = (&args.marker, direction) {
+        text = profiler.stage("mark", || format!("{}\n{}", marker, text));
+    }
+
+    if let Some(ending) = args.line_ending {
+        text = profiler.stage("line-ending", || text::convert_line_endings(&text, ending));
+    }
+
+    Ok(text)
+}
+
+/// Код завершения --count-only-mapped-ratio: 0, если доля символов с
+/// подстановкой не ниже --warn-ratio, --warn-exit-code, если она ниже
+/// --warn-ratio, но не ниже --fail-ratio, и --fail-exit-code, если ниже
+/// --fail-ratio
+fn mapped_ratio_exit_code(args: &Args, ratio: f64) -> Result<u8> {
+    let warn = args.warn_ratio.unwrap_or(0.0);
+    let fail = args.fail_ratio.unwrap_or(0.0);
+    if fail > warn {
+        anyhow::bail!("--fail-ratio ({}) не может быть больше --warn-ratio ({})", fail, warn);
+    }
+
+    Ok(if ratio < fail {
+        args.fail_exit_code
+    } else if ratio < warn {
+        args.warn_exit_code
+    } else {
+        0
+    })
+}
+
+/// Шифрует `plain_file` и сравнивает с `cipher_file`, затем расшифровывает
+/// `cipher_file` и сравнивает с `plain_file`. Обе проверки выполняются
+/// всегда, чтобы сразу было видно, какое направление разошлось.
+fn verify_roundtrip(args: &Args, plain_file: &str, cipher_file: &str) -> Result<()> {
+    let mut profiler = Profiler::new(args.profile);
+    let mode = Mode::from_args(args);
+    let alphabets = profiler.stage("read", || mode.read_alphabets(args))?;
+    let mut engine = profiler.stage("load", || mode.load(args, &alphabets))?;
+
+    let plain = read_input_file(args, plain_file)?;
+    let ciphertext = read_input_file(args, cipher_file)?;
+
+    let checks = [
+        (Direction::Encrypt, plain_file, &plain, cipher_file, &ciphertext),
+        (Direction::Decrypt, cipher_file, &ciphertext, plain_file, &plain),
+    ];
+    let mut failed = 0;
+    for (direction, source_file, source, expected_file, expected) in checks {
+        let name = match direction {
+            Direction::Encrypt => "Шифрование",
+            Direction::Decrypt => "Расшифровка",
+        };
+        let actual = transform(args, &mut engine, direction, source.clone(), &mut profiler)?;
+        engine.finish(args, direction)?;
+        match text::first_difference(expected, &actual) {
+            None => println!("{} {}: совпадает с {}", name, source_file, expected_file),
+            Some(difference) => {
+                failed += 1;
+                let show = |c: Option<char>| c.map_or("конец текста".to_string(), |c| format!("{:?}", c));
+                println!(
+                    "{} {}: расходится с {} в символе {} (строка {}, столбец {}): ожидалось {}, получено {}",
+                    name,
+                    source_file,
+                    expected_file,
+                    difference.position,
+                    difference.line,
+                    difference.column,
+                    show(difference.expected),
+                    show(difference.actual)
+                );
+            }
+        }
+    }
+
+    profiler.report();
+    if failed > 0 {
+        anyhow::bail!("Проверка не пройдена: расхождений {} из {}", failed, checks.len());
+    }
+    Ok(())
+}
+
+/// Расшифровывает текст каждым алфавитом --decrypt-guess и печатает
+/// варианты по возрастанию хи-квадрат: первым идет самый правдоподобный.
+/// Варианты без латинских букв оценить нельзя, они идут последними.
+fn decrypt_guess(args: &Args) -> Result<()> {
+    let mut input_text = read_whole_input(args)?;
+
+    // С --header перебираются только алфавиты с отпечатком из заголовка
+    let mut header = None;
+    if args.header {
+        let (parsed, rest) = Header::split(&input_text)?
+            .context("Входной текст не начинается с заголовка --header: не удается выбрать алфавит")?;
+        if parsed.mode != Mode::Substitution.name() {
+            anyhow::bail!("Текст зашифрован в режиме {}, а --decrypt-guess перебирает алфавиты простой подстановки", parsed.mode);
+        }
+        input_text = rest.to_string();
+        header = Some(parsed);
+    }
+
+    let mut profiler = Profiler::new(args.profile);
+    let mut candidates = Vec::with_capacity(args.decrypt_guess.len());
+    for key_file in &args.decrypt_guess {
+        let alphabet = Alphabet::from_file(key_file)?;
+        if let Some(header) = &header {
+            if key_fingerprint(args, Mode::Substitution, std::slice::from_ref(&alphabet))? != header.fingerprint {
+                continue;
+            }
+        }
+        let mut engine = Mode::Substitution.load(args, &[alphabet])?;
+        let result = transform(args, &mut engine, Direction::Decrypt, input_text.clone(), &mut profiler)
+            .and_then(|result| engine.finish(args, Direction::Decrypt).map(|()| result))
+            .with_context(|| format!("Не удалось расшифровать алфавитом {}", key_file))?;
+        candidates.push((score::english_chi_squared(&result), key_file, result));
+    }
+    if let (Some(header), true) = (&header, candidates.is_empty()) {
+        anyhow::bail!("Ни у одного алфавита --decrypt-guess нет отпечатка {} из заголовка", header.fingerprint);
+    }
+    let rank_key = |score: Option<f64>| score.unwrap_or(f64::INFINITY);
+    candidates.sort_by(|a, b| rank_key(a.0).total_cmp(&rank_key(b.0)));
+
+    for (rank, (score, key_file, result)) in candidates.iter().enumerate() {
+        let score = score.map_or("нет букв".to_string(), |score| format!("хи-квадрат {:.1}", score));
+        println!("{}. {} ({}): {}", rank + 1, key_file, score, preview(&result.replace('\n', " "), 60));
+    }
+    Ok(())
+}
+
+/// Предлагает ключ по частотам букв шифротекста (см. [`score::suggest_key`])
+fn suggest_key(args: &Args) -> Result<()> {
+    let ciphertext = read_whole_input(args)?;
+    let pairs = score::suggest_key(&ciphertext, args.lang);
+    if pairs.is_empty() {
+        anyhow::bail!("В шифротексте нет букв, подобрать ключ не по чему");
+    }
+    eprintln!("Ключ подобран только по частотам букв: используйте его как отправную точку и уточняйте вручную");
+
+    let content = format_entries(pairs);
+    match &args.output {
+        Some(output_file) => {
+            fs::write(output_file, &content)
+                .with_context(|| format!("Не удалось записать алфавит в файл: {}", output_file))?;
+            println!("Алфавит сохранен в файл: {}", output_file);
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+/// Текст аргументов или содержимое всех файлов --input одной строкой
+fn read_whole_input(args: &Args) -> Result<String> {
+    Ok(match (&args.text[..], &args.input[..]) {
+        ([], []) => anyhow::bail!("Не указан текст для обработки. Используйте текстовый аргумент или --input"),
+        ([], input_files) => input_files
+            .iter()
+            .map(|input_file| read_input_file(args, input_file))
+            .collect::<Result<Vec<_>>>()?
+            .concat(),
+        (text, _) => text.join(" "),
+    })
+}
+
+/// Заголовок --header для выбранных режима и ключа; `None` без --header
+fn text_header(args: &Args, alphabets: &[Alphabet]) -> Result<Option<Header>> {
+    if !args.header {
+        return Ok(None);
+    }
+    let mode = Mode::from_args(args);
+    Ok(Some(Header {
+        mode: mode.name().to_string(),
+        fingerprint: key_fingerprint(args, mode, alphabets)?,
+    }))
+}
+
+/// Отпечаток ключа режима. Алфавиты подстановки, в том числе роторы и
+/// алфавиты позиций, берутся в канонической записи, поэтому формат и
+/// комментарии файла на отпечаток не влияют; для остальных режимов
+/// отпечаток снимается с содержимого файлов или с ключевого слова.
+fn key_fingerprint(args: &Args, mode: Mode, alphabets: &[Alphabet]) -> Result<String> {
+    let key = match mode {
+        Mode::Substitution | Mode::Alternating | Mode::Cycle | Mode::Rotor => alphabets
+            .iter()
+            .map(|alphabet| Ok(alphabet.cipher_allowing_empty(args)?.to_canonical_string()))
+            .collect::<Result<Vec<_>>>()?
+            .join("\n"),
+        Mode::Homophonic | Mode::Words | Mode::OneTimePad => alphabets
+            .iter()
+            .map(|alphabet| alphabet.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Mode::Bifid => args.key.clone().unwrap_or_default(),
+    };
+    Ok(header::fingerprint(key.as_bytes()))
+}
+
+/// Отрезает заголовок --header в начале текста, сверив его с ожидаемым
+fn strip_header<'a>(text: &'a str, expected: &Header) -> Result<&'a str> {
+    let (header, rest) = Header::split(text)?
+        .context("Входной текст не начинается с заголовка --header: не удается проверить режим и ключ")?;
+    if header.mode != expected.mode {
+        let flags = Mode::ALL
+            .iter()
+            .find(|mode| mode.name() == header.mode)
+            .map_or(String::new(), |mode| format!(" ({})", mode.flags()));
+        anyhow::bail!("Текст зашифрован в режиме {}{}, а выбран режим {}", header.mode, flags, expected.mode);
+    }
+    if header.fingerprint != expected.fingerprint {
+        anyhow::bail!(
+            "Отпечаток ключа {} не совпадает с отпечатком {} из заголовка: текст зашифрован другим ключом",
+            expected.fingerprint,
+            header.fingerprint
+        );
+    }
+    Ok(rest)
+}
+
+/// Отрезает строку маркера в начале текста
+fn strip_marker<'a>(text: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(marker)?;
+    if rest.is_empty() {
+        return Some(rest);
+    }
+    rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))
+}
+
+/// Генератор случайных чисел: воспроизводимый, если задано начальное значение.
+/// Всегда ChaCha20 (`rand_chacha::ChaCha20Rng`), а не `StdRng`, алгоритм
+/// которого может смениться в новой версии rand: при одном начальном
+/// значении числа одни и те же на любой платформе и в любой версии.
+fn seeded_rng(seed: Option<u64>) -> ChaCha20Rng {
+    match seed {
+        Some(seed) => ChaCha20Rng::seed_from_u64(seed),
+        None => ChaCha20Rng::from_os_rng(),
+    }
+}
+
+/// Набор символов для режимов, которым важен номер символа: --charset,
+/// иначе порядок из директивы @order алфавита, иначе русский алфавит
+fn ordered_charset(args: &Args, order: Vec<char>) -> Vec<char> {
+    match &args.charset {
+        Some(charset) => charset.chars().collect(),
+        None if !order.is_empty() => order,
+        None => RUSSIAN.chars().collect(),
+    }
+}
+
+/// `true`, если --input — адрес http:// или https://, а не путь к файлу
+fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+#[cfg_attr(not(feature = "network"), allow(unused_variables))]
+fn read_input_bytes(args: &Args, input_file: &str) -> Result<Vec<u8>> {
+    if is_url(input_file) {
+        #[cfg(feature = "network")]
+        return network::fetch(input_file, args.timeout);
+        #[cfg(not(feature = "network"))]
+        anyhow::bail!("Чтение по адресу {} доступно только в сборке с фичей network", input_file);
+    }
+    fs::read(input_file).with_context(|| format!("Не удалось прочитать входной файл: {}", input_file))
+}
+
+fn read_input_file(args: &Args, input_file: &str) -> Result<String> {
+    let bytes = read_input_bytes(args, input_file)?;
+    let content = if args.lossy {
+        let (content, replaced) = args.input_encoding.decode_lossy(&bytes);
+        if replaced > 0 {
+            eprintln!("Файл {}: недопустимых байтов заменено символом U+FFFD: {}", input_file, replaced);
+        }
+        content
+    } else {
+        args.input_encoding
+            .decode(&bytes)
+            .with_context(|| format!("Не удалось прочитать входной файл: {}", input_file))?
+    };
+    Ok(match content.strip_prefix(BOM) {
+        Some(stripped) if !args.keep_bom => stripped.to_string(),
+        _ => content,
+    })
+}
+
+fn alphabet(args: &Args) -> Result<&str> {
+    args.alphabet
+        .as_deref()
+        .context("Не указан алфавит. Используйте --alphabet или --map")
+}
+
+fn print_info(cipher: &Cipher, show_hex: bool) {
+    println!("Подстановок: {}", cipher.len());
+
+    let fixed_points = cipher.fixed_points();
+    if fixed_points.is_empty() {
+        println!("Неподвижных символов нет");
+    } else {
+        let list: Vec<String> = fixed_points.iter().map(|&c| quoted(c, show_hex)).collect();
+        println!("Неподвижные символы ({}): {}", fixed_points.len(), list.join(", "));
+    }
+
+    let deleted = cipher.deleted();
+    if !deleted.is_empty() {
+        let list: Vec<String> = deleted.iter().map(|&c| quoted(c, show_hex)).collect();
+        println!("Удаляемые символы ({}): {}", deleted.len(), list.join(", "));
+    }
+
+    if cipher.is_identity() {
+        println!("Предупреждение: алфавит не меняет ни одного символа");
+    }
+}
+
+/// Символ в кавычках и, если нужно, его кодовая точка
+fn quoted(c: char, show_hex: bool) -> String {
+    if show_hex {
+        format!("'{}' {}", c.escape_debug(), text::codepoint(c))
+    } else {
+        format!("'{}'", c)
+    }
+}
+
+fn print_cycles(cipher: &Cipher) {
+    let cycles = cipher.cycles();
+    let notation: String = cycles
+        .iter()
+        .filter(|cycle| cycle.len() > 1)
+        .map(|cycle| {
+            let chars: Vec<String> = cycle.iter().map(char::to_string).collect();
+            format!("({})", chars.join(" "))
+        })
+        .collect();
+
+    if notation.is_empty() {
+        println!("Циклов длины больше 1 нет");
+    } else {
+        println!("{}", notation);
+    }
+
+    let fixed = cycles.iter().filter(|cycle| cycle.len() == 1).count();
+    if fixed > 0 {
+        println!("Неподвижных символов: {}", fixed);
+    }
+
+    match cipher.order() {
+        Some(order) => println!("Порядок: {} (столько раундов возвращают исходный текст)", order),
+        None => {
+            let in_cycles: usize = cycles.iter().map(Vec::len).sum();
+            println!(
+                "Подстановка не является перестановкой: {} из {} символов не входят в циклы, порядок не определен",
+                cipher.len() - in_cycles,
+                cipher.len()
+            );
+        }
+    }
+}
+
+/// Эвристики, по которым видно особенно слабый ключ. Это предупреждение, а
+/// не оценка стойкости: любую простую подстановку вскрывает частотный анализ.
+fn print_key_strength(cipher: &Cipher, charset: &[char]) {
+    let fixed = cipher.fixed_points();
+    let longest_cycle = cipher.cycles().iter().map(Vec::len).max().unwrap_or(0);
+    let order = cipher.order();
+    let shift = cipher.caesar_shift(charset);
+
+    println!("Символов в алфавите: {}", cipher.len());
+    if fixed.is_empty() {
+        println!("Неподвижных символов нет");
+    } else {
+        let chars: Vec<String> = fixed.iter().map(char::to_string).collect();
+        println!("Неподвижных символов: {} ({})", fixed.len(), chars.join(", "));
+    }
+    println!("Самый длинный цикл: {}", longest_cycle);
+    match order {
+        Some(order) => println!("Порядок: {}", order),
+        None => println!("Порядок не определен: подстановка не является перестановкой"),
+    }
+    match shift {
+        Some(shift) => println!("Ключ равносилен шифру Цезаря со сдвигом {}", shift),
+        None => println!("Ключ не сводится к шифру Цезаря"),
+    }
+
+    let mut weaknesses = Vec::new();
+    if cipher.is_identity() {
+        weaknesses.push("подстановка ничего не меняет".to_string());
+    } else {
+        if shift.is_some() {
+            weaknesses.push(format!(
+                "ключ — сдвиг: чтобы вскрыть шифр, достаточно перебрать все сдвиги, их {}",
+                charset.len().saturating_sub(1)
+            ));
+        }
+        // Один-два неподвижных символа случаются и в случайном ключе,
+        // а больше десятой части алфавита уже заметно в шифротексте
+        if fixed.len() * 10 > cipher.len() {
+            weaknesses.push(format!(
+                "{} из {} символов заменяются сами на себя и видны в шифротексте как есть",
+                fixed.len(),
+                cipher.len()
+            ));
+        }
+        if order == Some(2) {
+            weaknesses.push("ключ — инволюция: шифрование совпадает с расшифровкой".to_string());
+        }
+    }
+
+    if weaknesses.is_empty() {
+        println!("Явных слабостей не найдено");
+    } else {
+        println!("Слабости:");
+        for weakness in &weaknesses {
+            println!("  - {}", weakness);
+        }
+    }
+    println!("Любую простую подстановку можно вскрыть частотным анализом; эти проверки находят только особенно слабые ключи");
+}
+
+/// Переписывает алфавит в каноническом виде. Подстановки сортируются,
+/// директивы `@include` остаются в начале файла, а комментарии — с
+/// --keep-comments — перед ними. Файл на месте заменяется атомарно:
+/// результат пишется во временный файл рядом и переименовывается.
+fn canonicalize_keyfile(args: &Args, key_file: &str) -> Result<()> {
+    let content = fs::read_to_string(key_file)
+        .with_context(|| format!("Не удалось прочитать файл алфавита: {}", key_file))?;
+    let (includes, own) = include::split(&content);
+    let options = ParseOptions {
+        format: args.alphabet_format,
+        allow_empty_value: args.allow_empty_value,
+        disjoint_sets: args.disjoint_sets,
+        csv_header: args.csv_header,
+    };
+    let cipher = Cipher::parse_with(&own, options)
+        .with_context(|| format!("Ошибка в алфавите (файл {})", key_file))?;
+
+    let mut sections = Vec::new();
+    if args.keep_comments {
+        let comments: String = content
+            .lines()
+            .filter(|line| line.trim_start().starts_with('#'))
+            .map(|line| format!("{}\n", line.trim_start()))
+            .collect();
+        sections.push(comments);
+    }
+    sections.push(includes.iter().map(|target| format!("@include {}\n", target)).collect());
+    let order = cipher.alphabet_order();
+    if !order.is_empty() {
+        sections.push(format!("@order {}\n", order.into_iter().collect::<String>()));
+    }
+    sections.push(cipher.to_canonical_string());
+    sections.retain(|section| !section.is_empty());
+    let canonical = sections.join("\n");
+
+    let target = args.output.as_deref().unwrap_or(key_file);
+    let path = Path::new(target);
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let temporary = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+    fs::write(&temporary, canonical)
+        .with_context(|| format!("Не удалось записать временный файл: {}", temporary.display()))?;
+    if let Err(err) = fs::rename(&temporary, path) {
+        let _ = fs::remove_file(&temporary);
+        return Err(err).with_context(|| format!("Не удалось заменить файл: {}", target));
+    }
+
+    println!("Алфавит записан в каноническом виде: {}", target);
+    Ok(())
+}
+
+fn print_comparison(changes: &[KeyChange]) {
+    if changes.is_empty() {
+        println!("Алфавиты совпадают");
+        return;
+    }
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in changes {
+        match *change {
+            KeyChange::Added { key, value } => {
+                added += 1;
+                println!("+ {}", entry(key, value));
+            }
+            KeyChange::Removed { key, value } => {
+                removed += 1;
+                println!("- {}", entry(key, value));
+            }
+            KeyChange::Changed { key, old, new } => {
+                changed += 1;
+                println!("~ {} (было: {})", entry(key, new), entry(key, old));
+            }
+        }
+    }
+    println!("Добавлено: {}, удалено: {}, изменено: {}", added, removed, changed);
+}
+
+/// Подстановка в записи "ключ = значение"; удаляемый символ — "ключ ="
+fn entry(key: char, value: Option<char>) -> String {
+    match value {
+        Some(value) => format!("{} = {}", key, value),
+        None => format!("{} =", key),
+    }
+}
+
+/// Сколько самых частых непокрытых символов показывает --coverage
+const COVERAGE_TOP: usize = 10;
+
+fn print_coverage(cipher: &Cipher, corpus: &str, show_hex: bool) {
+    let frequencies = text::char_frequencies(corpus);
+    let mut uncovered: Vec<(char, usize)> = frequencies
+        .iter()
+        .filter(|(&c, _)| !cipher.contains(c))
+        .map(|(&c, &count)| (c, count))
+        .collect();
+    uncovered.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let distinct = frequencies.len();
+    let covered = distinct - uncovered.len();
+    let percent = if distinct == 0 {
+        100.0
+    } else {
+        covered as f64 / distinct as f64 * 100.0
+    };
+
+    println!("Различных символов в корпусе: {}", distinct);
+    println!("Покрыто алфавитом: {} ({:.1}%)", covered, percent);
+    println!("Не покрыто: {}", uncovered.len());
+
+    if !uncovered.is_empty() {
+        println!("Самые частые символы без подстановки:");
+        for (c, count) in uncovered.iter().take(COVERAGE_TOP) {
+            if show_hex {
+                println!("  {:?} {}  {}", c, text::codepoint(*c), count);
+            } else {
+                println!("  {:?}  {}", c, count);
+            }
+        }
+    }
+}
+
+/// Первые `limit` символов текста и многоточие, если текст длиннее
+fn preview(text: &str, limit: usize) -> String {
+    let mut chars = text.chars();
+    let mut shown: String = chars.by_ref().take(limit).collect();
+    if chars.next().is_some() {
+        shown.push('…');
+    }
+    shown
+}
+
+/// Текст алфавита и его источник для сообщений об ошибках
+struct Alphabet {
+    origin: String,
+    content: String,
+    /// Формат, известный заранее; `None` — определять по содержимому
+    format: Option<AlphabetFormat>,
+    /// Файл алфавита, от каталога которого отсчитываются пути `@include`
+    path: Option<PathBuf>,
+}
+
+impl Alphabet {
+    /// Собирает алфавит из флагов --map или читает файл из --alphabet
+    fn read(args: &Args) -> Result<Self> {
+        if !args.map.is_empty() {
+            return Ok(Self::from_maps(&args.map));
+        }
+        Self::from_file(alphabet(args)?)
+    }
+
+    fn from_file(filename: &str) -> Result<Self> {
+        let content = fs::read_to_string(filename)
+            .with_context(|| format!("Не удалось прочитать файл алфавита: {}", filename))?;
+        Ok(Alphabet {
+            origin: format!("файл {}", filename),
+            content,
+            format: None,
+            path: Some(PathBuf::from(filename)),
+        })
+    }
+
+    /// Каждый флаг --map становится строкой "ключ = значение", поэтому
+    /// номер строки в сообщении об ошибке — это порядковый номер флага
+    fn from_maps(maps: &[String]) -> Self {
+        Alphabet {
+            origin: "параметры --map".to_string(),
+            content: maps.join("\n"),
+            format: Some(AlphabetFormat::KeyValue),
+            path: None,
+        }
+    }
+
+    /// Разбирает алфавит разборщиком нужного режима, дополняя ошибку источником
+    fn parse<T>(&self, parse: impl FnOnce(&str) -> Result<T, CipherError>) -> Result<T> {
+        parse(&self.content).with_context(|| format!("Ошибка в алфавите ({})", self.origin))
+    }
+
+    /// Разбирает алфавит подстановки в формате --alphabet-format или,
+    /// если он не указан, в формате, определенном по содержимому. Алфавит
+    /// без подстановок (например, из одних комментариев) — ошибка, а с
+    /// --allow-empty — предупреждение.
+    fn cipher(&self, args: &Args) -> Result<Cipher> {
+        let cipher = self.cipher_allowing_empty(args)?;
+        if cipher.is_empty() {
+            if !args.allow_empty {
+                anyhow::bail!(
+                    "Алфавит ({}) не содержит ни одной подстановки, и текст прошел бы без изменений. \
+                     Если это намеренно, укажите --allow-empty",
+                    self.origin
+                );
+            }
+            eprintln!("Предупреждение ({}): алфавит не содержит ни одной подстановки", self.origin);
+        }
+        Ok(cipher)
+    }
+
+    fn cipher_allowing_empty(&self, args: &Args) -> Result<Cipher> {
+        let format = self.format(args);
+        let merged = match &self.path {
+            Some(path) => self.parse(|content| {
+                include::expand(content, path, format, args.allow_empty_value, args.include_conflict)
+            })?,
+            None => None,
+        };
+
+        let options = ParseOptions {
+            format: Some(if merged.is_some() { AlphabetFormat::KeyValue } else { format }),
+            allow_empty_value: args.allow_empty_value,
+            disjoint_sets: args.disjoint_sets,
+            csv_header: args.csv_header,
+        };
+        let cipher = match &merged {
+            Some(merged) => Cipher::parse_with(merged, options)
+                .with_context(|| format!("Ошибка в алфавите ({} с включенными файлами)", self.origin))?,
+            None => self.parse(|content| Cipher::parse_with(content, options))?,
+        };
+
+        if !args.no_long_key_warn {
+            for (line, key) in format::multi_char_keys(&self.content, self.format(args), args.csv_header) {
+                let first: String = key.chars().take(1).collect();
+                eprintln!(
+                    "Предупреждение ({}): строка {}: ключ '{}' длиннее одного символа, используется только '{}'",
+                    self.origin, line, key, first
+                );
+            }
+        }
+
+        Ok(cipher)
+    }
+
+    fn format(&self, args: &Args) -> AlphabetFormat {
+        args.alphabet_format
+            .or(self.format)
+            .unwrap_or_else(|| AlphabetFormat::detect(&self.content))
+    }
+}
+
+/// Читает из stdin алфавит и текст, разделенные пустой строкой
+fn read_combined_stdin() -> Result<(Alphabet, String)> {
+    let mut content = String::new();
+    io::stdin()
+        .read_to_string(&mut content)
+        .context("Не удалось прочитать стандартный ввод")?;
+
+    let (alphabet, text) = combined::split(&content).context(
+        "Во входном потоке нет пустой строки, отделяющей алфавит от текста",
+    )?;
+
+    let alphabet = Alphabet {
+        origin: "стандартный ввод".to_string(),
+        content: alphabet.to_string(),
+        format: None,
+        path: None,
+    };
+    Ok((alphabet, text.to_string()))
+}
+
+fn parse_map(value: &str) -> Result<String, String> {
+    if value.contains(['\n', '\r']) {
+        return Err("подстановка не может содержать перевод строки".to_string());
+    }
+    Ok(value.to_string())
+}
+
+fn parse_unit_interval(value: &str) -> Result<f64, String> {
+    let number: f64 = value
+        .parse()
+        .map_err(|_| format!("'{}' не является числом", value))?;
+    if !(0.0..=1.0).contains(&number) {
+        return Err(format!("значение должно быть от 0 до 1, получено {}", number));
+    }
+    Ok(number)
+}
+
+fn parse_exit_code(value: &str) -> Result<u8, String> {
+    match value.parse() {
+        Ok(code) if code > 1 => Ok(code),
+        _ => Err(format!("код завершения должен быть числом от 2 до 255 (1 означает ошибку), получено '{}'", value)),
+    }
+}
+
+fn parse_null_set(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("набор пустых символов не может быть пустым".to_string());
+    }
+    Ok(value.to_string())
+}
+
+fn parse_buffer_size(value: &str) -> Result<usize, String> {
+    let size: usize = value
+        .parse()
+        .map_err(|_| format!("'{}' не является целым числом", value))?;
+    if size < chunked::MIN_BUFFER_SIZE {
+        return Err(format!(
+            "буфер должен вмещать хотя бы один символ UTF-8 ({} байта), получено {}",
+            chunked::MIN_BUFFER_SIZE,
+            size
+        ));
+    }
+    Ok(size)
+}
+
+/// Проверяет шаблон --output-template: без {n} части не различались бы по имени
+fn parse_template(value: &str) -> Result<String, String> {
+    if !value.contains(split::PLACEHOLDER) {
+        return Err(format!("в шаблоне нет {}: все части записывались бы в один файл", split::PLACEHOLDER));
+    }
+    Ok(value.to_string())
+}
+
+/// Раскрывает escape-последовательности \n, \r, \t, \0 и \\
+fn parse_escapes(value: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        result.push(match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some(other) => return Err(format!("неизвестная escape-последовательность '\\{}'", other)),
+            None => return Err("строка не может заканчиваться одиночной '\\'".to_string()),
+        });
+    }
+
+    Ok(result)
+}
+
+fn parse_char_delimiter(value: &str) -> Result<String, String> {
+    let delimiter = parse_escapes(value)?;
+    if delimiter.is_empty() {
+        return Err("разделитель не может быть пустым".to_string());
+    }
+    Ok(delimiter)
+}
+
+fn parse_space_as(value: &str) -> Result<char, String> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(' '), None) => Err("пробел нельзя заменить самим собой".to_string()),
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("'{}' не является одним символом", value)),
+    }
+}
+
+fn parse_positive(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) => Err("значение должно быть положительным целым числом".to_string()),
+        Ok(number) => Ok(number),
+        Err(_) => Err(format!("'{}' не является целым числом", value)),
+    }
+}
+
+fn parse_period(value: &str) -> Result<usize, String> {
+    match parse_positive(value) {
+        Ok(period) => Ok(period),
+        Err(_) if value.parse::<usize>() == Ok(0) => Err("период должен быть положительным".to_string()),
+        Err(err) => Err(err),
+    }
+}
+
+fn parse_merge(value: &str) -> Result<(char, char), String> {
+    let mut chars = value.chars().map(|c| c.to_ascii_uppercase());
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(kept), Some(merged), None) => Ok((kept, merged)),
+        _ => Err(format!("ожидаются две буквы, например IJ, получено '{}'", value)),
+    }
+}
+
+fn parse_radix(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(radix) if numeric::RADIXES.contains(&radix) => Ok(radix),
+        _ => Err(format!("основание системы счисления должно быть одним из 2, 8, 10, 16, получено '{}'", value)),
+    }
+}
+
+fn parse_percent(value: &str) -> Result<f64, String> {
+    let percent: f64 = value
+        .parse()
+        .map_err(|_| format!("'{}' не является числом", value))?;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(format!("процент должен быть от 0 до 100, получено {}", percent));
+    }
+    Ok(percent)
+}
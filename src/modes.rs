@@ -0,0 +1,522 @@
+use std::collections::HashSet;
+use std::fs;
+use anyhow::{Context, Result};
+use cipher::bifid::BifidCipher;
+use cipher::generate::RUSSIAN;
+use cipher::homophonic::HomophonicCipher;
+use cipher::otp::OneTimePadCipher;
+use cipher::positional::PositionalCipher;
+use cipher::rotor::RotorCipher;
+use cipher::word::WordCipher;
+use cipher::{text, Cipher, Direction, Lookup, Stats};
+use rand_chacha::ChaCha20Rng;
+
+use crate::{ordered_charset, seeded_rng, Alphabet, Args};
+
+/// Режимы шифрования. Список `--list-modes` и выбор режима в `run`
+/// строятся по этой таблице, поэтому новый режим достаточно добавить сюда.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Substitution,
+    Homophonic,
+    Alternating,
+    Words,
+    Bifid,
+    OneTimePad,
+    Rotor,
+    Cycle,
+}
+
+impl Mode {
+    pub const ALL: &'static [Mode] = &[
+        Mode::Substitution,
+        Mode::Homophonic,
+        Mode::Alternating,
+        Mode::Words,
+        Mode::Bifid,
+        Mode::OneTimePad,
+        Mode::Rotor,
+        Mode::Cycle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Substitution => "substitution",
+            Mode::Homophonic => "homophonic",
+            Mode::Alternating => "double-substitution",
+            Mode::Words => "words",
+            Mode::Bifid => "bifid",
+            Mode::OneTimePad => "one-time-pad",
+            Mode::Rotor => "rotor",
+            Mode::Cycle => "cycle",
+        }
+    }
+
+    /// Флаги, необходимые для выбора режима
+    pub fn flags(self) -> &'static str {
+        match self {
+            Mode::Substitution => "-a ФАЙЛ",
+            Mode::Homophonic => "-a ФАЙЛ --homophonic",
+            Mode::Alternating => "--alternating --alphabet-even ФАЙЛ --alphabet-odd ФАЙЛ",
+            Mode::Words => "-a ФАЙЛ --word-mode",
+            Mode::Bifid => "--bifid --key СЛОВО",
+            Mode::OneTimePad => "--otp ФАЙЛ [--charset СИМВОЛЫ]",
+            Mode::Rotor => "--rotor ФАЙЛ... [--charset СИМВОЛЫ] [--rotor-step N]",
+            Mode::Cycle => "--cycle-alphabets ФАЙЛ ФАЙЛ...",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Mode::Substitution => "Простая подстановка: каждый символ заменяется одним символом",
+            Mode::Homophonic => "Каждый символ заменяется одним из нескольких вариантов с учетом весов",
+            Mode::Alternating => "Четные и нечетные позиции шифруются двумя разными алфавитами",
+            Mode::Words => "Целые слова заменяются по словарю, остальной текст не меняется",
+            Mode::Bifid => "Шифр Деластелля: координаты букв в квадрате Полибия перемешиваются",
+            Mode::OneTimePad => "Одноразовый блокнот: номер символа складывается с номером символа блокнота",
+            Mode::Rotor => "Символ проходит через несколько роторов, которые поворачиваются после каждого символа",
+            Mode::Cycle => "Позиции шифруются несколькими алфавитами по кругу: первая — первым, вторая — вторым",
+        }
+    }
+
+    pub fn from_args(args: &Args) -> Mode {
+        if args.homophonic {
+            Mode::Homophonic
+        } else if args.alternating {
+            Mode::Alternating
+        } else if args.word_mode {
+            Mode::Words
+        } else if args.bifid {
+            Mode::Bifid
+        } else if args.otp.is_some() {
+            Mode::OneTimePad
+        } else if !args.rotor.is_empty() {
+            Mode::Rotor
+        } else if !args.cycle_alphabets.is_empty() {
+            Mode::Cycle
+        } else {
+            Mode::Substitution
+        }
+    }
+
+    /// Файлы алфавитов, которые нужны режиму, в порядке их использования
+    pub fn alphabet_files(self, args: &Args) -> Result<Vec<&str>> {
+        Ok(match self {
+            Mode::Substitution | Mode::Homophonic | Mode::Words if !args.map.is_empty() => Vec::new(),
+            Mode::Substitution | Mode::Homophonic | Mode::Words => vec![crate::alphabet(args)?],
+            Mode::Alternating => vec![
+                args.alphabet_even.as_deref().context("Не указан алфавит для четных позиций. Используйте --alphabet-even")?,
+                args.alphabet_odd.as_deref().context("Не указан алфавит для нечетных позиций. Используйте --alphabet-odd")?,
+            ],
+            // Квадрат строится по ключевому слову, файл алфавита не нужен
+            Mode::Bifid => Vec::new(),
+            Mode::OneTimePad => vec![args.otp.as_deref().context("Не указан файл блокнота. Используйте --otp")?],
+            Mode::Rotor => args.rotor.iter().map(String::as_str).collect(),
+            Mode::Cycle => args.cycle_alphabets.iter().map(String::as_str).collect(),
+        })
+    }
+
+    pub fn read_alphabets(self, args: &Args) -> Result<Vec<Alphabet>> {
+        match self {
+            Mode::Substitution | Mode::Homophonic | Mode::Words => Ok(vec![Alphabet::read(args)?]),
+            Mode::OneTimePad => {
+                let pad_file = args.otp.as_deref().context("Не указан файл блокнота. Используйте --otp")?;
+                let content = fs::read_to_string(pad_file)
+                    .with_context(|| format!("Не удалось прочитать файл блокнота: {}", pad_file))?;
+                Ok(vec![Alphabet {
+                    origin: format!("блокнот {}", pad_file),
+                    content,
+                    format: None,
+                    path: None,
+                }])
+            }
+            _ => self
+                .alphabet_files(args)?
+                .into_iter()
+                .map(Alphabet::from_file)
+                .collect(),
+        }
+    }
+
+    /// Загружает алфавиты режима; один загруженный шифр можно применять
+    /// к нескольким текстам
+    pub fn load(self, args: &Args, alphabets: &[Alphabet]) -> Result<Engine> {
+        Ok(match (self, alphabets) {
+            (Mode::Substitution, [alphabet]) => Engine::Substitution {
+                cipher: alphabet.cipher(args)?,
+                stats: Stats::default(),
+            },
+            (Mode::Homophonic, [alphabet]) => Engine::Homophonic {
+                cipher: alphabet.parse(HomophonicCipher::parse)?,
+                rng: seeded_rng(args.seed),
+            },
+            (Mode::Alternating, [even, odd]) => Engine::Alternating(PositionalCipher::new(
+                vec![even.cipher(args)?, odd.cipher(args)?],
+                args.skip_unmapped_positions,
+            )),
+            (Mode::Cycle, alphabets) if alphabets.len() >= 2 => Engine::Alternating(PositionalCipher::new(
+                alphabets.iter().map(|alphabet| alphabet.cipher(args)).collect::<Result<Vec<_>>>()?,
+                args.skip_unmapped_positions,
+            )),
+            (Mode::Words, [alphabet]) => {
+                Engine::Words(alphabet.parse(|content| WordCipher::parse(content, args.preserve_case))?)
+            }
+            (Mode::Bifid, []) => {
+                let key = args.key.as_deref().context("Не указано ключевое слово. Используйте --key")?;
+                Engine::Bifid(BifidCipher::new(key, args.bifid_merge, args.period).context("Ошибка в ключе")?)
+            }
+            (Mode::OneTimePad, [pad]) => {
+                let charset: Vec<char> = args.charset.as_deref().unwrap_or(RUSSIAN).chars().collect();
+                Engine::OneTimePad {
+                    cipher: OneTimePadCipher::new(&charset, &pad.content).context("Ошибка в наборе символов")?,
+                    uses: 0,
+                }
+            }
+            (Mode::Rotor, rotors) if !rotors.is_empty() => {
+                let rotors = rotors.iter().map(|rotor| rotor.cipher(args)).collect::<Result<Vec<_>>>()?;
+                let charset = ordered_charset(args, rotors[0].alphabet_order());
+                Engine::Rotor(RotorCipher::new(&charset, &rotors, args.rotor_step).context("Ошибка в роторах")?)
+            }
+            _ => anyhow::bail!(
+                "Режим {} не поддерживает {} алфавит(а)",
+                self.name(),
+                alphabets.len()
+            ),
+        })
+    }
+}
+
+/// Загруженный шифр выбранного режима
+pub enum Engine {
+    /// `stats` — статистика всего текста, переданного шифру, для
+    /// --report-unmapped-to и других флагов, которые подводят итог
+    /// (см. [`Engine::finish`])
+    Substitution { cipher: Cipher, stats: Stats },
+    Homophonic {
+        cipher: HomophonicCipher,
+        rng: ChaCha20Rng,
+    },
+    Alternating(PositionalCipher),
+    Words(WordCipher),
+    Bifid(BifidCipher),
+    /// `uses` — сколько раз блокнот уже применялся: каждый раз он
+    /// расходуется с начала
+    OneTimePad { cipher: OneTimePadCipher, uses: usize },
+    Rotor(RotorCipher),
+}
+
+impl Engine {
+    /// Применяет шифр --rounds раз подряд
+    pub fn apply(&mut self, args: &Args, direction: Direction, input_text: &str) -> Result<String> {
+        if args.no_passthrough_newlines {
+            self.check_newlines(args, direction, input_text)?;
+        }
+
+        // Текст может приходить частями (поля, значения JSON, файлы), а
+        // итог подводится один раз по всем частям. Учитывается вход первого
+        // раунда: следующие раунды заменяют уже результат
+        if let (true, Engine::Substitution { cipher, stats }) = (collects_stats(args), &mut *self) {
+            stats.merge(cipher.analyze(input_text, direction));
+        }
+
+        let rounds = self.effective_rounds(args);
+        if rounds == 0 {
+            // Число раундов кратно порядку, и текст не меняется, но
+            // --fail-on-passthrough проверяет его так же, как первый раунд.
+            // Раунды перестановки заменяют только ключи алфавита, поэтому
+            // следующие раунды проверку пройдут
+            if let (true, Engine::Substitution { cipher, .. }) = (args.fail_on_passthrough, &*self) {
+                match direction {
+                    Direction::Encrypt => cipher.encrypt_strict(input_text)?,
+                    Direction::Decrypt => cipher.decrypt_strict(input_text)?,
+                };
+            }
+            return Ok(input_text.to_string());
+        }
+
+        let mut text = self.apply_once(args, direction, input_text)?;
+        for _ in 1..rounds {
+            text = self.apply_once(args, direction, &text)?;
+        }
+        Ok(text)
+    }
+
+    /// Ошибка на первом переводе строки (`\n` или `\r`), который шифр
+    /// оставил бы без изменений. Алфавит простой подстановки может заменять
+    /// перевод строки; остальные режимы всегда его пропускают.
+    fn check_newlines(&self, args: &Args, direction: Direction, input_text: &str) -> Result<()> {
+        let substitutable = substitutable(args);
+        let passes = |c: char| match self {
+            Engine::Substitution { cipher, .. } => !substitutable(c) || cipher.lookup(c, direction) == Lookup::Unmapped,
+            _ => true,
+        };
+
+        let found = input_text
+            .chars()
+            .enumerate()
+            .find(|&(_, c)| matches!(c, '\n' | '\r') && passes(c));
+        if let Some((index, c)) = found {
+            anyhow::bail!(
+                "Перевод строки {:?} в позиции {} прошел бы без изменений, а --no-passthrough-newlines это запрещает",
+                c,
+                index + 1
+            );
+        }
+        Ok(())
+    }
+
+    /// Число раундов с учетом порядка подстановки: после `order` раундов
+    /// перестановка возвращает исходный текст, поэтому достаточно `rounds %
+    /// order`. Сокращение верно только для простой подстановки без
+    /// --only-class и --exclude-chars: с ними символ, замененный в одном
+    /// раунде, в следующем может остаться без изменений. С
+    /// --swap-case-unmapped меняются и символы вне алфавита, и их период
+    /// с порядком не связан. --trace описывает каждый раунд, поэтому с ним
+    /// раунды выполняются все.
+    fn effective_rounds(&self, args: &Args) -> u64 {
+        match self {
+            Engine::Substitution { cipher, .. }
+                if args.only_class.is_empty() && args.exclude_chars.is_none() && !args.swap_case_unmapped && !args.trace =>
+            {
+                match cipher.order() {
+                    Some(order) => (u128::from(args.rounds) % order) as u64,
+                    None => args.rounds,
+                }
+            }
+            _ => args.rounds,
+        }
+    }
+
+    /// Подводит итог по всему тексту, который прошел через [`Engine::apply`]:
+    /// печатает --per-char-stats, записывает --report-unmapped-to и проверяет --max-unmapped и
+    /// --max-unmapped-pct. Вызывается один раз, когда текст
+    /// обработан целиком; накопленная статистика после этого сбрасывается.
+    pub fn finish(&mut self, args: &Args, direction: Direction) -> Result<()> {
+        let Engine::Substitution { cipher, stats } = self else {
+            return Ok(());
+        };
+        let stats = std::mem::take(stats);
+
+        if args.per_char_stats {
+            print_per_char_stats(cipher, direction, &stats);
+        }
+
+        if let Some(report_file) = &args.report_unmapped_to {
+            write_unmapped_report(report_file, &stats, args.show_hex)?;
+        }
+
+        let unmapped = stats.unmapped_count();
+        let percent = stats.unmapped_ratio() * 100.0;
+
+        if let Some(limit) = args.max_unmapped {
+            if unmapped > limit {
+                anyhow::bail!(
+                    "Слишком много символов без подстановки: {} из {}, допустимо не более {}. Возможно, выбран не тот алфавит",
+                    unmapped,
+                    stats.total,
+                    limit
+                );
+            }
+        }
+        if let Some(limit) = args.max_unmapped_pct {
+            if percent > limit {
+                anyhow::bail!(
+                    "Слишком много символов без подстановки: {:.1}%, допустимо не более {}%. Возможно, выбран не тот алфавит",
+                    percent,
+                    limit
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_once(&mut self, args: &Args, direction: Direction, input_text: &str) -> Result<String> {
+        match self {
+            Engine::Substitution { cipher, .. } => run_substitution(cipher, args, direction, input_text),
+            Engine::Homophonic { cipher, rng } => Ok(match direction {
+                Direction::Encrypt => cipher.encrypt(input_text, rng),
+                Direction::Decrypt => cipher.decrypt(input_text),
+            }),
+            Engine::Alternating(cipher) => Ok(match direction {
+                Direction::Encrypt => cipher.encrypt(input_text),
+                Direction::Decrypt => cipher.decrypt(input_text),
+            }),
+            Engine::Words(cipher) => Ok(match direction {
+                Direction::Encrypt => cipher.encrypt(input_text),
+                Direction::Decrypt => cipher.decrypt(input_text),
+            }),
+            Engine::Bifid(cipher) => Ok(match direction {
+                Direction::Encrypt => cipher.encrypt(input_text),
+                Direction::Decrypt => cipher.decrypt(input_text),
+            }),
+            Engine::Rotor(cipher) => Ok(match direction {
+                Direction::Encrypt => cipher.encrypt(input_text),
+                Direction::Decrypt => cipher.decrypt(input_text),
+            }),
+            Engine::OneTimePad { cipher, uses } => {
+                *uses += 1;
+                if *uses == 2 {
+                    eprintln!(
+                        "Предупреждение: блокнот применяется повторно с начала (несколько раундов, значений JSON или файлов). \
+                         Повторное использование блокнота позволяет восстановить текст"
+                    );
+                }
+                Ok(match direction {
+                    Direction::Encrypt => cipher.encrypt(input_text)?,
+                    Direction::Decrypt => cipher.decrypt(input_text)?,
+                })
+            }
+        }
+    }
+}
+
+pub fn print_list() {
+    print!("{}", list());
+}
+
+/// Таблица режимов для --list-modes: имя, флаги выбора и описание
+fn list() -> String {
+    let name_width = Mode::ALL.iter().map(|mode| mode.name().chars().count()).max().unwrap_or(0);
+    let flags_width = Mode::ALL.iter().map(|mode| mode.flags().chars().count()).max().unwrap_or(0);
+
+    Mode::ALL
+        .iter()
+        .map(|mode| {
+            format!(
+                "{:name_width$}  {:flags_width$}  {}\n",
+                mode.name(),
+                mode.flags(),
+                mode.description()
+            )
+        })
+        .collect()
+}
+
+/// Нужна ли статистика всего текста для [`Engine::finish`]
+fn collects_stats(args: &Args) -> bool {
+    args.report_unmapped_to.is_some() || args.max_unmapped.is_some() || args.max_unmapped_pct.is_some() || args.per_char_stats
+}
+
+/// Какие символы разрешено заменять с учетом --only-class и --exclude-chars.
+/// Сам алфавит при этом не меняется.
+pub fn substitutable(args: &Args) -> impl Fn(char) -> bool + '_ {
+    let excluded: HashSet<char> = args.exclude_chars.as_deref().unwrap_or_default().chars().collect();
+
+    move |c: char| {
+        !excluded.contains(&c)
+            && (args.only_class.is_empty() || args.only_class.iter().any(|class| class.contains(c)))
+    }
+}
+
+fn run_substitution(cipher: &Cipher, args: &Args, direction: Direction, input_text: &str) -> Result<String> {
+    let substitutable = substitutable(args);
+
+    if args.trace {
+        trace(cipher, direction, input_text, &substitutable);
+    }
+
+    let result = match (direction, args.fail_on_passthrough) {
+        _ if args.swap_case_unmapped => swap_case_unmapped(cipher, direction, input_text, &substitutable),
+        (Direction::Encrypt, true) => cipher.encrypt_strict(input_text)?,
+        (Direction::Decrypt, true) => cipher.decrypt_strict(input_text)?,
+        (Direction::Encrypt, false) => cipher.encrypt_where(input_text, substitutable),
+        (Direction::Decrypt, false) => cipher.decrypt_where(input_text, substitutable),
+    };
+
+    Ok(result)
+}
+
+/// Печатает в stderr по строке на сработавшую подстановку: исходный
+/// символ, результат и число вхождений, от самых частых к редким
+fn print_per_char_stats(cipher: &Cipher, direction: Direction, stats: &Stats) {
+    eprintln!("Подстановки по числу срабатываний:");
+    for (c, count) in stats.substitutions_by_count() {
+        match cipher.lookup(c, direction) {
+            Lookup::Mapped(substituted) => eprintln!("  {:?} -> {:?}: {}", c, substituted, count),
+            _ => eprintln!("  {:?} удален: {}", c, count),
+        }
+    }
+}
+
+/// Подстановка, в которой буквы без подстановки меняют регистр. Замена
+/// обратима, если буквы с измененным регистром не совпадают со значениями
+/// алфавита: такая буква при расшифровке была бы заменена по алфавиту.
+fn swap_case_unmapped(cipher: &Cipher, direction: Direction, input_text: &str, substitutable: impl Fn(char) -> bool) -> String {
+    input_text
+        .chars()
+        .filter_map(|c| match cipher.lookup(c, direction) {
+            _ if !substitutable(c) => Some(c),
+            Lookup::Mapped(substituted) => Some(substituted),
+            Lookup::Deleted => None,
+            Lookup::Unmapped => Some(text::swap_case(c)),
+        })
+        .collect()
+}
+
+/// Печатает в stderr по строке на символ: позицию (с единицы), символ,
+/// примененное правило и результат через табуляцию. Удаленный символ
+/// результата не имеет.
+fn trace(cipher: &Cipher, direction: Direction, input_text: &str, substitutable: impl Fn(char) -> bool) {
+    for (index, c) in input_text.chars().enumerate() {
+        let (rule, output) = match cipher.lookup(c, direction) {
+            _ if !substitutable(c) => ("исключен", Some(c)),
+            Lookup::Mapped(substituted) => ("подстановка", Some(substituted)),
+            Lookup::Deleted => ("удаление", None),
+            Lookup::Unmapped => ("нет подстановки", Some(c)),
+        };
+        let output = output.map(|output| format!("{:?}", output)).unwrap_or_default();
+        eprintln!("трассировка\t{}\t{:?}\t{}\t{}", index + 1, c, rule, output);
+    }
+}
+
+/// Записывает символы без подстановки по одному в строке: символ
+/// (управляющие символы экранируются) и через табуляцию число вхождений.
+/// С `show_hex` между ними добавляется столбец с кодовой точкой.
+fn write_unmapped_report(report_file: &str, stats: &Stats, show_hex: bool) -> Result<()> {
+    let report: String = stats
+        .unmapped
+        .iter()
+        .map(|(&c, count)| {
+            if show_hex {
+                format!("{}\t{}\t{}\n", c.escape_debug(), text::codepoint(c), count)
+            } else {
+                format!("{}\t{}\n", c.escape_debug(), count)
+            }
+        })
+        .collect();
+    fs::write(report_file, report)
+        .with_context(|| format!("Не удалось записать отчет о символах без подстановки: {}", report_file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// Аргументы из строки флагов режима: необязательные флаги в скобках
+    /// отбрасываются, а места для значений заполняются
+    fn args_for(mode: Mode) -> Args {
+        let flags = mode.flags().split(" [").next().unwrap();
+        let words = flags.split_whitespace().map(|word| match word {
+            "ФАЙЛ" | "ФАЙЛ..." => "key.txt",
+            "СЛОВО" => "KEY",
+            flag => flag,
+        });
+        Args::parse_from(["cipher"].into_iter().chain(words).chain(["-e", "текст"]))
+    }
+
+    #[test]
+    fn every_listed_mode_is_dispatched_by_its_flags() {
+        let listing = list();
+        for &mode in Mode::ALL {
+            assert!(
+                listing.lines().any(|line| line.starts_with(mode.name()) && line.contains(mode.flags())),
+                "{} нет в списке:\n{}",
+                mode.name(),
+                listing
+            );
+            assert_eq!(Mode::from_args(&args_for(mode)), mode, "{}", mode.flags());
+        }
+        assert_eq!(listing.lines().count(), Mode::ALL.len());
+    }
+}
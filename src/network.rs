@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+/// Загружает содержимое по адресу http:// или https://. Ответ с кодом
+/// ошибки (4xx, 5xx) считается неудачей, как и истечение --timeout.
+pub fn fetch(url: &str, timeout: u64) -> Result<Vec<u8>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(timeout)))
+        .build()
+        .into();
+
+    agent
+        .get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().with_config().limit(u64::MAX).read_to_vec())
+        .with_context(|| format!("Не удалось загрузить {}", url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Отвечает на один запрос по локальному адресу строкой статуса `status`
+    /// и телом `body`; возвращает адрес файла на этом сервере
+    fn serve_once(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+        });
+        format!("http://{}/text.txt", address)
+    }
+
+    #[test]
+    fn fetches_body() {
+        let url = serve_once("200 OK", "абв");
+        assert_eq!(fetch(&url, 5).unwrap(), "абв".as_bytes());
+    }
+
+    #[test]
+    fn error_status_is_failure() {
+        let url = serve_once("404 Not Found", "нет");
+        let message = format!("{:#}", fetch(&url, 5).unwrap_err());
+        assert!(message.contains(&format!("Не удалось загрузить {}", url)), "{}", message);
+        assert!(message.contains("404"), "{}", message);
+    }
+
+    #[test]
+    fn silent_server_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/text.txt", listener.local_addr().unwrap());
+        // Соединение принимается очередью сокета, но ответа нет
+        let message = format!("{:#}", fetch(&url, 1).unwrap_err());
+        assert!(message.contains("Не удалось загрузить"), "{}", message);
+        drop(listener);
+    }
+}
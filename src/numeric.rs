@@ -0,0 +1,113 @@
+//! Запись текста кодами символов Unicode: каждый символ заменяется
+//! номером, номера разделяются пробелами. По умолчанию номера
+//! шестнадцатеричные; так шифротекст можно передать по каналу, который
+//! искажает непечатаемые символы или не поддерживает Unicode. Числа в
+//! других системах счисления (2, 8, 10) подходят для данных, которые
+//! уже передаются в таком виде, например байтов.
+
+use thiserror::Error;
+
+/// Ошибки восстановления текста из кодов
+#[derive(Debug, Error)]
+pub enum NumericError {
+    #[error("Код {position} '{token}' не является числом в системе счисления с основанием {radix}")]
+    InvalidToken { position: usize, token: String, radix: u32 },
+
+    #[error("Код {position} '{token}' не соответствует символу Unicode: суррогаты D800–DFFF и значения больше 10FFFF недопустимы")]
+    InvalidCodepoint { position: usize, token: String },
+}
+
+/// Основания систем счисления, которые поддерживают [`encode_radix`] и [`decode_radix`]
+pub const RADIXES: [u32; 4] = [2, 8, 10, 16];
+
+/// Записывает каждый символ шестнадцатеричным кодом не короче четырех цифр
+pub fn encode(text: &str) -> String {
+    join_codes(text, |code| format!("{:04X}", code))
+}
+
+/// Восстанавливает текст из шестнадцатеричных кодов, разделенных
+/// пробельными символами. Суррогаты (D800–DFFF) и значения больше 10FFFF
+/// символами не являются и отклоняются с указанием кода и его порядкового номера.
+pub fn decode(text: &str) -> Result<String, NumericError> {
+    decode_radix(text, 16)
+}
+
+/// Записывает каждый символ кодом в системе счисления `radix` (одной из
+/// [`RADIXES`]). Коды дополняются нулями до длины, нужной для записи
+/// байта: восемь двоичных цифр, три восьмеричные, две шестнадцатеричные;
+/// десятичные не дополняются.
+pub fn encode_radix(text: &str, radix: u32) -> String {
+    join_codes(text, |code| match radix {
+        2 => format!("{:08b}", code),
+        8 => format!("{:03o}", code),
+        16 => format!("{:02X}", code),
+        _ => code.to_string(),
+    })
+}
+
+/// Восстанавливает текст из кодов в системе счисления `radix`, разделенных
+/// пробельными символами, с теми же проверками, что и [`decode`]
+pub fn decode_radix(text: &str, radix: u32) -> Result<String, NumericError> {
+    text.split_whitespace()
+        .enumerate()
+        .map(|(index, token)| {
+            let position = index + 1;
+            let value = u32::from_str_radix(token, radix).map_err(|_| NumericError::InvalidToken {
+                position,
+                token: token.to_string(),
+                radix,
+            })?;
+            char::from_u32(value).ok_or_else(|| NumericError::InvalidCodepoint {
+                position,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn join_codes(text: &str, code: impl Fn(u32) -> String) -> String {
+    text.chars()
+        .map(|c| code(c as u32))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(encode("аб!"), "0430 0431 0021");
+        assert_eq!(decode("0430 0431\n0021").unwrap(), "аб!");
+    }
+
+    #[test]
+    fn surrogate_is_rejected_with_position() {
+        let err = decode("0430 D800 0431").unwrap_err();
+        assert!(matches!(&err, NumericError::InvalidCodepoint { position: 2, token } if token == "D800"));
+        assert!(err.to_string().contains("Код 2 'D800'"), "{}", err);
+    }
+
+    #[test]
+    fn value_above_unicode_range_is_rejected() {
+        let err = decode("10FFFF 110000").unwrap_err();
+        assert!(matches!(&err, NumericError::InvalidCodepoint { position: 2, token } if token == "110000"));
+    }
+
+    #[test]
+    fn malformed_token_is_rejected() {
+        let err = decode_radix("12 1a", 10).unwrap_err();
+        assert!(matches!(&err, NumericError::InvalidToken { position: 2, radix: 10, .. }));
+    }
+
+    #[test]
+    fn every_radix_round_trips() {
+        for radix in RADIXES {
+            let encoded = encode_radix("аб z", radix);
+            assert_eq!(decode_radix(&encoded, radix).unwrap(), "аб z", "{}", radix);
+        }
+        assert_eq!(encode_radix("a\n", 2), "01100001 00001010");
+        assert_eq!(encode_radix("a\n", 16), "61 0A");
+    }
+}
@@ -0,0 +1,124 @@
+//! Одноразовый блокнот над заданным набором символов: номер каждого
+//! символа текста складывается с номером очередного символа блокнота по
+//! модулю длины набора (при расшифровке — вычитается).
+//!
+//! Символы текста, которых нет в наборе, проходят без изменений и символ
+//! блокнота не расходуют. Символы блокнота вне набора (переводы строк,
+//! пробелы между группами) пропускаются. Стойкость шифра держится на том,
+//! что блокнот случаен, не короче текста и никогда не используется дважды.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::generate::{check_charset, CharsetError};
+use crate::Direction;
+
+/// Ошибки одноразового блокнота
+#[derive(Debug, Error)]
+pub enum OtpError {
+    #[error(transparent)]
+    Charset(#[from] CharsetError),
+
+    #[error("Блокнот слишком короткий: для текста нужно {needed} символов набора, в блокноте {available}")]
+    PadTooShort { needed: usize, available: usize },
+}
+
+#[derive(Debug)]
+pub struct OneTimePadCipher {
+    charset: Vec<char>,
+    index: HashMap<char, usize>,
+    /// Номера символов блокнота в наборе
+    pad: Vec<usize>,
+}
+
+impl OneTimePadCipher {
+    pub fn new(charset: &[char], pad: &str) -> Result<Self, OtpError> {
+        check_charset(charset)?;
+
+        let index: HashMap<char, usize> = charset.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+        let pad = pad.chars().filter_map(|c| index.get(&c).copied()).collect();
+
+        Ok(OneTimePadCipher {
+            charset: charset.to_vec(),
+            index,
+            pad,
+        })
+    }
+
+    /// Сколько символов текста из набора можно зашифровать этим блокнотом
+    pub fn pad_len(&self) -> usize {
+        self.pad.len()
+    }
+
+    pub fn encrypt(&self, text: &str) -> Result<String, OtpError> {
+        self.apply(text, Direction::Encrypt)
+    }
+
+    pub fn decrypt(&self, text: &str) -> Result<String, OtpError> {
+        self.apply(text, Direction::Decrypt)
+    }
+
+    fn apply(&self, text: &str, direction: Direction) -> Result<String, OtpError> {
+        let needed = text.chars().filter(|c| self.index.contains_key(c)).count();
+        if needed > self.pad.len() {
+            return Err(OtpError::PadTooShort {
+                needed,
+                available: self.pad.len(),
+            });
+        }
+
+        let length = self.charset.len();
+        let mut pad = self.pad.iter();
+        Ok(text
+            .chars()
+            .map(|c| match self.index.get(&c) {
+                Some(&position) => {
+                    // Длины хватает: проверено выше
+                    let shift = pad.next().copied().unwrap_or(0);
+                    let shifted = match direction {
+                        Direction::Encrypt => (position + shift) % length,
+                        Direction::Decrypt => (position + length - shift) % length,
+                    };
+                    self.charset[shifted]
+                }
+                None => c,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATIN: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn textbook_example_round_trips() {
+        let charset: Vec<char> = LATIN.chars().collect();
+        let cipher = OneTimePadCipher::new(&charset, "XMCKL").unwrap();
+        assert_eq!(cipher.encrypt("HELLO").unwrap(), "EQNVZ");
+        assert_eq!(cipher.decrypt("EQNVZ").unwrap(), "HELLO");
+    }
+
+    #[test]
+    fn characters_outside_charset_do_not_use_pad() {
+        let charset: Vec<char> = LATIN.chars().collect();
+        let cipher = OneTimePadCipher::new(&charset, "XMC KL\n").unwrap();
+        assert_eq!(cipher.pad_len(), 5);
+        let encrypted = cipher.encrypt("HE, LLO!").unwrap();
+        assert_eq!(encrypted, "EQ, NVZ!");
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "HE, LLO!");
+    }
+
+    #[test]
+    fn short_pad_is_rejected() {
+        let charset: Vec<char> = LATIN.chars().collect();
+        let cipher = OneTimePadCipher::new(&charset, "XMCK").unwrap();
+        assert!(matches!(
+            cipher.encrypt("HELLO"),
+            Err(OtpError::PadTooShort { needed: 5, available: 4 })
+        ));
+    }
+}
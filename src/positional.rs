@@ -0,0 +1,102 @@
+use crate::{Cipher, Direction, Lookup};
+
+/// Шифр, который выбирает алфавит по позиции символа в тексте: символ
+/// на позиции `i` обрабатывается алфавитом `i % n`.
+///
+/// По умолчанию позицию занимает каждый символ текста, поэтому расшифровка
+/// всегда восстанавливает исходный текст. Если `skip_unmapped`, символ без
+/// подстановки в текущем алфавите проходит без изменений и позицию не
+/// занимает — так пробелы и знаки препинания не сбивают чередование букв.
+/// Обратимость в этом случае требует, чтобы каждый алфавит переводил свой
+/// набор ключей в тот же набор символов.
+///
+/// Символ, который текущий алфавит удаляет, при шифровании пропадает и
+/// позицию не занимает, поэтому остальные символы расшифровываются верно.
+#[derive(Debug)]
+pub struct PositionalCipher {
+    ciphers: Vec<Cipher>,
+    skip_unmapped: bool,
+}
+
+impl PositionalCipher {
+    pub fn new(ciphers: Vec<Cipher>, skip_unmapped: bool) -> Self {
+        PositionalCipher {
+            ciphers,
+            skip_unmapped,
+        }
+    }
+
+    pub fn encrypt(&self, text: &str) -> String {
+        self.apply(text, Direction::Encrypt)
+    }
+
+    pub fn decrypt(&self, text: &str) -> String {
+        self.apply(text, Direction::Decrypt)
+    }
+
+    fn apply(&self, text: &str, direction: Direction) -> String {
+        if self.ciphers.is_empty() {
+            return text.to_string();
+        }
+
+        let mut position = 0;
+        text.chars()
+            .filter_map(|c| match self.ciphers[position % self.ciphers.len()].lookup(c, direction) {
+                Lookup::Mapped(substituted) => {
+                    position += 1;
+                    Some(substituted)
+                }
+                Lookup::Deleted => None,
+                Lookup::Unmapped => {
+                    if !self.skip_unmapped {
+                        position += 1;
+                    }
+                    Some(c)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseOptions;
+
+    fn two_alphabets(skip_unmapped: bool) -> PositionalCipher {
+        let even = Cipher::parse("а = б\nб = в\nв = а\n").unwrap();
+        let odd = Cipher::parse("а = в\nб = а\nв = б\n").unwrap();
+        PositionalCipher::new(vec![even, odd], skip_unmapped)
+    }
+
+    #[test]
+    fn round_trip_with_two_alphabets() {
+        let cipher = two_alphabets(false);
+        let encrypted = cipher.encrypt("ааа бвб");
+        assert_eq!(encrypted, "бвб вбв");
+        assert_eq!(cipher.decrypt(&encrypted), "ааа бвб");
+    }
+
+    #[test]
+    fn unmapped_characters_can_skip_positions() {
+        let cipher = two_alphabets(true);
+        let encrypted = cipher.encrypt("аа аа");
+        assert_eq!(encrypted, "бв бв");
+        assert_eq!(cipher.decrypt(&encrypted), "аа аа");
+    }
+
+    #[test]
+    fn deleted_characters_are_dropped_without_taking_a_position() {
+        let options = ParseOptions {
+            allow_empty_value: true,
+            ..ParseOptions::default()
+        };
+        let even = Cipher::parse_with("а = б\nб = а\n- =\n", options).unwrap();
+        let odd = Cipher::parse_with("а = а\nб = б\n- =\n", options).unwrap();
+        let cipher = PositionalCipher::new(vec![even, odd], false);
+
+        let encrypted = cipher.encrypt("а-б-а");
+        assert_eq!(encrypted, "ббб");
+        assert_eq!(cipher.decrypt(&encrypted), "аба");
+    }
+}
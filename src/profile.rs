@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+/// Замеряет время именованных этапов обработки для `--profile`.
+/// Если замер выключен, этапы просто выполняются.
+pub struct Profiler {
+    enabled: bool,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Profiler {
+    pub fn new(enabled: bool) -> Self {
+        Profiler {
+            enabled,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Выполняет этап `name`; время повторных запусков этапа суммируется
+    pub fn stage<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+
+        let started = Instant::now();
+        let result = f();
+        let elapsed = started.elapsed();
+
+        match self.stages.iter_mut().find(|(stage, _)| *stage == name) {
+            Some((_, total)) => *total += elapsed,
+            None => self.stages.push((name, elapsed)),
+        }
+
+        result
+    }
+
+    /// Печатает таблицу этапов в stderr
+    pub fn report(&self) {
+        if !self.enabled {
+            return;
+        }
+
+        let width = self
+            .stages
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(["Итого".chars().count()])
+            .max()
+            .unwrap_or(0);
+
+        eprintln!("{:width$}  Время", "Этап");
+        for (name, elapsed) in &self.stages {
+            eprintln!("{:width$}  {}", name, format_duration(*elapsed));
+        }
+        let total = self.stages.iter().map(|(_, elapsed)| *elapsed).sum();
+        eprintln!("{:width$}  {}", "Итого", format_duration(total));
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3} мс", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,109 @@
+//! Сжатие серий повторяющихся символов шифротекста: серия записывается
+//! как `символ×число×`. Разделитель не должен встречаться в самом тексте,
+//! иначе запись была бы неоднозначной, поэтому такой текст отклоняется.
+
+use thiserror::Error;
+
+/// Ошибки сжатия и разворачивания серий
+#[derive(Debug, Error)]
+pub enum RleError {
+    #[error("Разделитель '{0}' встречается в тексте, сжатие серий было бы неоднозначным")]
+    DelimiterInText(char),
+
+    #[error("Символ {position}: некорректная запись серии")]
+    Malformed { position: usize },
+}
+
+/// Заменяет серии одинаковых символов записью `символ×число×`, если она
+/// короче самой серии; короткие серии остаются как есть
+pub fn encode(text: &str, delimiter: char) -> Result<String, RleError> {
+    if text.contains(delimiter) {
+        return Err(RleError::DelimiterInText(delimiter));
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let mut count = 1;
+        while chars.next_if_eq(&c).is_some() {
+            count += 1;
+        }
+
+        let encoded = format!("{}{}{}{}", c, delimiter, count, delimiter);
+        if encoded.chars().count() < count {
+            result.push_str(&encoded);
+        } else {
+            result.extend(std::iter::repeat_n(c, count));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Разворачивает записи `символ×число×` обратно в серии
+pub fn decode(text: &str, delimiter: char) -> Result<String, RleError> {
+    let mut result = String::with_capacity(text.len());
+    let mut previous = None;
+    let mut chars = text.chars().enumerate();
+
+    while let Some((index, c)) = chars.next() {
+        if c != delimiter {
+            result.push(c);
+            previous = Some(c);
+            continue;
+        }
+
+        // Символ серии уже записан один раз, добавляем остальные
+        let position = index + 1;
+        let repeated = previous.take().ok_or(RleError::Malformed { position })?;
+        let mut digits = String::new();
+        let mut closed = false;
+        for (_, c) in chars.by_ref() {
+            if c == delimiter {
+                closed = true;
+                break;
+            }
+            digits.push(c);
+        }
+        let count: usize = digits
+            .parse()
+            .ok()
+            .filter(|&count| closed && count > 0)
+            .ok_or(RleError::Malformed { position })?;
+        result.extend(std::iter::repeat_n(repeated, count - 1));
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_runs_round_trip_shorter() {
+        let text = format!("{}б{}вв12{}", "а".repeat(40), "г".repeat(7), "1".repeat(12));
+        let encoded = encode(&text, '×').unwrap();
+        assert_eq!(encoded, "а×40×бг×7×вв121×12×");
+        assert!(encoded.chars().count() < text.chars().count());
+        assert_eq!(decode(&encoded, '×').unwrap(), text);
+    }
+
+    #[test]
+    fn short_runs_are_kept() {
+        assert_eq!(encode("аабббб", '×').unwrap(), "аабббб");
+    }
+
+    #[test]
+    fn delimiter_in_text_is_rejected() {
+        assert!(matches!(encode("а×б", '×'), Err(RleError::DelimiterInText('×'))));
+    }
+
+    #[test]
+    fn malformed_run_is_rejected() {
+        assert!(matches!(decode("×3×", '×'), Err(RleError::Malformed { position: 1 })));
+        assert!(matches!(decode("а×3", '×'), Err(RleError::Malformed { position: 2 })));
+        assert!(matches!(decode("а×0×", '×'), Err(RleError::Malformed { position: 2 })));
+    }
+}
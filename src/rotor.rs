@@ -0,0 +1,180 @@
+//! Роторный шифр: символ по очереди проходит через несколько роторов —
+//! подстановок над общим набором символов, — а роторы после каждого
+//! символа поворачиваются, как колеса счетчика. Поэтому одинаковые символы
+//! текста на разных позициях шифруются по-разному.
+//!
+//! Правило шифрования. Пусть в наборе `n` символов, `x` — номер символа
+//! текста в наборе, `p[i]` — подстановка ротора `i` в виде номеров, `o[i]` —
+//! его текущий поворот. Ротор `i` переводит `x` в `p[i][(x + o[i]) mod n] -
+//! o[i] mod n`; роторы применяются по порядку, начиная с первого. При
+//! расшифровке обратные подстановки роторов применяются в обратном порядке.
+//!
+//! Правило поворота. После каждого символа из набора первый ротор
+//! поворачивается на `step` позиций. Каждый раз, когда поворот ротора
+//! переходит через `n` (ротор совершает полный оборот), следующий ротор
+//! поворачивается на одну позицию, и так далее. В начале все повороты
+//! нулевые. Символы вне набора проходят без изменений и роторы не
+//! поворачивают.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::generate::{check_charset, CharsetError};
+use crate::{Cipher, Direction, Lookup};
+
+/// Ошибки построения роторов
+#[derive(Debug, Error)]
+pub enum RotorError {
+    #[error(transparent)]
+    Charset(#[from] CharsetError),
+
+    #[error("Ротор {rotor} не является перестановкой набора символов: символ '{ch}' не заменяется символом набора")]
+    NotPermutation { rotor: usize, ch: char },
+}
+
+#[derive(Debug)]
+pub struct RotorCipher {
+    charset: Vec<char>,
+    index: HashMap<char, usize>,
+    /// Подстановки роторов в виде номеров символов набора
+    forward: Vec<Vec<usize>>,
+    /// Обратные подстановки роторов
+    backward: Vec<Vec<usize>>,
+    step: usize,
+}
+
+impl RotorCipher {
+    /// Строит роторы из алфавитов `rotors`. Каждый алфавит должен быть
+    /// перестановкой набора `charset`: задавать подстановку для каждого
+    /// символа набора, и только символом набора.
+    pub fn new(charset: &[char], rotors: &[Cipher], step: usize) -> Result<Self, RotorError> {
+        check_charset(charset)?;
+        if charset.is_empty() {
+            return Err(CharsetError::Empty.into());
+        }
+
+        let index: HashMap<char, usize> = charset.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+        let mut forward = Vec::with_capacity(rotors.len());
+        let mut backward = Vec::with_capacity(rotors.len());
+
+        for (number, rotor) in rotors.iter().enumerate() {
+            let mut wiring = vec![0; charset.len()];
+            let mut inverse = vec![0; charset.len()];
+            for (position, &c) in charset.iter().enumerate() {
+                let target = match rotor.lookup(c, Direction::Encrypt) {
+                    Lookup::Mapped(substituted) => index.get(&substituted).copied(),
+                    Lookup::Deleted | Lookup::Unmapped => None,
+                };
+                let Some(target) = target else {
+                    return Err(RotorError::NotPermutation { rotor: number + 1, ch: c });
+                };
+                wiring[position] = target;
+                inverse[target] = position;
+            }
+            forward.push(wiring);
+            backward.push(inverse);
+        }
+
+        Ok(RotorCipher {
+            charset: charset.to_vec(),
+            index,
+            forward,
+            backward,
+            step,
+        })
+    }
+
+    pub fn encrypt(&self, text: &str) -> String {
+        self.apply(text, Direction::Encrypt)
+    }
+
+    pub fn decrypt(&self, text: &str) -> String {
+        self.apply(text, Direction::Decrypt)
+    }
+
+    fn apply(&self, text: &str, direction: Direction) -> String {
+        let length = self.charset.len();
+        let mut offsets = vec![0; self.forward.len()];
+
+        text.chars()
+            .map(|c| {
+                let Some(&position) = self.index.get(&c) else {
+                    return c;
+                };
+
+                let through = |x: usize, wiring: &[usize], offset: usize| {
+                    (wiring[(x + offset) % length] + length - offset) % length
+                };
+                let position = match direction {
+                    Direction::Encrypt => self
+                        .forward
+                        .iter()
+                        .zip(&offsets)
+                        .fold(position, |x, (wiring, &offset)| through(x, wiring, offset)),
+                    Direction::Decrypt => self
+                        .backward
+                        .iter()
+                        .zip(&offsets)
+                        .rev()
+                        .fold(position, |x, (wiring, &offset)| through(x, wiring, offset)),
+                };
+
+                self.advance(&mut offsets);
+                self.charset[position]
+            })
+            .collect()
+    }
+
+    /// Поворачивает роторы после символа: первый на `step` позиций, каждый
+    /// следующий — на одну за каждый полный оборот предыдущего
+    fn advance(&self, offsets: &mut [usize]) {
+        let length = self.charset.len();
+        let mut carry = self.step;
+        for offset in offsets.iter_mut() {
+            if carry == 0 {
+                break;
+            }
+            let total = *offset + carry;
+            *offset = total % length;
+            carry = total / length;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotors(keys: &[&str]) -> Vec<Cipher> {
+        keys.iter().map(|key| Cipher::parse(key).unwrap()).collect()
+    }
+
+    #[test]
+    fn same_character_is_encrypted_by_position() {
+        let charset: Vec<char> = "абв".chars().collect();
+        let cipher = RotorCipher::new(&charset, &rotors(&["а = б\nб = а\nв = в\n"]), 1).unwrap();
+        assert_eq!(cipher.encrypt("аааа"), "бваб");
+        assert_eq!(cipher.encrypt("а-а"), "б-в");
+    }
+
+    #[test]
+    fn decrypt_reverses_encrypt_with_carry() {
+        let charset: Vec<char> = "абвгд".chars().collect();
+        let keys = ["а = в\nб = д\nв = а\nг = б\nд = г\n", "а = б\nб = г\nв = д\nг = а\nд = в\n"];
+        let text = "абвгд, где два? вдаб бг ааааааааааааа";
+        for step in [1, 2, 7] {
+            let cipher = RotorCipher::new(&charset, &rotors(&keys), step).unwrap();
+            let encrypted = cipher.encrypt(text);
+            assert_ne!(encrypted, text);
+            assert_eq!(cipher.decrypt(&encrypted), text, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn rotor_must_permute_charset() {
+        let charset: Vec<char> = "абв".chars().collect();
+        let error = RotorCipher::new(&charset, &rotors(&["а = б\nб = а\n"]), 1).unwrap_err();
+        assert!(matches!(error, RotorError::NotPermutation { rotor: 1, ch: 'в' }), "{}", error);
+    }
+}
@@ -0,0 +1,129 @@
+//! Оценка того, насколько текст похож на текст на естественном языке:
+//! помогает выбрать из нескольких вариантов расшифровки правильный.
+
+use std::collections::HashMap;
+
+/// Частоты латинских букв a–z в английских текстах, в процентах
+const ENGLISH_FREQUENCIES: [f64; 26] = [
+    8.167, 1.492, 2.782, 4.253, 12.702, 2.228, 2.015, 6.094, 6.966, 0.153, 0.772, 4.025, 2.406,
+    6.749, 7.507, 1.929, 0.095, 5.987, 6.327, 9.056, 2.758, 0.978, 2.360, 0.150, 1.974, 0.074,
+];
+
+/// Строчные латинские буквы от самой частой в английских текстах к самой редкой
+const ENGLISH_BY_FREQUENCY: &str = "etaoinshrdlcumwfgypbvkjxqz";
+
+/// Строчные русские буквы от самой частой в русских текстах к самой редкой
+const RUSSIAN_BY_FREQUENCY: &str = "оеаинтсрвлкмдпуяызьбгчйхжшюцщэфъё";
+
+/// Язык открытого текста для [`suggest_key`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Language {
+    /// Английский
+    En,
+    /// Русский
+    Ru,
+}
+
+impl Language {
+    fn letters_by_frequency(self) -> &'static str {
+        match self {
+            Language::En => ENGLISH_BY_FREQUENCY,
+            Language::Ru => RUSSIAN_BY_FREQUENCY,
+        }
+    }
+}
+
+/// Предполагаемый ключ простой подстановки по частотам: самая частая буква
+/// шифротекста считается заменой самой частой буквы языка, вторая — второй
+/// и так далее. Регистр шифротекста не учитывается, при равных частотах
+/// раньше идет меньший символ.
+///
+/// Возвращает пары (буква открытого текста, символ шифротекста) от самой
+/// частой к самой редкой, а после них — такие же пары заглавных букв.
+/// Буквы шифротекста сверх числа букв языка пар не получают. Ключи и
+/// значения не повторяются, поэтому результат — корректный алфавит, но
+/// почти наверняка неточный: это отправная точка для ручного подбора.
+pub fn suggest_key(ciphertext: &str, language: Language) -> Vec<(char, char)> {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in ciphertext.chars().filter(|c| c.is_alphabetic()) {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        *counts.entry(lower).or_default() += 1;
+    }
+
+    let mut ranked: Vec<(char, usize)> = counts.into_iter().collect();
+    ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let lower: Vec<(char, char)> = language
+        .letters_by_frequency()
+        .chars()
+        .zip(ranked.into_iter().map(|(c, _)| c))
+        .collect();
+    let upper: Vec<(char, char)> = lower
+        .iter()
+        .filter_map(|&(plain, cipher)| Some((single_uppercase(plain)?, single_uppercase(cipher)?)))
+        .collect();
+    lower.into_iter().chain(upper).collect()
+}
+
+/// Заглавная пара строчной буквы, если это один символ, отличный от нее
+fn single_uppercase(c: char) -> Option<char> {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) if u != c => Some(u),
+        _ => None,
+    }
+}
+
+/// Критерий хи-квадрат для частот латинских букв текста (без учета
+/// регистра) относительно частот английского языка. Чем меньше значение,
+/// тем больше текст похож на английский. Остальные символы не учитываются;
+/// `None`, если латинских букв в тексте нет.
+pub fn english_chi_squared(text: &str) -> Option<f64> {
+    let mut counts = [0usize; 26];
+    for c in text.chars().filter(char::is_ascii_alphabetic) {
+        counts[usize::from(c.to_ascii_lowercase() as u8 - b'a')] += 1;
+    }
+
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    Some(
+        counts
+            .iter()
+            .zip(ENGLISH_FREQUENCIES)
+            .map(|(&observed, frequency)| {
+                let expected = total as f64 * frequency / 100.0;
+                (observed as f64 - expected).powi(2) / expected
+            })
+            .sum(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format_entries, Cipher};
+
+    #[test]
+    fn suggested_key_is_a_valid_alphabet() {
+        let pairs = suggest_key("жжжж ззз ии к", Language::Ru);
+        let cipher = Cipher::parse(&format_entries(pairs)).unwrap();
+        assert_eq!(cipher.decrypt("жжжж ззз ии к"), "оооо еее аа и");
+    }
+
+    #[test]
+    fn english_text_scores_lower_than_shifted_text() {
+        let english = "It was the best of times, it was the worst of times";
+        let shifted: String = english
+            .chars()
+            .map(|c| match c {
+                'a'..='z' => char::from(b'a' + (c as u8 - b'a' + 7) % 26),
+                _ => c,
+            })
+            .collect();
+        assert!(english_chi_squared(english).unwrap() < english_chi_squared(&shifted).unwrap());
+        assert_eq!(english_chi_squared("123, абв!"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Место в шаблоне --output-template, куда подставляется номер файла
+pub const PLACEHOLDER: &str = "{n}";
+
+/// Записывает результат в файлы по шаблону, начиная новый файл после
+/// каждых `lines_per_file` строк. Файлы нумеруются с единицы и создаются
+/// по мере надобности, поэтому пустого последнего файла не бывает.
+pub struct SplitWriter {
+    template: String,
+    lines_per_file: usize,
+    current: Option<BufWriter<File>>,
+    /// Номер последнего созданного файла
+    index: usize,
+    /// Строк, записанных в текущий файл
+    lines: usize,
+}
+
+impl SplitWriter {
+    pub fn new(template: &str, lines_per_file: usize) -> Self {
+        SplitWriter {
+            template: template.to_string(),
+            lines_per_file,
+            current: None,
+            index: 0,
+            lines: 0,
+        }
+    }
+
+    fn file(&mut self) -> io::Result<&mut BufWriter<File>> {
+        if self.current.is_none() {
+            self.index += 1;
+            let path = self.template.replace(PLACEHOLDER, &self.index.to_string());
+            let file = File::create(&path)
+                .map_err(|err| io::Error::new(err.kind(), format!("Не удалось создать файл {}: {}", path, err)))?;
+            self.current = Some(BufWriter::new(file));
+        }
+        Ok(self.current.as_mut().expect("файл только что открыт"))
+    }
+}
+
+impl Write for SplitWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in buf.split_inclusive(|&byte| byte == b'\n') {
+            self.file()?.write_all(line)?;
+            if line.ends_with(b"\n") {
+                self.lines += 1;
+                if self.lines == self.lines_per_file {
+                    if let Some(mut file) = self.current.take() {
+                        file.flush()?;
+                    }
+                    self.lines = 0;
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
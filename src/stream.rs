@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::ControlFlow;
+use anyhow::{Result, Context};
+use cipher::text::{InputEncoding, OutputCase};
+use cipher::{chunked, Direction, BOM};
+
+use crate::modes::{self, Mode};
+use crate::profile::Profiler;
+use crate::split::SplitWriter;
+use crate::{append_separators, check_output_size, is_url, open_append, Alphabet, Args, OutputFormat};
+
+/// Опция, из-за которой текст нельзя обработать по частям: ей нужен
+/// весь текст сразу. `None`, если потоковая обработка возможна.
+pub fn blocker(args: &Args) -> Option<&'static str> {
+    match &args.input[..] {
+        [] => return Some("чтением текста не из --input"),
+        [input] if is_url(input) => return Some("чтением --input по адресу"),
+        [_] => {}
+        _ => return Some("несколькими файлами --input"),
+    }
+    if Mode::from_args(args) != Mode::Substitution {
+        return Some("режимами, кроме простой подстановки");
+    }
+
+    let blockers = [
+        (args.json_values, "--json-values"),
+        (args.field.is_some(), "--field"),
+        (args.preserve_indent, "--preserve-indent"),
+        (args.marker.is_some(), "--marker"),
+        (args.header, "--header"),
+        (args.collapse_whitespace, "--collapse-whitespace"),
+        (args.strip_whitespace, "--strip-whitespace"),
+        (args.space_as.is_some(), "--space-as"),
+        (args.insert_nulls.is_some(), "--insert-nulls"),
+        (args.strip_nulls, "--strip-nulls"),
+        (args.salt_output.is_some(), "--salt-output"),
+        (args.rounds != 1, "--rounds"),
+        (args.numeric, "--numeric"),
+        (args.input_radix.is_some(), "--input-radix"),
+        (args.output_case != OutputCase::Preserve, "--output-case"),
+        (args.interleave.is_some(), "--interleave"),
+        (args.deinterleave, "--deinterleave"),
+        (args.char_delimiter.is_some(), "--char-delimiter"),
+        (args.rle, "--rle"),
+        (args.normalize_eol_input, "--normalize-eol-input"),
+        (args.max_line_length.is_some(), "--max-line-length"),
+        (args.input_encoding != InputEncoding::Utf8, "--input-encoding"),
+        (args.lossy, "--lossy"),
+        (args.line_ending.is_some(), "--line-ending"),
+        (args.tabular, "--tabular"),
+        (args.reverse_input, "--reverse-input"),
+        (args.reverse_output, "--reverse-output"),
+        (args.shuffle_blocks.is_some(), "--shuffle-blocks"),
+        (args.measure_entropy, "--measure-entropy"),
+        (args.max_unmapped.is_some(), "--max-unmapped"),
+        (args.max_unmapped_pct.is_some(), "--max-unmapped-pct"),
+        (args.report_unmapped_to.is_some(), "--report-unmapped-to"),
+        (args.fail_on_passthrough, "--fail-on-passthrough"),
+        (args.no_passthrough_newlines, "--no-passthrough-newlines"),
+        (args.trace, "--trace"),
+        (args.per_char_stats, "--per-char-stats"),
+        (args.line_summary, "--line-summary"),
+        (args.stats_json, "--stats-json"),
+        (args.count_only_mapped_ratio, "--count-only-mapped-ratio"),
+        (args.bundle.is_some(), "--bundle"),
+        (args.limit_alphabet_to_input, "--limit-alphabet-to-input"),
+        (OutputFormat::from_args(args) != OutputFormat::Text, "--auto-format"),
+        (args.swap_case_unmapped, "--swap-case-unmapped"),
+    ];
+    blockers
+        .into_iter()
+        .find(|(active, _)| *active)
+        .map(|(_, option)| option)
+}
+
+/// Шифрует входной файл по частям размером --buffer-size. С --preview
+/// чтение прекращается, как только набрано нужное число символов.
+pub fn run(args: &Args, direction: Direction, profiler: &mut Profiler) -> Result<()> {
+    let input_file = args.input.first().context("Для --stream укажите --input")?;
+    let alphabet = profiler.stage("read", || Alphabet::read(args))?;
+    let cipher = profiler.stage("load", || alphabet.cipher(args))?;
+
+    let reader = File::open(input_file)
+        .with_context(|| format!("Не удалось открыть входной файл: {}", input_file))?;
+
+    // Что записать до и после результата: в режиме добавления это задают
+    // --append-separator и --no-trailing-separator, а на экране результат,
+    // как и при обработке в памяти, завершается переводом строки
+    let mut separators = ("", "");
+    let (writer, message): (Box<dyn Write>, Option<String>) = match (&args.output, args.split_lines, &args.output_template) {
+        (_, Some(lines), Some(template)) => (
+            Box::new(SplitWriter::new(template, lines)),
+            Some(format!("Результат записан в файлы по шаблону: {}", template)),
+        ),
+        (Some(output_file), _, _) if args.preview.is_none() => {
+            if args.append {
+                let file = open_append(output_file)?;
+                separators = append_separators(args, &file, output_file)?;
+                (Box::new(file), Some(format!("Результат добавлен в файл: {}", output_file)))
+            } else {
+                let file = File::create(output_file)
+                    .with_context(|| format!("Не удалось создать файл: {}", output_file))?;
+                (Box::new(file), Some(format!("Результат сохранен в файл: {}", output_file)))
+            }
+        }
+        _ => {
+            separators = ("", "\n");
+            (Box::new(io::stdout().lock()), None)
+        }
+    };
+    let (before, after) = separators;
+    let mut writer = BufWriter::with_capacity(args.buffer_size, writer);
+
+    let substitutable = modes::substitutable(args);
+    let mut first_chunk = true;
+    let mut remaining = args.preview;
+    let mut written: u64 = 0;
+
+    profiler
+        .stage("stream", || {
+            writer.write_all(before.as_bytes())?;
+            chunked::for_each_chunk(reader, args.buffer_size, |chunk| {
+                let chunk = match chunk.strip_prefix(BOM) {
+                    Some(stripped) if first_chunk && !args.keep_bom => stripped,
+                    _ => chunk,
+                };
+                first_chunk = false;
+
+                let mut result = match direction {
+                    Direction::Encrypt => cipher.encrypt_where(chunk, &substitutable),
+                    Direction::Decrypt => cipher.decrypt_where(chunk, &substitutable),
+                };
+
+                if let Some(limit) = remaining.as_mut() {
+                    if let Some((cut, _)) = result.char_indices().nth(*limit) {
+                        result.truncate(cut);
+                        result.push('…');
+                        writer.write_all(result.as_bytes())?;
+                        return Ok(ControlFlow::Break(()));
+                    }
+                    *limit -= result.chars().count();
+                }
+
+                // Часть, после которой результат превысил бы ограничение, не записывается
+                written += result.len() as u64;
+                check_output_size(args, written).map_err(io::Error::other)?;
+
+                writer.write_all(result.as_bytes())?;
+                Ok(ControlFlow::Continue(()))
+            })?;
+
+            writer.write_all(after.as_bytes())?;
+            writer.flush()
+        })
+        .with_context(|| format!("Ошибка при потоковой обработке файла: {}", input_file))?;
+
+    if let Some(message) = message {
+        println!("{}", message);
+    }
+
+    Ok(())
+}
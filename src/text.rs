@@ -0,0 +1,615 @@
+//! Подготовка текста до и после шифрования
+
+use std::collections::HashMap;
+
+use rand::seq::IndexedRandom;
+use rand::Rng;
+
+use thiserror::Error;
+
+/// Байты не являются текстом в указанной кодировке
+#[derive(Debug, Error)]
+#[error("Содержимое не является корректным текстом в кодировке {0}")]
+pub struct InvalidEncoding(pub &'static str);
+
+/// Заменяет каждую серию пробельных символов (пробелы, табуляции,
+/// переводы строк) одним пробелом
+pub fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_run = false;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_run {
+                result.push(' ');
+            }
+            in_run = true;
+        } else {
+            result.push(c);
+            in_run = false;
+        }
+    }
+
+    result
+}
+
+/// Число вхождений каждого символа текста
+pub fn char_frequencies(text: &str) -> HashMap<char, usize> {
+    let mut frequencies = HashMap::new();
+    for c in text.chars() {
+        *frequencies.entry(c).or_insert(0) += 1;
+    }
+    frequencies
+}
+
+/// Кодовая точка символа в записи Unicode: `U+0430` для `а`. Помогает
+/// различить невидимые и похожие друг на друга символы.
+pub fn codepoint(c: char) -> String {
+    format!("U+{:04X}", c as u32)
+}
+
+/// Энтропия Шеннона текста в битах на символ по частотам его символов.
+/// Для пустого текста равна нулю.
+///
+/// Простая подстановка только переименовывает символы и частоты не меняет,
+/// поэтому энтропия шифротекста совпадает с энтропией исходного текста.
+pub fn shannon_entropy(text: &str) -> f64 {
+    let frequencies = char_frequencies(text);
+    let total: usize = frequencies.values().sum();
+    frequencies
+        .values()
+        .map(|&count| {
+            let probability = count as f64 / total as f64;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// Меняет регистр буквы на противоположный. Символ без регистра и буква,
+/// замену которой нельзя обратить (`ß` → `SS`, `ſ` → `S` → `s`), остаются
+/// без изменений.
+pub fn swap_case(c: char) -> char {
+    match toggle_case(c) {
+        Some(swapped) if toggle_case(swapped) == Some(c) => swapped,
+        _ => c,
+    }
+}
+
+fn toggle_case(c: char) -> Option<char> {
+    let mut swapped: Vec<char> = if c.is_lowercase() {
+        c.to_uppercase().collect()
+    } else if c.is_uppercase() {
+        c.to_lowercase().collect()
+    } else {
+        return None;
+    };
+    match swapped.len() {
+        1 => swapped.pop(),
+        _ => None,
+    }
+}
+
+/// Переставляет символы текста в обратном порядке. Работает с отдельными
+/// символами Unicode, поэтому комбинируемые знаки отделяются от своих букв
+/// (до повторного обращения).
+pub fn reverse(text: &str) -> String {
+    text.chars().rev().collect()
+}
+
+/// Кодировка входных файлов
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1: каждый байт — символ с тем же номером
+    Latin1,
+    /// Windows-1251, кириллица Windows
+    Cp1251,
+    Utf16le,
+    Utf16be,
+}
+
+impl InputEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            InputEncoding::Utf8 => "UTF-8",
+            InputEncoding::Latin1 => "ISO-8859-1",
+            InputEncoding::Cp1251 => "Windows-1251",
+            InputEncoding::Utf16le => "UTF-16LE",
+            InputEncoding::Utf16be => "UTF-16BE",
+        }
+    }
+
+    /// Декодирует байты в строку. Недопустимые последовательности не
+    /// заменяются символом-заменителем, а дают ошибку. Метка порядка
+    /// байтов не удаляется и остается в начале строки как `BOM`.
+    pub fn decode(self, bytes: &[u8]) -> Result<String, InvalidEncoding> {
+        let invalid = || InvalidEncoding(self.name());
+        match self {
+            InputEncoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|_| invalid()),
+            InputEncoding::Latin1 => Ok(bytes.iter().map(|&byte| char::from(byte)).collect()),
+            InputEncoding::Cp1251 => decode_with(encoding_rs::WINDOWS_1251, bytes).ok_or_else(invalid),
+            InputEncoding::Utf16le => decode_with(encoding_rs::UTF_16LE, bytes).ok_or_else(invalid),
+            InputEncoding::Utf16be => decode_with(encoding_rs::UTF_16BE, bytes).ok_or_else(invalid),
+        }
+    }
+
+    /// Декодирует байты в строку, заменяя каждую недопустимую
+    /// последовательность символом-заменителем U+FFFD, как
+    /// `String::from_utf8_lossy`. Возвращает строку и число замененных байтов.
+    pub fn decode_lossy(self, bytes: &[u8]) -> (String, usize) {
+        match self {
+            InputEncoding::Utf8 => {
+                let replaced = bytes.utf8_chunks().map(|chunk| chunk.invalid().len()).sum();
+                (String::from_utf8_lossy(bytes).into_owned(), replaced)
+            }
+            InputEncoding::Latin1 => (bytes.iter().map(|&byte| char::from(byte)).collect(), 0),
+            InputEncoding::Cp1251 => decode_lossy_with(encoding_rs::WINDOWS_1251, bytes),
+            InputEncoding::Utf16le => decode_lossy_with(encoding_rs::UTF_16LE, bytes),
+            InputEncoding::Utf16be => decode_lossy_with(encoding_rs::UTF_16BE, bytes),
+        }
+    }
+}
+
+fn decode_lossy_with(encoding: &'static encoding_rs::Encoding, mut bytes: &[u8]) -> (String, usize) {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let capacity = |decoder: &encoding_rs::Decoder, length: usize| {
+        decoder
+            .max_utf8_buffer_length_without_replacement(length)
+            .unwrap_or(length * 3 + 16)
+    };
+    let mut text = String::with_capacity(capacity(&decoder, bytes.len()));
+    let mut replaced = 0;
+
+    loop {
+        let (result, read) = decoder.decode_to_string_without_replacement(bytes, &mut text, true);
+        bytes = &bytes[read..];
+        match result {
+            encoding_rs::DecoderResult::InputEmpty => break,
+            encoding_rs::DecoderResult::OutputFull => text.reserve(capacity(&decoder, bytes.len())),
+            encoding_rs::DecoderResult::Malformed(length, _) => {
+                replaced += usize::from(length);
+                text.push(char::REPLACEMENT_CHARACTER);
+            }
+        }
+    }
+
+    (text, replaced)
+}
+
+fn decode_with(encoding: &'static encoding_rs::Encoding, bytes: &[u8]) -> Option<String> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+}
+
+/// Приводит переводы строк CRLF и одиночные CR к LF
+pub fn normalize_eol(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Первая строка длиннее `max` символов: ее номер (с единицы) и длина.
+/// Перевод строки (`\n` или `\r\n`) в длину не входит.
+pub fn find_long_line(text: &str, max: usize) -> Option<(usize, usize)> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).chars().count())
+        .enumerate()
+        .find(|&(_, length)| length > max)
+        .map(|(index, length)| (index + 1, length))
+}
+
+/// Обрезает строки длиннее `max` символов до `max`, сохраняя переводы
+/// строк. Возвращает результат и число обрезанных строк.
+pub fn truncate_lines(text: &str, max: usize) -> (String, usize) {
+    let mut result = String::with_capacity(text.len());
+    let mut truncated = 0;
+
+    for (number, line) in text.split('\n').enumerate() {
+        if number > 0 {
+            result.push('\n');
+        }
+        let (line, ending) = match line.strip_suffix('\r') {
+            Some(stripped) => (stripped, "\r"),
+            None => (line, ""),
+        };
+
+        match line.char_indices().nth(max) {
+            Some((cut, _)) => {
+                result.push_str(&line[..cut]);
+                truncated += 1;
+            }
+            None => result.push_str(line),
+        }
+        result.push_str(ending);
+    }
+
+    (result, truncated)
+}
+
+/// Перевод строки, которым записывается результат
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LineEnding {
+    /// \n (Unix)
+    Lf,
+    /// \r\n (Windows)
+    Crlf,
+    /// \r (классическая Mac OS)
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+/// Заменяет каждый LF на `ending`
+pub fn convert_line_endings(text: &str, ending: LineEnding) -> String {
+    match ending {
+        LineEnding::Lf => text.to_string(),
+        _ => text.replace('\n', ending.as_str()),
+    }
+}
+
+/// Чередует символы текста и символы-приманки: один символ текста, один
+/// символ `decoy`. Если приманка короче текста, она повторяется по кругу,
+/// если длиннее — лишнее отбрасывается, поэтому результат ровно вдвое длиннее
+/// текста. Пустая приманка оставляет текст без изменений.
+pub fn interleave(text: &str, decoy: &[char]) -> String {
+    if decoy.is_empty() {
+        return text.to_string();
+    }
+
+    text.chars()
+        .zip(decoy.iter().cycle())
+        .flat_map(|(c, &d)| [c, d])
+        .collect()
+}
+
+/// Обратно к [`interleave`]: оставляет символы на четных позициях
+/// (считая с нуля) и отбрасывает приманку между ними
+pub fn deinterleave(text: &str) -> String {
+    text.chars().step_by(2).collect()
+}
+
+/// Ставит строку `delimiter` между соседними символами текста
+pub fn join_chars(text: &str, delimiter: &str) -> String {
+    let mut result = String::with_capacity(text.len() * (delimiter.len() + 1));
+    for (index, c) in text.chars().enumerate() {
+        if index > 0 {
+            result.push_str(delimiter);
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Обратно к [`join_chars`]: убирает разделитель между символами.
+/// Если текст записан не в таком виде, возвращает номер символа (с
+/// единицы), после которого не нашлось разделителя.
+pub fn split_chars(text: &str, delimiter: &str) -> Result<String, usize> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+        if rest.is_empty() {
+            break;
+        }
+        match rest.strip_prefix(delimiter) {
+            Some(stripped) => rest = stripped,
+            None => return Err(result.chars().count()),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Регистр, к которому приводится результат
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputCase {
+    /// Заглавные буквы
+    Upper,
+    /// Строчные буквы
+    Lower,
+    /// Регистр не меняется
+    #[default]
+    Preserve,
+}
+
+/// Приводит текст к заданному регистру по правилам Unicode
+pub fn change_case(text: &str, case: OutputCase) -> String {
+    match case {
+        OutputCase::Upper => text.to_uppercase(),
+        OutputCase::Lower => text.to_lowercase(),
+        OutputCase::Preserve => text.to_string(),
+    }
+}
+
+/// Удаляет все пробельные символы
+pub fn strip_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// После каждого символа с вероятностью `rate` вставляет случайный
+/// «пустой» символ из `nulls`
+pub fn insert_nulls<R: Rng + ?Sized>(text: &str, nulls: &[char], rate: f64, rng: &mut R) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        result.push(c);
+        if rng.random_bool(rate) {
+            if let Some(&null) = nulls.choose(rng) {
+                result.push(null);
+            }
+        }
+    }
+
+    result
+}
+
+/// Удаляет все символы из набора `chars`
+pub fn remove_chars(text: &str, chars: &[char]) -> String {
+    text.chars().filter(|c| !chars.contains(c)).collect()
+}
+
+/// Ставит строки двух текстов рядом: каждая строка `left` дополняется
+/// пробелами до длины самой длинной из них, затем идут `separator` и строка
+/// `right` с тем же номером. Если строк в текстах разное число, недостающие
+/// считаются пустыми. Перевод строки в конце `left` сохраняется в
+/// результате, а не превращается в лишнюю пустую строку.
+pub fn side_by_side(left: &str, right: &str, separator: &str) -> String {
+    let (left, trailing_newline) = records(left);
+    let (right, _) = records(right);
+    let width = left.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+
+    let mut result = (0..left.len().max(right.len()))
+        .map(|index| {
+            let left_line = left.get(index).copied().unwrap_or_default();
+            let right_line = right.get(index).copied().unwrap_or_default();
+            format!("{:width$}{}{}", left_line, separator, right_line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if trailing_newline {
+        result.push('\n');
+    }
+    result
+}
+
+/// Сравнивает текст до и после преобразования построчно: возвращает число
+/// строк и число строк, которые изменились. `None`, если число строк
+/// разное (например, перевод строки был заменен) и строки нельзя сопоставить.
+pub fn changed_lines(before: &str, after: &str) -> Option<(usize, usize)> {
+    let (before, _) = records(before);
+    let (after, _) = records(after);
+    if before.len() != after.len() {
+        return None;
+    }
+    let changed = before.iter().zip(&after).filter(|(old, new)| old != new).count();
+    Some((before.len(), changed))
+}
+
+/// Первое различие двух текстов: номер символа, строка и столбец (все с
+/// единицы) и символы обоих текстов в этом месте (`None` — текст кончился).
+/// `None`, если тексты совпадают.
+pub fn first_difference(expected: &str, actual: &str) -> Option<Difference> {
+    let mut expected_chars = expected.chars();
+    let mut actual_chars = actual.chars();
+    let (mut line, mut column) = (1, 1);
+
+    for position in 1.. {
+        let (expected, actual) = (expected_chars.next(), actual_chars.next());
+        if expected != actual {
+            return Some(Difference { position, line, column, expected, actual });
+        }
+        match expected {
+            None => return None,
+            Some('\n') => (line, column) = (line + 1, 1),
+            Some(_) => column += 1,
+        }
+    }
+    None
+}
+
+/// Место, где тексты расходятся, которое возвращает [`first_difference`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difference {
+    pub position: usize,
+    pub line: usize,
+    pub column: usize,
+    pub expected: Option<char>,
+    pub actual: Option<char>,
+}
+
+/// Делит текст на строки-записи, как `str::lines`, и сообщает, завершался
+/// ли текст переводом строки. Пустая строка после последнего перевода
+/// строки записью не считается, поэтому текст с переводом строки в конце и
+/// без него дают одни и те же записи.
+pub fn records(text: &str) -> (Vec<&str>, bool) {
+    (text.lines().collect(), text.ends_with('\n'))
+}
+
+/// Применяет `f` к каждой строке текста без ведущих пробельных символов
+/// (отступа). Отступ и переводы строк (`\n` и `\r\n`) переносятся в
+/// результат без изменений.
+pub fn map_indented<E>(
+    text: &str,
+    f: &mut impl FnMut(&str) -> Result<String, E>,
+) -> Result<String, E> {
+    let mut result = String::with_capacity(text.len());
+
+    for (number, line) in text.split('\n').enumerate() {
+        if number > 0 {
+            result.push('\n');
+        }
+        let (line, ending) = match line.strip_suffix('\r') {
+            Some(stripped) => (stripped, "\r"),
+            None => (line, ""),
+        };
+
+        let content = line.trim_start();
+        result.push_str(&line[..line.len() - content.len()]);
+        if !content.is_empty() {
+            result.push_str(&f(content)?);
+        }
+        result.push_str(ending);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn collapses_runs_of_mixed_whitespace() {
+        assert_eq!(collapse_whitespace("а \t\n б\r\n\r\nв  г"), "а б в г");
+        assert_eq!(collapse_whitespace("\n\tа\u{a0}б \n"), " а б ");
+    }
+
+    #[test]
+    fn inserted_nulls_are_removed_again() {
+        let text = "баг гв ба".repeat(20);
+        let nulls = ['x', 'y', 'z'];
+        let padded = insert_nulls(&text, &nulls, 0.5, &mut ChaCha20Rng::seed_from_u64(5));
+        assert!(padded.chars().count() > text.chars().count());
+        assert_eq!(padded, insert_nulls(&text, &nulls, 0.5, &mut ChaCha20Rng::seed_from_u64(5)));
+        assert_eq!(remove_chars(&padded, &nulls), text);
+    }
+
+    #[test]
+    fn side_by_side_pads_to_longest_left_line() {
+        assert_eq!(side_by_side("а\nабв\n\nаб\n", "б\nбаг\n\nба\n", " | "), "а   | б\nабв | баг\n    | \nаб  | ба\n");
+        assert_eq!(side_by_side("аб\nв", "ба", "\t"), "аб\tба\nв \t");
+    }
+
+    #[test]
+    fn normalize_eol_converts_crlf_and_cr() {
+        assert_eq!(normalize_eol("а\r\nб\rв\n\r\n"), "а\nб\nв\n\n");
+    }
+
+    #[test]
+    fn interleave_repeats_or_truncates_decoy() {
+        assert_eq!(interleave("абв", &['x', 'y']), "аxбyвx");
+        assert_eq!(interleave("аб", &['x', 'y', 'z']), "аxбy");
+        assert_eq!(deinterleave(&interleave("абв", &['x'])), "абв");
+    }
+
+    #[test]
+    fn change_case_forces_one_case() {
+        assert_eq!(change_case("бА гВ!", OutputCase::Upper), "БА ГВ!");
+        assert_eq!(change_case("бА гВ!", OutputCase::Lower), "ба гв!");
+        assert_eq!(change_case("бА гВ!", OutputCase::Preserve), "бА гВ!");
+    }
+
+    #[test]
+    fn codepoint_is_padded_to_four_digits() {
+        assert_eq!(codepoint('а'), "U+0430");
+        assert_eq!(codepoint('a'), "U+0061");
+        assert_eq!(codepoint('\u{a0}'), "U+00A0");
+        assert_eq!(codepoint('😀'), "U+1F600");
+    }
+
+    #[test]
+    fn entropy_of_known_distributions() {
+        assert_eq!(shannon_entropy(""), 0.0);
+        assert_eq!(shannon_entropy("аааа"), 0.0);
+        assert_eq!(shannon_entropy("абвг"), 2.0);
+        // p = 1/2, 1/4, 1/4: 0.5·1 + 2·0.25·2 = 1.5
+        assert_eq!(shannon_entropy("аабв"), 1.5);
+    }
+
+    #[test]
+    fn decodes_each_input_encoding() {
+        assert_eq!(InputEncoding::Cp1251.decode(&[0xe0, 0xe1, 0x20, 0xc0, 0xdf]).unwrap(), "аб АЯ");
+        assert_eq!(InputEncoding::Latin1.decode(&[0x63, 0x61, 0x66, 0xe9]).unwrap(), "café");
+        assert_eq!(InputEncoding::Utf16le.decode(&[0x30, 0x04, 0x31, 0x04]).unwrap(), "аб");
+        assert_eq!(InputEncoding::Utf16be.decode(&[0x04, 0x30, 0x04, 0x31]).unwrap(), "аб");
+        assert!(InputEncoding::Utf8.decode(&[0xe0, 0xe1]).is_err());
+    }
+
+    #[test]
+    fn swap_case_is_reversible_or_identity() {
+        assert_eq!(swap_case('a'), 'A');
+        assert_eq!(swap_case('Ж'), 'ж');
+        assert_eq!(swap_case('!'), '!');
+        assert_eq!(swap_case('ß'), 'ß');
+        assert_eq!(swap_case('ſ'), 'ſ');
+    }
+
+    #[test]
+    fn records_ignore_final_newline() {
+        assert_eq!(records("а\nб\n"), (vec!["а", "б"], true));
+        assert_eq!(records("а\nб"), (vec!["а", "б"], false));
+        assert_eq!(side_by_side("а\n", "б\n", " "), "а б\n");
+        assert_eq!(side_by_side("а", "б", " "), "а б");
+    }
+
+    #[test]
+    fn lossy_decoding_counts_replaced_bytes() {
+        let bytes = [0xd0, 0xb0, 0xff, 0xfe, 0xd0, 0xb1];
+        assert!(InputEncoding::Utf8.decode(&bytes).is_err());
+        assert_eq!(InputEncoding::Utf8.decode_lossy(&bytes), ("а\u{fffd}\u{fffd}б".to_string(), 2));
+        assert_eq!(InputEncoding::Utf8.decode_lossy("аб".as_bytes()), ("аб".to_string(), 0));
+        assert_eq!(InputEncoding::Utf16le.decode_lossy(&[0x30, 0x04, 0x31]), ("а\u{fffd}".to_string(), 1));
+    }
+
+    #[test]
+    fn changed_lines_counts_differing_lines() {
+        assert_eq!(changed_lines("аб\nxy\nв\n", "ба\nxy\nг\n"), Some((3, 2)));
+        assert_eq!(changed_lines("xy\n\n", "xy\n\n"), Some((2, 0)));
+        assert_eq!(changed_lines("а\nб", "а б"), None);
+    }
+
+    #[test]
+    fn first_difference_reports_line_and_column() {
+        assert_eq!(first_difference("аб\nвг", "аб\nвг"), None);
+        assert_eq!(
+            first_difference("аб\nвг", "аб\nвд"),
+            Some(Difference { position: 5, line: 2, column: 2, expected: Some('г'), actual: Some('д') })
+        );
+        assert_eq!(
+            first_difference("аб", "абв"),
+            Some(Difference { position: 3, line: 1, column: 3, expected: None, actual: Some('в') })
+        );
+    }
+
+    #[test]
+    fn map_indented_keeps_indentation_and_line_endings() {
+        let mut seen = Vec::new();
+        let result = map_indented("  аб\r\n\t\tв г\n\n   \nд", &mut |line: &str| {
+            seen.push(line.to_string());
+            Ok::<_, ()>(line.to_uppercase())
+        });
+        assert_eq!(result, Ok("  АБ\r\n\t\tВ Г\n\n   \nД".to_string()));
+        assert_eq!(seen, ["аб", "в г", "д"]);
+    }
+
+    #[test]
+    fn split_chars_reverses_join_chars() {
+        for text in ["", "а", "аб в", "а--б"] {
+            assert_eq!(split_chars(&join_chars(text, "-|"), "-|"), Ok(text.to_string()));
+        }
+        assert_eq!(join_chars("абв", "-"), "а-б-в");
+        assert_eq!(split_chars("а-бв", "-"), Err(2));
+    }
+
+    #[test]
+    fn long_lines_are_found_or_truncated() {
+        let text = "абв\r\nабвгде\nаб\nабвгдеж";
+        assert_eq!(find_long_line(text, 5), Some((2, 6)));
+        assert_eq!(find_long_line(text, 7), None);
+        assert_eq!(find_long_line("абв\r\n", 3), None);
+        assert_eq!(truncate_lines(text, 4), ("абв\r\nабвг\nаб\nабвг".to_string(), 2));
+        assert_eq!(truncate_lines(text, 7), (text.to_string(), 0));
+    }
+}
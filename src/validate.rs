@@ -0,0 +1,120 @@
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use anyhow::{Context, Result};
+
+use crate::modes::Mode;
+use crate::{is_url, read_combined_stdin, Args};
+
+/// Проверяет алфавит, входные данные и возможность записи результата,
+/// ничего не записывая. Печатает итог по каждой проверке и завершается
+/// с ошибкой, если хотя бы одна из них не пройдена.
+pub fn run(args: &Args) -> Result<()> {
+    let mode = Mode::from_args(args);
+
+    let checks: [(&str, Result<()>); 3] = [
+        ("Алфавит", check_alphabet(args, mode)),
+        ("Входные данные", check_input(args)),
+        ("Вывод", check_output(args)),
+    ];
+
+    let mut failed = 0;
+    for (name, result) in &checks {
+        match result {
+            Ok(()) => println!("{}: ОК", name),
+            Err(err) => {
+                failed += 1;
+                println!("{}: ОШИБКА: {:#}", name, err);
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("Проверка не пройдена: ошибок {} из {}", failed, checks.len());
+    }
+    println!("Все проверки пройдены");
+    Ok(())
+}
+
+/// Разбирает алфавит так же, как перед обработкой, включая проверку
+/// повторяющихся ключей и значений, благодаря которой шифр обратим
+fn check_alphabet(args: &Args, mode: Mode) -> Result<()> {
+    let alphabets = if args.stdin_combined {
+        vec![read_combined_stdin()?.0]
+    } else {
+        mode.read_alphabets(args)?
+    };
+    mode.load(args, &alphabets)?;
+    Ok(())
+}
+
+fn check_input(args: &Args) -> Result<()> {
+    if let Some(pattern) = &args.input_glob {
+        let matched = glob::glob(pattern)
+            .with_context(|| format!("Некорректный шаблон: {}", pattern))?
+            .filter_map(Result::ok)
+            .any(|path| path.is_file());
+        if !matched {
+            anyhow::bail!("Под шаблон {} не подходит ни один файл", pattern);
+        }
+        return Ok(());
+    }
+
+    // Доступность адресов проверяется только при загрузке
+    for input_file in args.input.iter().filter(|input| !is_url(input)) {
+        File::open(input_file)
+            .with_context(|| format!("Не удалось открыть входной файл: {}", input_file))?;
+    }
+
+    if args.input.is_empty() && args.text.is_empty() && !args.stdin_combined {
+        anyhow::bail!("Не указан текст для обработки. Используйте текстовый аргумент или --input");
+    }
+    Ok(())
+}
+
+fn check_output(args: &Args) -> Result<()> {
+    let Some(output) = &args.output else {
+        return Ok(());
+    };
+    let path = Path::new(output);
+
+    if args.input_glob.is_some() {
+        if path.exists() && !path.is_dir() {
+            anyhow::bail!("{} существует и не является каталогом", output);
+        }
+        return check_writable_dir(path);
+    }
+
+    if path.exists() {
+        // Открытие на добавление ничего не меняет в файле, но требует права на запись
+        OpenOptions::new()
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Нет доступа на запись к файлу: {}", output))?;
+        return Ok(());
+    }
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !parent.is_dir() {
+        anyhow::bail!("Каталог для файла {} не существует", output);
+    }
+    check_writable_dir(parent)
+}
+
+/// Проверяет права на запись в каталог (или в ближайший существующий
+/// каталог над ним, где будут созданы недостающие) пробным созданием
+/// временного файла, который сразу удаляется. Атрибуты каталога для этого
+/// не годятся: они не учитывают владельца, группу и права администратора.
+fn check_writable_dir(path: &Path) -> Result<()> {
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.as_os_str().is_empty() || ancestor.exists())
+        .map(|ancestor| if ancestor.as_os_str().is_empty() { Path::new(".") } else { ancestor })
+        .unwrap_or(Path::new("."));
+
+    tempfile::NamedTempFile::new_in(existing)
+        .with_context(|| format!("Нет доступа на запись к каталогу: {}", existing.display()))?;
+    Ok(())
+}
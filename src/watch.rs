@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{Event, RecursiveMode, Watcher};
+
+use cipher::include;
+
+use crate::modes::Mode;
+use crate::{is_url, Args};
+
+/// Время тишины, после которого серия событий считается завершенной
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+pub fn run(args: &Args) -> Result<()> {
+    let targets = watched_files(args)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .context("Не удалось запустить наблюдение за файлами")?;
+    let mut directories = HashSet::new();
+    watch_directories(&mut watcher, &mut directories, &targets)?;
+
+    rerun(args);
+
+    // Набор файлов меняется вместе с директивами @include, поэтому
+    // фильтр событий читает его заново для каждого события
+    let targets = Arc::new(Mutex::new(targets));
+    let (relevant_tx, relevant_rx) = mpsc::channel();
+    let filter_targets = Arc::clone(&targets);
+    std::thread::spawn(move || {
+        for event in rx {
+            let touches_target = match event {
+                // События доступа порождает само преобразование, читая файлы
+                Ok(Event { kind, paths, .. }) => {
+                    let targets = filter_targets.lock().unwrap_or_else(PoisonError::into_inner);
+                    !kind.is_access() && paths.iter().any(|path| targets.contains(path))
+                }
+                Err(err) => {
+                    eprintln!("Ошибка наблюдения: {}", err);
+                    false
+                }
+            };
+            if touches_target && relevant_tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    while wait_for_change(&relevant_rx, DEBOUNCE) {
+        match watched_files(args) {
+            Ok(updated) => {
+                if let Err(err) = watch_directories(&mut watcher, &mut directories, &updated) {
+                    eprintln!("Ошибка: {:#}", err);
+                }
+                *targets.lock().unwrap_or_else(PoisonError::into_inner) = updated;
+            }
+            Err(err) => eprintln!("Ошибка: {:#}", err),
+        }
+        rerun(args);
+    }
+
+    Ok(())
+}
+
+/// Файлы, изменение которых перезапускает преобразование: алфавиты режима,
+/// файлы из их директив `@include` и входные файлы (кроме адресов)
+fn watched_files(args: &Args) -> Result<HashSet<PathBuf>> {
+    let mut targets = HashSet::new();
+    for alphabet in Mode::from_args(args).alphabet_files(args)? {
+        let alphabet = absolute(alphabet)?;
+        for included in include::included_files(&alphabet) {
+            // Каталог включенного файла может еще не существовать
+            if let Some(included) = included.to_str().and_then(|included| absolute(included).ok()) {
+                targets.insert(included);
+            }
+        }
+        targets.insert(alphabet);
+    }
+    for input_file in args.input.iter().filter(|input| !is_url(input)) {
+        targets.insert(absolute(input_file)?);
+    }
+
+    if targets.is_empty() {
+        anyhow::bail!("--watch не за чем наблюдать: укажите файл алфавита или входной файл (--input)");
+    }
+    Ok(targets)
+}
+
+/// Наблюдает за каталогами файлов `targets`, к которым наблюдение еще не
+/// подключено. Наблюдаем за каталогами, а не за самими файлами: многие
+/// редакторы сохраняют файл через переименование, и наблюдение за файлом теряется.
+fn watch_directories(watcher: &mut impl Watcher, watched: &mut HashSet<PathBuf>, targets: &HashSet<PathBuf>) -> Result<()> {
+    for directory in targets.iter().filter_map(|target| target.parent()) {
+        if watched.contains(directory) {
+            continue;
+        }
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Не удалось наблюдать за каталогом: {}", directory.display()))?;
+        watched.insert(directory.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Выполняет преобразование, не прерывая наблюдение при ошибке
+fn rerun(args: &Args) {
+    if let Err(err) = crate::run(args) {
+        eprintln!("Ошибка: {:#}", err);
+    }
+}
+
+/// Ждет первое событие, затем поглощает все последующие, пока не наступит
+/// пауза длиной `quiet`. Возвращает `false`, если источник событий закрыт.
+fn wait_for_change(events: &Receiver<()>, quiet: Duration) -> bool {
+    if events.recv().is_err() {
+        return false;
+    }
+
+    loop {
+        match events.recv_timeout(quiet) {
+            Ok(()) => continue,
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return true,
+        }
+    }
+}
+
+fn absolute(filename: &str) -> Result<PathBuf> {
+    let path = Path::new(filename);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let directory = directory
+        .canonicalize()
+        .with_context(|| format!("Не удалось найти каталог файла: {}", filename))?;
+    let name = path
+        .file_name()
+        .with_context(|| format!("Некорректное имя файла: {}", filename))?;
+    Ok(directory.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    use clap::Parser;
+
+    #[test]
+    fn burst_of_events_triggers_one_rerun() {
+        let (tx, rx) = mpsc::channel();
+        let sender = thread::spawn(move || {
+            for _ in 0..5 {
+                tx.send(()).unwrap();
+                thread::sleep(Duration::from_millis(10));
+            }
+            thread::sleep(Duration::from_millis(200));
+            tx.send(()).unwrap();
+        });
+
+        let quiet = Duration::from_millis(100);
+        assert!(wait_for_change(&rx, quiet), "первая серия");
+        assert!(rx.try_recv().is_err(), "серия должна быть поглощена целиком");
+        assert!(wait_for_change(&rx, quiet), "событие после паузы — новая серия");
+        sender.join().unwrap();
+        assert!(!wait_for_change(&rx, quiet), "источник закрыт");
+    }
+
+    #[test]
+    fn watched_files_follow_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        std::fs::write(dir.join("common.txt"), "а = б\n").unwrap();
+        std::fs::write(dir.join("key.txt"), "@include common.txt\nв = г\n").unwrap();
+        let key = dir.join("key.txt");
+
+        let args = Args::parse_from(["cipher", "-a", key.to_str().unwrap(), "-e", "а"]);
+        let targets = watched_files(&args).unwrap();
+        assert_eq!(targets, HashSet::from([key.clone(), dir.join("common.txt")]));
+    }
+
+    #[test]
+    fn nothing_to_watch_is_an_error() {
+        let args = Args::parse_from(["cipher", "--map", "а=б", "-e", "а"]);
+        let message = format!("{:#}", watched_files(&args).unwrap_err());
+        assert!(message.contains("не за чем наблюдать"), "{}", message);
+    }
+}
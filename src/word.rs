@@ -0,0 +1,148 @@
+//! Подстановка целых слов по словарю
+//!
+//! Словарь записывается в формате «ключ = значение»: `привет = здравствуй`.
+//! Словом считается непрерывная последовательность букв и цифр; пробелы,
+//! знаки препинания и слова, которых нет в словаре, не меняются.
+
+use std::collections::HashMap;
+
+use crate::{parse_entries, read_alphabet_file, CipherError};
+
+#[derive(Debug)]
+pub struct WordCipher {
+    encrypt_map: HashMap<String, String>,
+    decrypt_map: HashMap<String, String>,
+    preserve_case: bool,
+}
+
+impl WordCipher {
+    pub fn from_file(filename: &str, preserve_case: bool) -> Result<Self, CipherError> {
+        Self::parse(&read_alphabet_file(filename)?, preserve_case)
+    }
+
+    /// Разбирает словарь. Если `preserve_case`, слова сравниваются без учета
+    /// регистра, а регистр исходного слова переносится на замену: `Привет`
+    /// становится `Здравствуй`, `ПРИВЕТ` — `ЗДРАВСТВУЙ`.
+    pub fn parse(content: &str, preserve_case: bool) -> Result<Self, CipherError> {
+        let mut encrypt_map = HashMap::new();
+        let mut decrypt_map = HashMap::new();
+
+        for entry in parse_entries(content)? {
+            let (original, substituted) = if preserve_case {
+                (entry.key.to_lowercase(), entry.value.to_lowercase())
+            } else {
+                (entry.key.to_string(), entry.value.to_string())
+            };
+
+            if encrypt_map.contains_key(&original) {
+                return Err(CipherError::DuplicateWord {
+                    line: entry.line,
+                    word: original,
+                });
+            }
+            if decrypt_map.contains_key(&substituted) {
+                return Err(CipherError::DuplicateTranslation {
+                    line: entry.line,
+                    word: substituted,
+                });
+            }
+
+            encrypt_map.insert(original.clone(), substituted.clone());
+            decrypt_map.insert(substituted, original);
+        }
+
+        Ok(WordCipher {
+            encrypt_map,
+            decrypt_map,
+            preserve_case,
+        })
+    }
+
+    pub fn encrypt(&self, text: &str) -> String {
+        self.substitute(text, &self.encrypt_map)
+    }
+
+    pub fn decrypt(&self, text: &str) -> String {
+        self.substitute(text, &self.decrypt_map)
+    }
+
+    fn substitute(&self, text: &str, map: &HashMap<String, String>) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut word_start = None;
+
+        for (index, c) in text.char_indices() {
+            if c.is_alphanumeric() {
+                word_start.get_or_insert(index);
+                continue;
+            }
+            if let Some(start) = word_start.take() {
+                result.push_str(&self.substitute_word(&text[start..index], map));
+            }
+            result.push(c);
+        }
+        if let Some(start) = word_start {
+            result.push_str(&self.substitute_word(&text[start..], map));
+        }
+
+        result
+    }
+
+    fn substitute_word(&self, word: &str, map: &HashMap<String, String>) -> String {
+        if !self.preserve_case {
+            return map.get(word).cloned().unwrap_or_else(|| word.to_string());
+        }
+
+        match map.get(&word.to_lowercase()) {
+            Some(substituted) => apply_case(word, substituted),
+            None => word.to_string(),
+        }
+    }
+}
+
+/// Переносит регистр слова `source` на `target`: слово целиком из
+/// заглавных букв дает заглавные, заглавная первая буква — заглавную
+/// первую букву
+fn apply_case(source: &str, target: &str) -> String {
+    let mut letters = source.chars().filter(|c| c.is_alphabetic());
+    let first_upper = letters.next().is_some_and(char::is_uppercase);
+    let rest: Vec<char> = letters.collect();
+
+    if first_upper && !rest.is_empty() && rest.iter().all(|c| c.is_uppercase()) {
+        target.to_uppercase()
+    } else if first_upper {
+        let mut chars = target.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    } else {
+        target.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DICTIONARY: &str = "hello = bonjour\nworld = monde\n";
+
+    #[test]
+    fn words_next_to_punctuation_are_substituted() {
+        let cipher = WordCipher::parse(DICTIONARY, false).unwrap();
+        assert_eq!(cipher.encrypt("hello, world! (hello)world..."), "bonjour, monde! (bonjour)monde...");
+        assert_eq!(cipher.decrypt("«bonjour»—monde?"), "«hello»—world?");
+    }
+
+    #[test]
+    fn unmapped_and_partial_words_pass_through() {
+        let cipher = WordCipher::parse(DICTIONARY, false).unwrap();
+        assert_eq!(cipher.encrypt("hello helloworld  worlds\tHello"), "bonjour helloworld  worlds\tHello");
+    }
+
+    #[test]
+    fn preserve_case_follows_source_word() {
+        let cipher = WordCipher::parse(DICTIONARY, true).unwrap();
+        assert_eq!(cipher.encrypt("Hello, WORLD; hello."), "Bonjour, MONDE; bonjour.");
+        assert_eq!(cipher.decrypt("Bonjour, MONDE; bonjour."), "Hello, WORLD; hello.");
+    }
+}
@@ -0,0 +1,1657 @@
+//! Проверки утилиты целиком: каждый тест запускает собранный исполняемый
+//! файл с файлами алфавитов во временном каталоге
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Алфавит из двух пар взаимных замен
+const KEY: &str = "а = б\nб = а\nв = г\nг = в\n";
+
+fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, content).unwrap();
+    path
+}
+
+fn path(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+fn cipher(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cipher")).args(args).output().unwrap()
+}
+
+/// Результат успешного запуска без перевода строки, которым вывод завершается
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "cipher завершился с ошибкой: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    stdout.strip_suffix('\n').unwrap_or(&stdout).to_string()
+}
+
+/// Сообщение об ошибке запуска, который должен завершиться неудачей
+fn stderr(output: Output) -> String {
+    assert!(!output.status.success(), "cipher должен был завершиться с ошибкой");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn space_as_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let plain = "аб вг  а";
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "--space-as", "_", "-e", plain]));
+    assert_eq!(encrypted, "ба_гв__б");
+
+    let decrypted = stdout(cipher(&["-a", path(&key), "--space-as", "_", "-d", &encrypted]));
+    assert_eq!(decrypted, plain);
+}
+
+#[test]
+fn space_as_rejects_alphabet_characters() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "y = x\nb = c\n");
+
+    let message = stderr(cipher(&["-a", path(&key), "--space-as", "x", "-e", "y b"]));
+    assert!(message.contains("--space-as"), "{}", message);
+    let message = stderr(cipher(&["-a", path(&key), "--space-as", "y", "-e", "b b"]));
+    assert!(message.contains("--space-as"), "{}", message);
+}
+
+#[test]
+fn space_as_rejects_character_from_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let message = stderr(cipher(&["-a", path(&key), "--space-as", "_", "-e", "а_б в"]));
+    assert!(message.contains("символ 2"), "{}", message);
+}
+
+#[test]
+fn report_unmapped_covers_whole_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "table.csv", "аz,x\nбzq,y\n");
+    let report = dir.path().join("report.txt");
+
+    let args = ["-a", path(&key), "-i", path(&input), "--field", "1", "--delimiter", ",", "--report-unmapped-to", path(&report)];
+    stdout(cipher(&args));
+    assert_eq!(fs::read_to_string(&report).unwrap(), "q\t1\nz\t2\n");
+}
+
+#[test]
+fn report_unmapped_counts_rounds_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let report = dir.path().join("report.txt");
+
+    stdout(cipher(&["-a", path(&key), "--rounds", "3", "--report-unmapped-to", path(&report), "-e", "а!б!"]));
+    assert_eq!(fs::read_to_string(&report).unwrap(), "!\t2\n");
+}
+
+#[test]
+fn max_unmapped_below_threshold_succeeds() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--max-unmapped", "2", "-e", "а!б?"])), "б!а?");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--max-unmapped-pct", "50", "-e", "а!б?"])), "б!а?");
+}
+
+#[test]
+fn max_unmapped_above_threshold_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let message = stderr(cipher(&["-a", path(&key), "--max-unmapped", "1", "-e", "а!б?"]));
+    assert!(message.contains("2 из 4"), "{}", message);
+    let message = stderr(cipher(&["-a", path(&key), "--max-unmapped-pct", "49.9", "-e", "а!б?"]));
+    assert!(message.contains("50.0%"), "{}", message);
+}
+
+#[test]
+fn max_unmapped_counts_all_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "table.csv", "аz,x\nбz,y\n");
+
+    let args = ["-a", path(&key), "-i", path(&input), "--field", "1", "--delimiter", ",", "--max-unmapped", "1"];
+    let message = stderr(cipher(&args));
+    assert!(message.contains("2 из 4"), "{}", message);
+}
+
+#[test]
+fn per_char_stats_counts_repeats_once_over_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "table.csv", "аав,x\nаб,y\n");
+
+    let args = ["-a", path(&key), "-i", path(&input), "--field", "1", "--delimiter", ",", "--per-char-stats"];
+    let output = cipher(&args);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Подстановки по числу срабатываний:\n  'а' -> 'б': 3\n  'б' -> 'а': 1\n  'в' -> 'г': 1\n"
+    );
+}
+
+#[test]
+fn rounds_equal_to_order_give_identity() {
+    let dir = tempfile::tempdir().unwrap();
+    // Цикл длины 3 и цикл длины 2: порядок 6
+    let key = write(dir.path(), "key.txt", "а = б\nб = в\nв = а\nг = д\nд = г\n");
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--rounds", "6", "-e", "абвгд"])), "абвгд");
+    let reduced = stdout(cipher(&["-a", path(&key), "--rounds", "4", "-e", "абвгд"]));
+    let huge = stdout(cipher(&["-a", path(&key), "--rounds", "1000000000000", "-e", "абвгд"]));
+    assert_eq!(huge, reduced);
+    assert_eq!(huge, "бвагд");
+}
+
+#[test]
+fn rounds_shortcut_keeps_checks() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let message = stderr(cipher(&["-a", path(&key), "--rounds", "2", "--max-unmapped", "0", "-e", "а!"]));
+    assert!(message.contains("1 из 2"), "{}", message);
+    let message = stderr(cipher(&["-a", path(&key), "--rounds", "2", "--fail-on-passthrough", "-e", "а!"]));
+    assert!(message.contains("'!'"), "{}", message);
+
+    let output = cipher(&["-a", path(&key), "--rounds", "2", "--trace", "-e", "а"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn cycle_alphabets_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = write(dir.path(), "first.txt", "а = б\nб = в\nв = а\n");
+    let second = write(dir.path(), "second.txt", "а = в\nб = а\nв = б\n");
+    let plain = "ааа бвб";
+
+    let encrypted = stdout(cipher(&["-e", plain, "--cycle-alphabets", path(&first), path(&second)]));
+    assert_eq!(encrypted, "бвб вбв");
+    let decrypted = stdout(cipher(&["-d", &encrypted, "--cycle-alphabets", path(&first), path(&second)]));
+    assert_eq!(decrypted, plain);
+}
+
+#[test]
+fn allow_empty_value_deletes_character() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\n- =\n");
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--allow-empty-value", "-e", "а-а-б"])), "ббб");
+    let message = stderr(cipher(&["-a", path(&key), "-e", "а-а"]));
+    assert!(message.contains("пустое значение"), "{}", message);
+}
+
+#[test]
+fn allow_empty_value_conflicts_with_positional_modes() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\n- =\n");
+
+    let message = stderr(cipher(&["--allow-empty-value", "-e", "а", "--cycle-alphabets", path(&key), path(&key)]));
+    assert!(message.contains("--allow-empty-value"), "{}", message);
+    let message = stderr(cipher(&[
+        "--allow-empty-value",
+        "--alternating",
+        "--alphabet-even",
+        path(&key),
+        "--alphabet-odd",
+        path(&key),
+        "-e",
+        "а",
+    ]));
+    assert!(message.contains("--allow-empty-value"), "{}", message);
+}
+
+#[test]
+fn long_key_warning_and_silencing() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "th = @\nb = c\n");
+
+    let output = cipher(&["-a", path(&key), "-e", "tb"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "@c\n");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("строка 1: ключ 'th' длиннее одного символа, используется только 't'"));
+
+    for flag in ["--no-long-key-warn", "--no-prefix-warn"] {
+        let output = cipher(&["-a", path(&key), flag, "-e", "tb"]);
+        assert!(output.status.success());
+        assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+#[test]
+fn long_key_does_not_take_precedence_over_its_prefix() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "t = 1\nth = @\n");
+
+    let message = stderr(cipher(&["-a", path(&key), "-e", "the"]));
+    assert!(message.contains("Строка 2: дублирующийся ключ 't'"), "{}", message);
+}
+
+#[test]
+fn dry_validate_reports_writable_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let output = dir.path().join("out.txt");
+
+    let report = stdout(cipher(&["-a", path(&key), "-o", path(&output), "--dry-validate", "-e", "аб"]));
+    assert!(report.contains("Вывод: ОК"), "{}", report);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1, "проверка не должна оставлять файлов");
+}
+
+#[cfg(unix)]
+#[test]
+fn dry_validate_checks_read_only_directory_by_writing() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let locked = dir.path().join("locked");
+    fs::create_dir(&locked).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+
+    // Администратор пишет и в каталог без права записи: тогда проверка
+    // должна пройти, хотя атрибуты каталога говорят об обратном
+    let writable = fs::write(locked.join("probe"), "").is_ok();
+    let _ = fs::remove_file(locked.join("probe"));
+
+    let output = cipher(&["-a", path(&key), "-o", path(&locked.join("out.txt")), "--dry-validate", "-e", "аб"]);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.success(), writable, "{}", report);
+    if writable {
+        assert!(report.contains("Вывод: ОК"), "{}", report);
+    } else {
+        assert!(report.contains("Вывод: ОШИБКА: Нет доступа на запись к каталогу"), "{}", report);
+    }
+    assert_eq!(fs::read_dir(&locked).unwrap().count(), 0);
+}
+
+#[test]
+fn mapped_ratio_below_threshold_sets_exit_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let args = ["-a", path(&key), "--count-only-mapped-ratio", "--warn-ratio", "0.9", "--fail-ratio", "0.6", "-e", "аб!?"];
+    let output = cipher(&args);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "0.5000\n");
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn split_lines_writes_numbered_parts() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "а\nб\nв\nг\nаб\n");
+    let template = dir.path().join("part_{n}.txt");
+
+    stdout(cipher(&["-a", path(&key), "-i", path(&input), "--split-lines", "2", "--output-template", path(&template), "-e"]));
+
+    let part = |n: usize| fs::read_to_string(dir.path().join(format!("part_{}.txt", n))).unwrap();
+    assert_eq!(part(1), "б\nа\n");
+    assert_eq!(part(2), "г\nв\n");
+    assert_eq!(part(3), "ба\n");
+    assert!(!dir.path().join("part_4.txt").exists());
+}
+
+#[test]
+fn output_template_requires_placeholder() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "а\n");
+
+    let message = stderr(cipher(&["-a", path(&key), "-i", path(&input), "--split-lines", "2", "--output-template", "part.txt", "-e"]));
+    assert!(message.contains("{n}"), "{}", message);
+}
+
+#[test]
+fn seeded_generate_is_golden() {
+    let output = stdout(cipher(&["--generate", "--charset", "абвгдеж", "--seed", "42"]));
+    assert_eq!(output, "а = д\nб = в\nв = г\nг = е\nд = ж\nе = б\nж = а");
+}
+
+#[test]
+fn suggested_key_parses_as_alphabet() {
+    let dir = tempfile::tempdir().unwrap();
+    let secret = write(dir.path(), "secret.txt", "Xxxx yyy zz w");
+    let key = dir.path().join("guess.txt");
+
+    stdout(cipher(&["--suggest-key", "--lang", "en", "-i", path(&secret), "-o", path(&key)]));
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-d", "-i", path(&secret)])), "Eeee ttt aa o");
+}
+
+#[test]
+fn dump_is_byte_identical_across_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    // Достаточно символов, чтобы порядок обхода HashMap почти наверняка менялся
+    let pairs: String = ('а'..='я').zip(('а'..='я').rev()).map(|(key, value)| format!("{} = {}\n", key, value)).collect();
+    let key = write(dir.path(), "key.txt", &pairs);
+
+    for flag in ["--dump", "--info", "--cycles"] {
+        let first = cipher(&["-a", path(&key), flag]);
+        let second = cipher(&["-a", path(&key), flag]);
+        assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stderr));
+        assert_eq!(first.stdout, second.stdout, "{}", flag);
+    }
+    let dump = stdout(cipher(&["-a", path(&key), "--dump"]));
+    assert!(dump.starts_with("а = я\nб = ю\n"), "{}", dump);
+}
+
+/// Имена этапов из таблицы --profile без чтения, загрузки и записи
+fn profiled_stages(output: Output) -> Vec<String> {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|stage| !["read", "load", "write", "Итого"].contains(stage))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn pipeline_stages_run_in_order_and_reverse_on_decrypt() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let plain = "абвгг ааа вб";
+    let stages = [
+        "-a", path(&key), "--reverse-output", "--salt-output", "3", "--shuffle-blocks", "4", "--seed", "5",
+        "--insert-nulls", "0.5", "--null-set", "#", "--char-delimiter", "|", "--rle", "--numeric",
+    ];
+
+    let encrypted = stdout(cipher(&[&stages[..], &["-e", plain]].concat()));
+    assert!(encrypted.split(' ').all(|code| code.len() == 4), "{}", encrypted);
+    let decrypted = stdout(cipher(&[&stages[..], &["--strip-nulls", "-d", &encrypted]].concat()));
+    assert_eq!(decrypted, plain);
+
+    assert_eq!(
+        profiled_stages(cipher(&[&stages[..], &["--profile", "-e", plain]].concat())),
+        ["cipher", "reverse", "salt", "shuffle", "nulls", "delimit", "rle", "encode"]
+    );
+    assert_eq!(
+        profiled_stages(cipher(&[&stages[..], &["--strip-nulls", "--profile", "-d", &encrypted]].concat())),
+        ["decode", "unrle", "undelimit", "strip-nulls", "unshuffle", "unsalt", "reverse", "cipher"]
+    );
+}
+
+#[test]
+fn reverse_rle_and_numeric_apply_after_cipher() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let args = ["-a", path(&key), "--reverse-output", "--rle", "--numeric"];
+
+    // "ааааааб" → "бббббба" → "абббббб" → "аб×6×"
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "ааааааб"]].concat()));
+    assert_eq!(encrypted, "0430 0431 00D7 0036 00D7");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "ааааааб");
+}
+
+#[test]
+fn bifid_published_example() {
+    let args = ["--bifid", "--key", "phqgmeaylnofdxkrcvszwbuti", "--period", "5"];
+    assert_eq!(stdout(cipher(&[&args[..], &["-e", "defend the east wall of the castle"]].concat())), "FFYHMK HYC PLIA SHAD TR LHC CHLBLR");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", "FFYHMKHYCPLIASHADTRLHCCHLBLR"]].concat())), "DEFENDTHEEASTWALLOFTHECASTLE");
+}
+
+#[test]
+fn printed_seed_reproduces_generated_alphabet() {
+    let output = cipher(&["--generate", "--charset", "абвгдежзийклмноп"]);
+    assert!(output.status.success());
+    let message = String::from_utf8(output.stderr).unwrap();
+    let seed = message
+        .split("--seed ")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .unwrap_or_else(|| panic!("нет начального значения в {}", message));
+
+    let repeated = cipher(&["--generate", "--charset", "абвгдежзийклмноп", "--seed", seed]);
+    assert!(repeated.status.success());
+    assert_eq!(repeated.stdout, output.stdout);
+    assert!(String::from_utf8(repeated.stderr).unwrap().contains(&format!("--seed {}", seed)));
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn input_url_is_fetched_from_server() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/secret.txt", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        reader.read_line(&mut request).unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        let body = "аб вг";
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        request
+    });
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-i", &url, "-e"])), "ба гв");
+    assert!(server.join().unwrap().starts_with("GET /secret.txt "));
+}
+
+#[test]
+fn header_accepts_matching_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    // Тот же ключ в другом порядке и с комментарием
+    let same = write(dir.path(), "same.txt", "# копия\nг = в\nв = г\nб = а\nа = б\n");
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "--header", "-e", "аб вг"]));
+    assert!(encrypted.starts_with("#cipher/1 mode=substitution key="), "{}", encrypted);
+    assert_eq!(stdout(cipher(&["-a", path(&same), "--header", "-d", &encrypted])), "аб вг");
+}
+
+#[test]
+fn header_rejects_mismatching_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let other = write(dir.path(), "other.txt", "а = в\nв = а\n");
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "--header", "-e", "аб"]));
+    let message = stderr(cipher(&["-a", path(&other), "--header", "-d", &encrypted]));
+    assert!(message.contains("текст зашифрован другим ключом"), "{}", message);
+}
+
+#[test]
+fn bom_is_stripped_from_key_and_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", &format!("\u{feff}{}", KEY));
+    let input = write(dir.path(), "in.txt", "\u{feff}аб");
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-e", "аб"])), "ба");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-i", path(&input), "-e"])), "ба");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-i", path(&input), "--keep-bom", "-e"])), "\u{feff}ба");
+}
+
+#[test]
+fn only_class_letters_on_mixed_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = а\n1 = 2\n2 = 1\n! = ?\n");
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "--only-class", "letters", "-e", "а1!б2"]));
+    assert_eq!(encrypted, "б1!а2");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--only-class", "letters", "-d", &encrypted])), "а1!б2");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--only-class", "letters", "--only-class", "digits", "-e", "а1!"])), "б2!");
+}
+
+#[cfg(feature = "locking")]
+#[test]
+fn concurrent_appends_do_not_interleave() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let log = dir.path().join("log.txt");
+
+    // Длинные строки пишутся несколькими вызовами write, поэтому без
+    // блокировки строки разных процессов перемешались бы
+    let threads: Vec<_> = (0..8)
+        .map(|thread| {
+            let (key, log) = (key.clone(), log.clone());
+            std::thread::spawn(move || {
+                let text = format!("{}{}", "а".repeat(20_000), thread);
+                for _ in 0..5 {
+                    stdout(cipher(&["-a", path(&key), "-o", path(&log), "--append", "-e", &text]));
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let content = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 40);
+    for line in lines {
+        let (letters, thread) = line.split_at(line.len() - 1);
+        assert_eq!(letters, "б".repeat(20_000));
+        assert!(thread.chars().all(|c| c.is_ascii_digit()), "{}", thread);
+    }
+}
+
+#[test]
+fn preview_truncates_with_ellipsis() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", &"абвг".repeat(10_000));
+    let output = dir.path().join("out.txt");
+
+    for mode in [&[][..], &["--stream", "--buffer-size", "16"][..]] {
+        let args = &[mode, &["-a", path(&key), "-i", path(&input), "-o", path(&output)]].concat();
+        assert_eq!(stdout(cipher(&[&args[..], &["--preview", "6", "-e"]].concat())), "багвба…");
+        assert!(!output.exists());
+    }
+
+    // Текст не длиннее ограничения выводится целиком, без многоточия
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--preview", "4", "-e", "абвг"])), "багв");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--preview", "5", "-e", "абвг"])), "багв");
+}
+
+#[test]
+fn keyword_generate_writes_mixed_alphabet() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = dir.path().join("key.txt");
+
+    let charset = ["--charset", "abcdefghijklmnopqrstuvwxyz"];
+    stdout(cipher(&[&charset[..], &["--generate", "--keyword", "zebras", "-o", path(&key)]].concat()));
+    let content = fs::read_to_string(&key).unwrap();
+    let mixed: String = content.lines().map(|line| line.chars().last().unwrap()).collect();
+    assert_eq!(mixed, "zebrascdfghijklmnopqtuvwxy");
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-e", "flee at once"])), "siaa zq lkba");
+}
+
+#[test]
+fn input_glob_mirrors_nested_tree() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let docs = dir.path().join("docs");
+    fs::create_dir_all(docs.join("sub/deep")).unwrap();
+    write(&docs, "a.txt", "аб");
+    write(&docs, "sub/b.txt", "вг");
+    write(&docs, "sub/deep/c.txt", "ба");
+    write(&docs, "sub/notes.md", "аб");
+    let out = dir.path().join("out");
+    let pattern = format!("{}/**/*.txt", docs.display());
+
+    let message = stdout(cipher(&["-a", path(&key), "--input-glob", &pattern, "-o", path(&out), "-e"]));
+    assert!(message.starts_with("Обработано файлов: 3, пропущено: 0"), "{}", message);
+    assert_eq!(fs::read_to_string(out.join("a.txt")).unwrap(), "ба");
+    assert_eq!(fs::read_to_string(out.join("sub/b.txt")).unwrap(), "гв");
+    assert_eq!(fs::read_to_string(out.join("sub/deep/c.txt")).unwrap(), "аб");
+    assert!(!out.join("sub/notes.md").exists());
+}
+
+#[test]
+fn input_glob_skips_unreadable_files_unless_fail_fast() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let docs = dir.path().join("docs");
+    fs::create_dir_all(docs.join("sub")).unwrap();
+    write(&docs, "a.txt", "аб");
+    fs::write(docs.join("sub/broken.txt"), [0xff, 0xfe, 0x00]).unwrap();
+    let pattern = format!("{}/**/*.txt", docs.display());
+    let args = ["-a", path(&key), "--input-glob", &pattern, "-e"];
+
+    let out = dir.path().join("out");
+    let output = cipher(&[&args[..], &["-o", path(&out)]].concat());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Файл пропущен"));
+    assert!(stdout(output).starts_with("Обработано файлов: 1, пропущено: 1"));
+    assert_eq!(fs::read_to_string(out.join("a.txt")).unwrap(), "ба");
+
+    let strict = dir.path().join("strict");
+    stderr(cipher(&[&args[..], &["-o", path(&strict), "--fail-fast"]].concat()));
+}
+
+#[test]
+fn marker_round_trips_through_decrypt_auto() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let args = ["-a", path(&key), "--marker", "#зашифровано"];
+
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "абвг"]].concat()));
+    assert_eq!(encrypted, "#зашифровано\nбагв");
+    assert_eq!(stdout(cipher(&[&args[..], &["--decrypt-auto", &encrypted]].concat())), "абвг");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "абвг");
+}
+
+#[test]
+fn decrypt_auto_without_marker_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let message = stderr(cipher(&["-a", path(&key), "--marker", "#зашифровано", "--decrypt-auto", "багв"]));
+    assert!(message.contains("не начинается с маркера '#зашифровано'"), "{}", message);
+}
+
+#[test]
+fn collapse_whitespace_before_cipher() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "аб \t\n  вг\r\n\r\nа");
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-i", path(&input), "--collapse-whitespace", "-e"])), "ба гв б");
+    let message = stderr(cipher(&["-a", path(&key), "--collapse-whitespace", "--strip-whitespace", "-e", "аб"]));
+    assert!(message.contains("--strip-whitespace"), "{}", message);
+}
+
+#[test]
+fn json_values_keep_document_structure() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.json", r#"{"а": "аб", "в": [1, "вг", {"г": ["а", false, null]}]}"#);
+
+    let output = stdout(cipher(&["-a", path(&key), "-i", path(&input), "--json-values", "-e"]));
+    let document: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(document, serde_json::json!({"а": "ба", "в": [1, "гв", {"г": ["б", false, null]}]}));
+
+    let output = stdout(cipher(&["-a", path(&key), "-i", path(&input), "--json-values", "--json-keys", "-e"]));
+    let document: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(document, serde_json::json!({"б": "ба", "г": [1, "гв", {"в": ["б", false, null]}]}));
+}
+
+#[test]
+fn positional_words_are_joined_with_spaces() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-e", "аб", "вг", "а"])), "ба гв б");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-d", "ба", "гв", "б"])), "аб вг а");
+
+    let input = write(dir.path(), "in.txt", "аб");
+    stderr(cipher(&["-a", path(&key), "-i", path(&input), "-e", "аб", "вг"]));
+}
+
+#[test]
+fn nulls_round_trip_with_fixed_seed() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let args = ["-a", path(&key), "--null-set", "xyz"];
+    let text = "абвг аб вг".repeat(10);
+
+    let encrypt = [&args[..], &["--insert-nulls", "0.3", "--seed", "11", "-e", &text]].concat();
+    let encrypted = stdout(cipher(&encrypt));
+    assert_eq!(encrypted, stdout(cipher(&encrypt)));
+    assert!(encrypted.contains(['x', 'y', 'z']), "{}", encrypted);
+    assert_eq!(stdout(cipher(&[&args[..], &["--strip-nulls", "-d", &encrypted]].concat())), text);
+}
+
+/// Запуск с текстом `input` на стандартном вводе
+fn cipher_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cipher"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn stdin_combined_reads_alphabet_and_text() {
+    let output = cipher_with_stdin(&["--stdin-combined", "-e"], &format!("{}\nабвг\nга\n", KEY));
+    assert_eq!(stdout(output), "багв\nвб\n");
+
+    let message = stderr(cipher_with_stdin(&["--stdin-combined", "-e"], KEY));
+    assert!(message.contains("нет пустой строки"), "{}", message);
+}
+
+#[test]
+fn info_lists_fixed_points() {
+    let dir = tempfile::tempdir().unwrap();
+    let mixed = write(dir.path(), "mixed.txt", "а = б\nб = а\nв = в\n");
+    let free = write(dir.path(), "free.txt", KEY);
+
+    assert!(stdout(cipher(&["-a", path(&mixed), "--info"])).contains("Неподвижные символы (1): 'в'"));
+    assert!(stdout(cipher(&["-a", path(&free), "--info"])).contains("Неподвижных символов нет"));
+}
+
+#[test]
+fn exclude_chars_pass_through_without_changing_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--exclude-chars", "ав", "-e", "абвг"])), "аавв");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-e", "абвг"])), "багв");
+}
+
+#[test]
+fn alternating_round_trip_and_position_counting() {
+    let dir = tempfile::tempdir().unwrap();
+    let even = write(dir.path(), "even.txt", "а = б\nб = в\nв = а\n");
+    let odd = write(dir.path(), "odd.txt", "а = в\nб = а\nв = б\n");
+    let args = ["--alternating", "--alphabet-even", path(&even), "--alphabet-odd", path(&odd)];
+
+    // По умолчанию пробел занимает позицию, и второе слово начинается с нечетной
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "аа аа"]].concat()));
+    assert_eq!(encrypted, "бв вб");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "аа аа");
+
+    let skipping = [&args[..], &["--skip-unmapped-positions"]].concat();
+    let encrypted = stdout(cipher(&[&skipping[..], &["-e", "аа аа"]].concat()));
+    assert_eq!(encrypted, "бв бв");
+    assert_eq!(stdout(cipher(&[&skipping[..], &["-d", &encrypted]].concat())), "аа аа");
+}
+
+#[test]
+fn detect_format_reports_each_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let cases = [
+        ("key.txt", KEY, "key-value"),
+        ("aligned.txt", "абвг\nбагв\n", "aligned"),
+        ("key.json", r#"{"а": "б", "б": "а"}"#, "json"),
+    ];
+
+    for (name, content, format) in cases {
+        let key = write(dir.path(), name, content);
+        let output = stdout(cipher(&["-a", path(&key), "--detect-format"]));
+        assert!(output.contains(format), "{}: {}", name, output);
+        assert_eq!(stdout(cipher(&["-a", path(&key), "-e", "аб"])), "ба", "{}", name);
+    }
+
+    let csv = write(dir.path(), "key.csv", "а,б\nб,а\n");
+    assert_eq!(stdout(cipher(&["-a", path(&csv), "--alphabet-format", "csv", "-e", "аб"])), "ба");
+}
+
+#[test]
+fn word_mode_keeps_punctuation() {
+    let dir = tempfile::tempdir().unwrap();
+    let dictionary = write(dir.path(), "words.txt", "привет = здравствуй\nмир = свет\n");
+    let args = ["-a", path(&dictionary), "--word-mode", "--preserve-case"];
+
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "Привет, мир! (мир)"]].concat()));
+    assert_eq!(encrypted, "Здравствуй, свет! (свет)");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "Привет, мир! (мир)");
+}
+
+#[test]
+fn coverage_of_small_corpus() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let corpus = write(dir.path(), "corpus.txt", "аааббв xx!");
+
+    let output = stdout(cipher(&["-a", path(&key), "--coverage", path(&corpus)]));
+    assert_eq!(
+        output,
+        "Различных символов в корпусе: 6\n\
+         Покрыто алфавитом: 3 (50.0%)\n\
+         Не покрыто: 3\n\
+         Самые частые символы без подстановки:\n  'x'  2\n  ' '  1\n  '!'  1"
+    );
+}
+
+#[test]
+fn append_twice_with_custom_separator() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "вг");
+
+    for stream in [&[][..], &["--stream"][..]] {
+        for (trailing, expected) in [(&[][..], "ба\n---\nгв\n---\n"), (&["--no-trailing-separator"][..], "ба\n---\nгв")] {
+            let log = dir.path().join("log.txt");
+            let _ = fs::remove_file(&log);
+            let args = [&["-a", path(&key), "-o", path(&log), "--append", "--append-separator", "\\n---\\n"][..], trailing].concat();
+
+            stdout(cipher(&[&args[..], &["-e", "аб"]].concat()));
+            stdout(cipher(&[&args[..], stream, &["-i", path(&input), "-e"]].concat()));
+            assert_eq!(fs::read_to_string(&log).unwrap(), expected, "{:?} {:?}", stream, trailing);
+        }
+    }
+}
+
+#[test]
+fn same_password_generates_same_alphabet() {
+    let generate = |password| stdout(cipher(&["--generate", "--password", password]));
+
+    let first = generate("корова");
+    assert_eq!(first, generate("корова"));
+    assert_ne!(first, generate("коровы"));
+}
+
+#[test]
+fn numeric_decode_rejects_invalid_codepoints() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    for token in ["D800", "110000"] {
+        let message = stderr(cipher(&["-a", path(&key), "--numeric", "-d", &format!("0430 {}", token)]));
+        assert!(message.contains(&format!("Код 2 '{}'", token)), "{}", message);
+    }
+}
+
+#[test]
+fn tabular_aligns_multi_line_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "а\nабвг\nаб\n");
+
+    let output = stdout(cipher(&["-a", path(&key), "-i", path(&input), "--tabular", "-e"]));
+    assert_eq!(output, "а    | б\nабвг | багв\nаб   | ба\n");
+    let separated = stdout(cipher(&["-a", path(&key), "-i", path(&input), "--tabular", "--column-separator", " -> ", "-e"]));
+    assert_eq!(separated.lines().nth(1), Some("абвг -> багв"));
+}
+
+#[test]
+fn normalize_eol_input_on_crlf_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "аб\r\nвг\rа\r\n");
+    let args = ["-a", path(&key), "-i", path(&input)];
+
+    assert_eq!(stdout(cipher(&[&args[..], &["-e"]].concat())), "ба\r\nгв\rб\r\n");
+    assert_eq!(stdout(cipher(&[&args[..], &["--normalize-eol-input", "-e"]].concat())), "ба\nгв\nб\n");
+    assert_eq!(
+        stdout(cipher(&[&args[..], &["--normalize-eol-input", "--line-ending", "crlf", "-e"]].concat())),
+        "ба\r\nгв\r\nб\r\n"
+    );
+}
+
+#[test]
+fn rle_round_trip_is_shorter() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let text = format!("{} {}", "а".repeat(30), "вг".repeat(3));
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "--rle", "--rle-delimiter", "#", "-e", &text]));
+    assert_eq!(encrypted, "б#30# гвгвгв");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--rle", "--rle-delimiter", "#", "-d", &encrypted])), text);
+}
+
+#[test]
+fn map_flags_build_cipher_without_file() {
+    let args = ["--map", "a=x", "--map", "b=y", "--map", "x=a", "--map", "y=b"];
+    assert_eq!(stdout(cipher(&[&args[..], &["-e", "ab"]].concat())), "xy");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", "xy"]].concat())), "ab");
+}
+
+#[test]
+fn map_flags_reject_duplicate_value() {
+    let message = stderr(cipher(&["--map", "a=x", "--map", "b=x", "-e", "ab"]));
+    assert!(message.contains("параметры --map"), "{}", message);
+    assert!(message.contains("Строка 2: дублирующееся значение 'x'"), "{}", message);
+
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    stderr(cipher(&["--map", "a=x", "-a", path(&key), "-e", "ab"]));
+}
+
+#[test]
+fn emit_dot_prints_graph() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = а\n");
+
+    let output = stdout(cipher(&["-a", path(&key), "--emit-dot"]));
+    assert_eq!(output, "digraph cipher {\n    \"а\" -> \"б\";\n    \"б\" -> \"а\";\n}");
+}
+
+#[test]
+fn cycles_prints_cycles_and_order() {
+    let args = ["--map", "a=x", "--map", "x=m", "--map", "m=a", "--map", "b=y", "--map", "y=b", "--cycles"];
+    assert_eq!(stdout(cipher(&args)), "(a x m)(b y)\nПорядок: 6 (столько раундов возвращают исходный текст)");
+}
+
+#[test]
+fn interleave_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let decoy = write(dir.path(), "decoy.txt", "xyz");
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "--interleave", path(&decoy), "-e", "абвг а"]));
+    assert_eq!(encrypted, "бxаyгzвx yбz");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--deinterleave", "-d", &encrypted])), "абвг а");
+}
+
+#[test]
+fn output_case_on_mixed_case_ciphertext() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = а\nА = Б\nБ = А\n");
+    let args = ["-a", path(&key)];
+
+    assert_eq!(stdout(cipher(&[&args[..], &["-e", "аБ вГ"]].concat())), "бА вГ");
+    assert_eq!(stdout(cipher(&[&args[..], &["--output-case", "upper", "-e", "аБ вГ"]].concat())), "БА ВГ");
+    assert_eq!(stdout(cipher(&[&args[..], &["--output-case", "lower", "-e", "аБ вГ"]].concat())), "ба вг");
+    // Регистр меняется до кодирования
+    assert_eq!(stdout(cipher(&[&args[..], &["--output-case", "upper", "--numeric", "-e", "аБ"]].concat())), "0411 0410");
+}
+
+#[test]
+fn limit_memory_chooses_processing_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let small = write(dir.path(), "small.txt", "аб");
+    let large = write(dir.path(), "large.txt", &"аб".repeat(1000));
+
+    for (input, path_name, expected) in [(&small, "в памяти", "ба".to_string()), (&large, "потоковая", "ба".repeat(1000))] {
+        let output = cipher(&["-a", path(&key), "-i", path(input), "--limit-memory", "1024", "--report-path", "-e"]);
+        let report = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(report.contains(&format!("Обработка: {}", path_name)), "{}", report);
+        assert_eq!(stdout(output), expected);
+    }
+}
+
+#[test]
+fn link_case_generates_matching_case_pairs() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = dir.path().join("key.txt");
+
+    stdout(cipher(&["--generate", "--link-case", "--charset", "абвгАБВГ", "--seed", "9", "-o", path(&key)]));
+    let lowercase = stdout(cipher(&["-a", path(&key), "-e", "абвг"]));
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-e", "АБВГ"])), lowercase.to_uppercase());
+
+    let message = stderr(cipher(&["--generate", "--link-case", "--charset", "абв", "--seed", "9"]));
+    assert!(message.contains("нет пар строчных и заглавных букв"), "{}", message);
+}
+
+#[test]
+fn show_hex_annotates_characters() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = а\nв = в\n");
+    let corpus = write(dir.path(), "corpus.txt", "аa\u{a0}");
+    let report = dir.path().join("report.txt");
+
+    let coverage = stdout(cipher(&["-a", path(&key), "--coverage", path(&corpus), "--show-hex"]));
+    assert!(coverage.ends_with("  'a' U+0061  1\n  '\\u{a0}' U+00A0  1"), "{}", coverage);
+    let info = stdout(cipher(&["-a", path(&key), "--info", "--show-hex"]));
+    assert!(info.contains("Неподвижные символы (1): 'в' U+0432"), "{}", info);
+
+    stdout(cipher(&["-a", path(&key), "--report-unmapped-to", path(&report), "--show-hex", "-e", "аa"]));
+    assert_eq!(fs::read_to_string(&report).unwrap(), "a\tU+0061\t1\n");
+}
+
+#[test]
+fn fail_on_passthrough_reports_position() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let message = stderr(cipher(&["-a", path(&key), "--fail-on-passthrough", "-e", "абвгx"]));
+    assert!(message.contains("Символ 5 'x' не имеет подстановки"), "{}", message);
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--fail-on-passthrough", "-e", "абвг"])), "багв");
+}
+
+#[test]
+fn input_radix_hex_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "--input-radix", "16", "-e", "430 431 20 432"]));
+    assert_eq!(encrypted, "431 430 20 433");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--input-radix", "16", "-d", &encrypted])), "430 431 20 432");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--input-radix", "10", "-e", "1072 1073"])), "1073 1072");
+}
+
+#[test]
+fn compare_keys_prints_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = write(dir.path(), "old.txt", "а = б\nб = а\nв = г\nг = в\n");
+    let new = write(dir.path(), "new.txt", "б = а\nа = б\nв = д\nд = в\nе = е\n");
+
+    assert_eq!(
+        stdout(cipher(&["--compare-keys", path(&old), path(&new)])),
+        "~ в = д (было: в = г)\n\
+         - г = в\n\
+         + д = в\n\
+         + е = е\n\
+         Добавлено: 2, удалено: 1, изменено: 1"
+    );
+    assert_eq!(stdout(cipher(&["--compare-keys", path(&old), path(&old)])), "Алфавиты совпадают");
+}
+
+#[test]
+fn otp_round_trip_and_reuse_warning() {
+    let dir = tempfile::tempdir().unwrap();
+    let pad = write(dir.path(), "pad.txt", "XMCKL XMCKL\n");
+    let args = ["--otp", path(&pad), "--charset", "ABCDEFGHIJKLMNOPQRSTUVWXYZ"];
+
+    let output = cipher(&[&args[..], &["-e", "HELLO, WORLD"]].concat());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+    let encrypted = stdout(output);
+    assert_eq!(encrypted, "EQNVZ, TATVO");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "HELLO, WORLD");
+
+    let output = cipher(&[&args[..], &["--rounds", "2", "-e", "HELLO"]].concat());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("блокнот применяется повторно"));
+}
+
+#[test]
+fn several_inputs_are_concatenated_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let inputs = [
+        write(dir.path(), "1.txt", "аб"),
+        write(dir.path(), "2.txt", "вг"),
+        write(dir.path(), "3.txt", "га"),
+    ];
+    let output = dir.path().join("out.txt");
+
+    let args = ["-a", path(&key), "-i", path(&inputs[0]), "-i", path(&inputs[1]), "-i", path(&inputs[2])];
+    stdout(cipher(&[&args[..], &["--append-separator", "\\n--\\n", "-o", path(&output), "-e"]].concat()));
+    assert_eq!(fs::read_to_string(&output).unwrap(), "ба\n--\nгв\n--\nвб");
+    assert_eq!(stdout(cipher(&[&args[..], &["-e"]].concat())), "ба\nгв\nвб");
+}
+
+#[test]
+fn measure_entropy_reports_input_and_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let output = cipher(&["-a", path(&key), "--measure-entropy", "-e", "аабв"]);
+    let report = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(report.contains("Энтропия входного текста: 1.5000 бит/символ"), "{}", report);
+    assert!(report.contains("Энтропия результата: 1.5000 бит/символ"), "{}", report);
+    assert_eq!(stdout(output), "ббаг");
+}
+
+#[test]
+fn reverse_with_substitution_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = в\nв = а\n");
+
+    for (flags, expected) in [
+        (&["--reverse-input"][..], "а бвб"),
+        (&["--reverse-output"][..], "а бвб"),
+        (&["--reverse-input", "--reverse-output"][..], "бвб а"),
+    ] {
+        let args = [&["-a", path(&key)][..], flags].concat();
+        let encrypted = stdout(cipher(&[&args[..], &["-e", "аба в"]].concat()));
+        assert_eq!(encrypted, expected, "{:?}", flags);
+        assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "аба в", "{:?}", flags);
+    }
+}
+
+#[test]
+fn cp1251_input_is_decoded() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = dir.path().join("cp1251.txt");
+    // "абвг аб" в Windows-1251
+    fs::write(&input, [0xe0, 0xe1, 0xe2, 0xe3, 0x20, 0xe0, 0xe1]).unwrap();
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-i", path(&input), "--input-encoding", "cp1251", "-e"])), "багв ба");
+    stderr(cipher(&["-a", path(&key), "-i", path(&input), "-e"]));
+}
+
+#[test]
+fn expanding_map_exceeds_output_cap() {
+    let dir = tempfile::tempdir().unwrap();
+    // Каждый однобайтовый символ заменяется двухбайтовым
+    let key = write(dir.path(), "key.txt", "a = я\nя = a\n");
+    let input = write(dir.path(), "in.txt", &"a".repeat(1000));
+    let output = dir.path().join("out.txt");
+    let args = ["-a", path(&key), "-i", path(&input), "-o", path(&output), "--max-output-size", "1500"];
+
+    let message = stderr(cipher(&[&args[..], &["-e"]].concat()));
+    assert!(message.contains("Результат больше --max-output-size: 2000 байт при ограничении 1500"), "{}", message);
+    assert!(!output.exists());
+
+    // При потоковой обработке запись прекращается до превышения ограничения
+    let message = stderr(cipher(&[&args[..], &["--stream", "--buffer-size", "64", "-e"]].concat()));
+    assert!(message.contains("--max-output-size"), "{}", message);
+    let written = fs::metadata(&output).unwrap().len();
+    assert!(written <= 1500, "{}", written);
+
+    stdout(cipher(&["-a", path(&key), "-i", path(&input), "--max-output-size", "2000", "-e"]));
+}
+
+#[test]
+fn trace_lists_every_character() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = а\nъ =\n");
+
+    let output = cipher(&["-a", path(&key), "--allow-empty-value", "--exclude-chars", "б", "--trace", "-e", "аъ!б"]);
+    let trace = String::from_utf8(output.stderr.clone()).unwrap();
+    assert_eq!(stdout(output), "б!б");
+
+    let lines: Vec<Vec<&str>> = trace.lines().map(|line| line.split('\t').collect()).collect();
+    assert_eq!(lines.len(), 4, "{}", trace);
+    let rules = ["подстановка", "удаление", "нет подстановки", "исключен"];
+    for (index, (fields, rule)) in lines.iter().zip(rules).enumerate() {
+        assert_eq!(fields.len(), 5, "{:?}", fields);
+        assert_eq!(fields[0], "трассировка");
+        assert_eq!(fields[1], (index + 1).to_string());
+        assert_eq!(fields[3], rule);
+    }
+}
+
+#[test]
+fn swap_case_unmapped_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let args = ["-a", path(&key), "--swap-case-unmapped"];
+
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "аZвq, ßгW!"]].concat()));
+    assert_eq!(encrypted, "бzгQ, ßвw!");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "аZвq, ßгW!");
+}
+
+#[test]
+fn include_directive_and_cycle_error() {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "base.txt", "а = б\nб = а\n");
+    let key = write(dir.path(), "key.txt", "@include base.txt\nв = г\nг = в\n");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "-e", "абвг"])), "багв");
+
+    let first = write(dir.path(), "first.txt", "@include second.txt\nа = б\n");
+    write(dir.path(), "second.txt", "@include first.txt\nб = а\n");
+    let message = stderr(cipher(&["-a", path(&first), "-e", "аб"]));
+    assert!(message.contains("Циклическое включение алфавитов"), "{}", message);
+}
+
+#[test]
+fn stats_json_parses_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let output = stdout(cipher(&["-a", path(&key), "--stats-json", "-e", "аб\"\n\"\u{a0}"]));
+    let stats: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(
+        stats,
+        serde_json::json!({
+            "total": 6,
+            "substituted": 2,
+            "unmapped": 4,
+            "unmapped_chars": [
+                {"char": "\n", "codepoint": "U+000A", "count": 1},
+                {"char": "\"", "codepoint": "U+0022", "count": 2},
+                {"char": "\u{a0}", "codepoint": "U+00A0", "count": 1},
+            ]
+        })
+    );
+}
+
+#[test]
+fn canonicalize_keyfile_sorts_unsorted_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let unsorted = "г = в\n# мой ключ\nа = б\n\n   в = г\nб = а\n";
+    let key = write(dir.path(), "key.txt", unsorted);
+
+    stdout(cipher(&["--canonicalize-keyfile", path(&key)]));
+    assert_eq!(fs::read_to_string(&key).unwrap(), "а = б\nб = а\nв = г\nг = в\n");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    let source = write(dir.path(), "source.txt", unsorted);
+    let output = dir.path().join("sorted.txt");
+    stdout(cipher(&["--canonicalize-keyfile", path(&source), "--keep-comments", "-o", path(&output)]));
+    assert_eq!(fs::read_to_string(&output).unwrap(), "# мой ключ\n\nа = б\nб = а\nв = г\nг = в\n");
+    assert_eq!(fs::read_to_string(&source).unwrap(), unsorted);
+}
+
+#[test]
+fn field_two_of_comma_delimited_fixture() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "data.csv", "1,аб,аб\n2,вг,вг\n3\n");
+
+    let output = cipher(&["-a", path(&key), "-i", path(&input), "--field", "2", "--delimiter", ",", "-e"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("оставлено без изменений: 1"));
+    assert_eq!(stdout(output), "1,ба,аб\n2,гв,вг\n3\n");
+}
+
+#[test]
+fn record_modes_round_trip_trailing_newline() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let modes: [&[&str]; 3] = [&[], &["--field", "1", "--delimiter", ","], &["--preserve-indent"]];
+
+    for content in ["аб,в\n  вг,а\n", "аб,в\n  вг,а", "аб\n\n"] {
+        let input = write(dir.path(), "in.txt", content);
+        let encrypted = dir.path().join("encrypted.txt");
+        let decrypted = dir.path().join("decrypted.txt");
+        for mode in modes {
+            let args = [&["-a", path(&key)][..], mode].concat();
+            stdout(cipher(&[&args[..], &["-i", path(&input), "-o", path(&encrypted), "-e"]].concat()));
+            stdout(cipher(&[&args[..], &["-i", path(&encrypted), "-o", path(&decrypted), "-d"]].concat()));
+            assert_eq!(fs::read_to_string(&decrypted).unwrap(), content, "{:?}", mode);
+        }
+    }
+}
+
+#[test]
+fn key_strength_reports_caesar_key_and_fixed_points() {
+    let caesar = stdout(cipher(&[
+        "--map", "а=в", "--map", "б=г", "--map", "в=д", "--map", "г=а", "--map", "д=б",
+        "--charset", "абвгд", "--key-strength",
+    ]));
+    assert!(caesar.contains("Неподвижных символов нет"), "{}", caesar);
+    assert!(caesar.contains("Ключ равносилен шифру Цезаря со сдвигом 2"), "{}", caesar);
+    assert!(caesar.contains("ключ — сдвиг"), "{}", caesar);
+
+    let mostly_fixed = stdout(cipher(&[
+        "--map", "а=б", "--map", "б=а", "--map", "в=в", "--map", "г=г", "--map", "д=д",
+        "--charset", "абвгд", "--key-strength",
+    ]));
+    assert!(mostly_fixed.contains("Неподвижных символов: 3 (в, г, д)"), "{}", mostly_fixed);
+    assert!(mostly_fixed.contains("Ключ не сводится к шифру Цезаря"), "{}", mostly_fixed);
+    assert!(mostly_fixed.contains("3 из 5 символов заменяются сами на себя"), "{}", mostly_fixed);
+    assert!(mostly_fixed.contains("ключ — инволюция"), "{}", mostly_fixed);
+}
+
+#[test]
+fn bundle_decrypts_with_stdin_combined() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let bundle = dir.path().join("bundle.txt");
+
+    let output = cipher(&["-a", path(&key), "--bundle", path(&bundle), "-e", "где вы"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Алфавит и результат сохранены"));
+    assert_eq!(stdout(output), "вде гы");
+
+    let content = fs::read_to_string(&bundle).unwrap();
+    assert!(content.ends_with("\n\nвде гы"), "{:?}", content);
+    assert_eq!(stdout(cipher_with_stdin(&["--stdin-combined", "-d"], &content)), "где вы");
+}
+
+#[test]
+fn generated_pad_has_requested_length_and_charset() {
+    let dir = tempfile::tempdir().unwrap();
+    let pad = dir.path().join("pad.txt");
+    let charset = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    let output = cipher(&["--generate-pad", "50", "--charset", charset, "--seed", "1", "--output", path(&pad)]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("используйте блокнот только один раз"));
+    stdout(output);
+    let content = fs::read_to_string(&pad).unwrap();
+    assert_eq!(content.chars().count(), 50);
+    assert!(content.chars().all(|c| charset.contains(c)), "{}", content);
+
+    let again = stdout(cipher(&["--generate-pad", "50", "--charset", charset, "--seed", "1"]));
+    assert_eq!(again, content);
+
+    let args = ["--otp", path(&pad), "--charset", charset];
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "HELLO, WORLD"]].concat()));
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "HELLO, WORLD");
+}
+
+#[test]
+fn no_passthrough_newlines_reports_embedded_newline() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "аб,в\nг,а\n");
+    let args = ["-a", path(&key), "-i", path(&input), "--no-passthrough-newlines"];
+
+    let message = stderr(cipher(&[&args[..], &["-e"]].concat()));
+    assert!(message.contains("Перевод строки '\\n' в позиции 5"), "{}", message);
+
+    // Поля режутся по строкам, и в самих полях переводов строки нет
+    assert_eq!(stdout(cipher(&[&args[..], &["--field", "1", "--delimiter", ",", "-e"]].concat())), "ба,в\nв,а\n");
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--no-passthrough-newlines", "-e", "абв"])), "баг");
+}
+
+#[test]
+fn invalid_utf8_is_an_error_unless_lossy() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = dir.path().join("in.txt");
+    fs::write(&input, [0xd0, 0xb0, 0xff, 0xd0, 0xb1]).unwrap();
+    let args = ["-a", path(&key), "-i", path(&input)];
+
+    let message = stderr(cipher(&[&args[..], &["-e"]].concat()));
+    assert!(message.contains("Не удалось прочитать входной файл"), "{}", message);
+
+    let output = cipher(&[&args[..], &["--lossy", "-e"]].concat());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("заменено символом U+FFFD: 1"));
+    assert_eq!(stdout(output), "б\u{fffd}а");
+}
+
+#[test]
+fn shuffle_blocks_round_trip_with_fixed_seed() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let text = "абвг вгба гав, бав! гв";
+
+    for partial in ["keep", "include"] {
+        let args = ["-a", path(&key), "--shuffle-blocks", "4", "--partial-block", partial, "--seed", "9"];
+        let encrypted = stdout(cipher(&[&args[..], &["-e", text]].concat()));
+        assert_ne!(encrypted, stdout(cipher(&["-a", path(&key), "-e", text])));
+        assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), text, "{}", partial);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn preserve_metadata_copies_mode_and_mtime() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "абвг");
+    let output = dir.path().join("out.txt");
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    fs::File::options().write(true).open(&input).unwrap().set_modified(modified).unwrap();
+    fs::set_permissions(&input, fs::Permissions::from_mode(0o640)).unwrap();
+    let args = ["-a", path(&key), "-i", path(&input), "-o", path(&output), "-e"];
+
+    stdout(cipher(&[&args[..], &["--preserve-metadata"]].concat()));
+    let metadata = fs::metadata(&output).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+    assert_ne!(metadata.modified().unwrap(), modified);
+
+    fs::remove_file(&output).unwrap();
+    stdout(cipher(&[&args[..], &["--preserve-metadata", "--preserve-mtime"]].concat()));
+    let metadata = fs::metadata(&output).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+    assert_eq!(metadata.modified().unwrap(), modified);
+    assert_eq!(fs::read_to_string(&output).unwrap(), "багв");
+}
+
+#[test]
+fn line_summary_counts_changed_and_unchanged_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", "аб\nxyz\n\nв, г\n123\n");
+
+    let output = cipher(&["-a", path(&key), "-i", path(&input), "--line-summary", "-e"]);
+    let message = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(message.contains("Строк обработано: 5, изменено: 2, без изменений: 3"), "{}", message);
+    assert_eq!(stdout(output), "ба\nxyz\n\nг, в\n123\n");
+}
+
+#[test]
+fn rotor_round_trip_depends_on_position() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = write(dir.path(), "first.txt", "а = б\nб = а\nв = в\n");
+    let second = write(dir.path(), "second.txt", "а = в\nб = а\nв = б\n");
+    let args = ["--rotor", path(&first), path(&second), "--charset", "абв", "--rotor-step", "2"];
+
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "аааааа, бв"]].concat()));
+    assert!(encrypted.chars().take(6).collect::<std::collections::HashSet<_>>().len() > 1, "{}", encrypted);
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "аааааа, бв");
+}
+
+#[test]
+fn all_comments_alphabet_is_rejected_unless_allowed() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "# только комментарии\n\n# и пустые строки\n");
+
+    let message = stderr(cipher(&["-a", path(&key), "-e", "абв"]));
+    assert!(message.contains("не содержит ни одной подстановки"), "{}", message);
+    assert!(message.contains("--allow-empty"), "{}", message);
+
+    let output = cipher(&["-a", path(&key), "--allow-empty", "-e", "абв"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Предупреждение"));
+    assert_eq!(stdout(output), "абв");
+
+    assert_eq!(stdout(cipher(&["-a", path(&key), "--print-alphabet-size"])), "0");
+    let full = write(dir.path(), "full.txt", "а = б\nб = а\nв =\n");
+    assert_eq!(stdout(cipher(&["-a", path(&full), "--allow-empty-value", "--print-alphabet-size"])), "3");
+}
+
+#[test]
+fn verify_roundtrip_file_passes_and_reports_first_difference() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let plain = write(dir.path(), "plain.txt", "абв\nгде\n");
+    let encrypted = write(dir.path(), "cipher.txt", "баг\nвде\n");
+
+    let report = stdout(cipher(&["-a", path(&key), "--verify-roundtrip-file", path(&plain), path(&encrypted)]));
+    assert_eq!(report.lines().count(), 2, "{}", report);
+    assert!(report.lines().all(|line| line.contains("совпадает с")), "{}", report);
+
+    // После смены ключа старый шифротекст больше не воспроизводится
+    let changed = write(dir.path(), "changed.txt", "а = б\nб = а\nв = д\nд = в\n");
+    let output = cipher(&["-a", path(&changed), "--verify-roundtrip-file", path(&plain), path(&encrypted)]);
+    let report = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(report.contains("в символе 3 (строка 1, столбец 3): ожидалось 'г', получено 'д'"), "{}", report);
+    let message = stderr(output);
+    assert!(message.contains("Проверка не пройдена: расхождений 2 из 2"), "{}", message);
+}
+
+#[test]
+fn decrypt_guess_ranks_correct_key_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let shift = |n: u8| -> String {
+        (b'a'..=b'z')
+            .map(|c| format!("{} = {}\n", char::from(c), char::from(b'a' + (c - b'a' + n) % 26)))
+            .collect()
+    };
+    let keys: Vec<PathBuf> = [1, 3, 13, 20]
+        .iter()
+        .map(|&n| write(dir.path(), &format!("shift{}.txt", n), &shift(n)))
+        .collect();
+    let sample = "it was the best of times it was the worst of times it was the age of wisdom";
+    let encrypted = stdout(cipher(&["-a", path(&keys[1]), "-e", sample]));
+
+    let mut args = vec![&encrypted[..], "--decrypt-guess"];
+    args.extend(keys.iter().map(|key| path(key)));
+    let ranking = stdout(cipher(&args));
+    let first = ranking.lines().next().unwrap();
+    assert!(first.starts_with(&format!("1. {} (хи-квадрат", path(&keys[1]))), "{}", ranking);
+    assert!(first.ends_with("): it was the best of times it was the worst of times it was th…"), "{}", ranking);
+    assert_eq!(ranking.lines().count(), 4);
+}
+
+#[test]
+fn preserve_indent_keeps_indentation_byte_identical() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let content = "где {\n    а = б;\n\tif в {\n\t    г();\r\n\t}\n}\n";
+    let input = write(dir.path(), "in.txt", content);
+
+    let encrypted = stdout(cipher(&["-a", path(&key), "-i", path(&input), "--preserve-indent", "-e"]));
+    assert_eq!(encrypted, "вде {\n    б = а;\n\tif г {\n\t    в();\r\n\t}\n}\n");
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    for (original, result) in content.split('\n').zip(encrypted.split('\n')) {
+        assert_eq!(original.as_bytes()[..indent(original)], result.as_bytes()[..indent(result)]);
+    }
+}
+
+#[test]
+fn salt_output_is_stripped_on_decrypt() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let args = ["-a", path(&key), "--salt-output", "5"];
+
+    let first = stdout(cipher(&[&args[..], &["--seed", "1", "-e", "где вы"]].concat()));
+    assert_eq!(first.chars().count(), 5 + 6);
+    assert!(first.ends_with("вде гы"), "{}", first);
+    assert!(first.chars().take(5).all(|c| "абвг".contains(c)), "{}", first);
+    assert_eq!(stdout(cipher(&[&args[..], &["--seed", "1", "-e", "где вы"]].concat())), first);
+
+    let second = stdout(cipher(&[&args[..], &["--seed", "2", "-e", "где вы"]].concat()));
+    assert_ne!(second, first);
+    for encrypted in [&first, &second] {
+        assert_eq!(stdout(cipher(&[&args[..], &["-d", encrypted]].concat())), "где вы");
+    }
+
+    let message = stderr(cipher(&[&args[..], &["-d", "аб"]].concat()));
+    assert!(message.contains("Текст короче соли"), "{}", message);
+}
+
+#[test]
+fn mapped_ratio_gate_exit_code_per_tier() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let args = ["-a", path(&key), "--count-only-mapped-ratio", "--warn-ratio", "0.9", "--fail-ratio", "0.5"];
+
+    for (text, ratio, code) in [("абвг", "1.0000", 0), ("абвгxy", "0.6667", 2), ("аxyz", "0.2500", 3)] {
+        let output = cipher(&[&args[..], &["-e", text]].concat());
+        assert_eq!(output.status.code(), Some(code), "{}", text);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\n", ratio));
+    }
+
+    let custom = [&args[..], &["--warn-exit-code", "10", "--fail-exit-code", "20"]].concat();
+    assert_eq!(cipher(&[&custom[..], &["-e", "абвгxy"]].concat()).status.code(), Some(10));
+    assert_eq!(cipher(&[&custom[..], &["-e", "аxyz"]].concat()).status.code(), Some(20));
+
+    let message = stderr(cipher(&["-a", path(&key), "--count-only-mapped-ratio", "--warn-ratio", "0.5", "--fail-ratio", "0.9", "-e", "аб"]));
+    assert!(message.contains("не может быть больше --warn-ratio"), "{}", message);
+}
+
+#[test]
+fn char_delimiter_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let args = ["-a", path(&key), "--char-delimiter", "·"];
+
+    let encrypted = stdout(cipher(&[&args[..], &["-e", "абв г"]].concat()));
+    assert_eq!(encrypted, "б·а·г· ·в");
+    assert_eq!(stdout(cipher(&[&args[..], &["-d", &encrypted]].concat())), "абв г");
+
+    let message = stderr(cipher(&[&args[..], &["-d", "б·аг"]].concat()));
+    assert!(message.contains("После символа 2 нет разделителя"), "{}", message);
+    let message = stderr(cipher(&[&args[..], &["-e", "а·б"]].concat()));
+    assert!(message.contains("встречается в шифротексте"), "{}", message);
+}
+
+#[test]
+fn auto_format_by_output_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = а\nв = г\nг = в\n");
+    let encrypt_to = |name: &str, extra: &[&str]| {
+        let output = dir.path().join(name);
+        let args = [&["-a", path(&key), "--auto-format", "-o", path(&output)][..], extra, &["-e", "абвг"]].concat();
+        stdout(cipher(&args));
+        fs::read_to_string(&output).unwrap()
+    };
+
+    assert_eq!(encrypt_to("out.b64", &[]), "0LHQsNCz0LI=");
+    assert_eq!(encrypt_to("out.json", &[]), "\"багв\"");
+    assert_eq!(encrypt_to("OUT.JSON", &[]), "\"багв\"");
+    assert!(encrypt_to("out.dot", &[]).starts_with("digraph cipher {\n    \"а\" -> \"б\";\n"));
+    assert_eq!(encrypt_to("out.txt", &[]), "багв");
+    assert_eq!(encrypt_to("out", &[]), "багв");
+    // Явный флаг формата важнее расширения
+    assert_eq!(encrypt_to("numeric.b64", &["--numeric"]), "0431 0430 0433 0432");
+}
+
+#[test]
+fn disjoint_sets_passes_disjoint_and_rejects_overlapping_map() {
+    let dir = tempfile::tempdir().unwrap();
+    let disjoint = write(dir.path(), "disjoint.txt", "а = x\nб = y\n");
+    let overlapping = write(dir.path(), "overlapping.txt", KEY);
+
+    assert_eq!(stdout(cipher(&["-a", path(&disjoint), "--disjoint-sets", "-e", "аб"])), "xy");
+    let message = stderr(cipher(&["-a", path(&overlapping), "--disjoint-sets", "-e", "аб"]));
+    assert!(message.contains("и среди ключей, и среди значений алфавита: 'а', 'б', 'в', 'г'"), "{}", message);
+}
+
+#[test]
+fn long_line_errors_or_is_truncated() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let input = write(dir.path(), "in.txt", &format!("аб\n{}\nвг\n", "а".repeat(100)));
+    let args = ["-a", path(&key), "-i", path(&input), "--max-line-length", "10"];
+
+    let message = stderr(cipher(&[&args[..], &["-e"]].concat()));
+    assert!(message.contains("Строка 2 длиннее --max-line-length: 100 символов, допустимо не более 10"), "{}", message);
+
+    let output = cipher(&[&args[..], &["--truncate-lines", "-e"]].concat());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Строк длиннее 10 символов обрезано: 1"));
+    assert_eq!(stdout(output), format!("ба\n{}\nгв\n", "б".repeat(10)));
+}
+
+#[test]
+fn same_seed_file_generates_same_alphabet() {
+    let dir = tempfile::tempdir().unwrap();
+    let shared = write(dir.path(), "shared.bin", "корова");
+    let copy = write(dir.path(), "copy.bin", "корова");
+    let other = write(dir.path(), "other.bin", "коровы");
+    let generate = |file: &Path| stdout(cipher(&["--generate", "--seed-file", path(file)]));
+
+    let first = generate(&shared);
+    assert_eq!(first, generate(&shared));
+    assert_eq!(first, generate(&copy));
+    assert_ne!(first, generate(&other));
+    // Начальное значение из файла и из пароля с тем же содержимым совпадают
+    assert_eq!(first, stdout(cipher(&["--generate", "--password", "корова"])));
+
+    let message = stderr(cipher(&["--generate", "--seed-file", path(&shared), "--seed", "1"]));
+    assert!(message.contains("cannot be used with"), "{}", message);
+}
+
+#[test]
+fn emit_csv_round_trips_through_csv_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", ", = \"\n\" = ,\nа = б\nб = а\n");
+    let text = "а, \"б\"";
+    let expected = stdout(cipher(&["-a", path(&key), "-e", text]));
+    assert_eq!(expected, "б\" ,а,");
+
+    for header in [&[][..], &["--csv-header"]] {
+        let csv = stdout(cipher(&[&["-a", path(&key), "--emit-csv"][..], header].concat()));
+        let table = write(dir.path(), "key.csv", &csv);
+        let args = [&["-a", path(&table), "--alphabet-format", "csv"][..], header].concat();
+        assert_eq!(stdout(cipher(&[&args[..], &["-e", text]].concat())), expected, "{}", csv);
+    }
+}
+
+#[test]
+fn limited_alphabet_output_equals_full() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "а = б\nб = а\nв = г\nг = в\nд = е\nе = д\nж = з\nз = ж\n");
+    let input = write(dir.path(), "in.txt", "где жаба?\nвдвое\n");
+
+    for direction in ["-e", "-d"] {
+        let args = ["-a", path(&key), "-i", path(&input), direction];
+        let full = stdout(cipher(&args));
+        assert_eq!(stdout(cipher(&[&args[..], &["--limit-alphabet-to-input"]].concat())), full, "{}", direction);
+    }
+
+    let message = stderr(cipher(&["-a", path(&key), "--limit-alphabet-to-input", "--rounds", "2", "-e", "аб"]));
+    assert!(message.contains("несовместим с --rounds"), "{}", message);
+}
+
+#[test]
+fn declared_order_is_used_by_index_based_modes() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", "@order вагб\nв = а\nа = г\nг = б\nб = в\n");
+
+    let report = stdout(cipher(&["-a", path(&key), "--key-strength"]));
+    assert!(report.contains("Ключ равносилен шифру Цезаря со сдвигом 1"), "{}", report);
+    // --charset важнее директивы, и над порядком абвг тот же ключ уже не сдвиг
+    let report = stdout(cipher(&["-a", path(&key), "--charset", "абвг", "--key-strength"]));
+    assert!(report.contains("Ключ не сводится к шифру Цезаря"), "{}", report);
+}
+
+#[test]
+fn generate_rejects_empty_charset() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("key.txt");
+
+    for extra in [&[][..], &["--link-case"], &["--keyword", ""], &["--output", path(&output)]] {
+        let message = stderr(cipher(&[&["--generate", "--charset", ""][..], extra].concat()));
+        assert!(message.contains("Набор символов пуст"), "{:?}: {}", extra, message);
+        assert!(!message.contains("Начальное значение генератора"), "{}", message);
+    }
+    assert!(!output.exists());
+}
+
+#[test]
+fn zero_is_rejected_with_neutral_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+    let rotor = write(dir.path(), "rotor.txt", "а = б\nб = а\n");
+    let input = write(dir.path(), "in.txt", "аб\n");
+    let template = dir.path().join("part{n}.txt");
+
+    let cases: [&[&str]; 7] = [
+        &["-a", path(&key), "--field", "0", "-e", "аб"],
+        &["--generate-pad", "0"],
+        &["-a", path(&key), "--shuffle-blocks", "0", "--seed", "1", "-e", "аб"],
+        &["--rotor", path(&rotor), "--charset", "аб", "--rotor-step", "0", "-e", "аб"],
+        &["-a", path(&key), "-i", path(&input), "--split-lines", "0", "--output-template", path(&template), "-e"],
+        &["-a", path(&key), "--salt-output", "0", "-e", "аб"],
+        &["-a", path(&key), "--max-line-length", "0", "-e", "аб"],
+    ];
+    for args in cases {
+        let message = stderr(cipher(args));
+        assert!(message.contains("значение должно быть положительным целым числом"), "{:?}: {}", args, message);
+    }
+
+    let message = stderr(cipher(&["--bifid", "--period", "0", "-e", "ab"]));
+    assert!(message.contains("период должен быть положительным"), "{}", message);
+}
+
+#[test]
+fn unit_interval_flags_reject_values_outside_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = write(dir.path(), "key.txt", KEY);
+
+    let cases: [&[&str]; 3] = [
+        &["-a", path(&key), "--null-set", "xy", "--insert-nulls", "1.5", "-e", "аб"],
+        &["-a", path(&key), "--count-only-mapped-ratio", "--warn-ratio", "1.5", "-e", "аб"],
+        &["-a", path(&key), "--count-only-mapped-ratio", "--warn-ratio", "0.5", "--fail-ratio=-1", "-e", "аб"],
+    ];
+    for args in cases {
+        let message = stderr(cipher(args));
+        assert!(message.contains("значение должно быть от 0 до 1"), "{:?}: {}", args, message);
+    }
+}